@@ -0,0 +1,54 @@
+//! Records the commit and time dapsd was built at for `/api/version`.
+//! `DAPSD_GIT_COMMIT` and `SOURCE_DATE_EPOCH` override them, for builds
+//! outside a checkout and reproducible builds.
+
+use std::{
+    env, fs,
+    path::Path,
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+fn main() {
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-env-changed=DAPSD_GIT_COMMIT");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+
+    if env::var_os("DAPSD_GIT_COMMIT").is_none() {
+        if let Some(commit) = git_commit() {
+            println!("cargo:rustc-env=DAPSD_GIT_COMMIT={}", commit);
+        }
+    }
+
+    let built_at = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|seconds| seconds.parse::<u64>().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs())
+        });
+    println!("cargo:rustc-env=DAPSD_BUILT_AT={}", built_at);
+}
+
+fn git_commit() -> Option<String> {
+    let git_dir = Path::new(".git");
+    if !git_dir.is_dir() {
+        return None;
+    }
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    if let Ok(head) = fs::read_to_string(git_dir.join("HEAD")) {
+        if let Some(reference) = head.trim().strip_prefix("ref: ") {
+            println!("cargo:rerun-if-changed=.git/{}", reference);
+        }
+    }
+    let output = Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let commit = String::from_utf8(output.stdout).ok()?.trim().to_string();
+    Some(commit).filter(|commit| !commit.is_empty())
+}
@@ -0,0 +1,106 @@
+//! One structured log record per request, in place of tide's default request
+//! logging, labelled with the language and project being served, and with
+//! the client when `--log-clients` is given.
+
+use std::{env, str::FromStr, time::Instant};
+
+use femme::LevelFilter;
+use tide::{log, utils::async_trait, Middleware, Next, Request, Result};
+
+use crate::{proxy, LanguageName, ServerState};
+
+const NONE: &str = "-";
+
+/// Longer `User-Agent`s are cut short, so a client can't flood the log.
+const MAX_USER_AGENT_LEN: usize = 256;
+
+/// Starts the logger at the level named by `RUST_LOG`, such as `debug` or
+/// `warn`, defaulting to `info`.
+pub fn start() {
+    let requested = env::var("RUST_LOG").ok();
+    let parsed = requested.as_deref().map(LevelFilter::from_str);
+    femme::with_level(match parsed {
+        Some(Ok(level)) => level,
+        _ => LevelFilter::Info,
+    });
+    if let Some(Err(_)) = parsed {
+        log::warn!("Ignoring unrecognized RUST_LOG level {:?}", requested);
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct AccessLog;
+
+#[async_trait]
+impl Middleware<ServerState> for AccessLog {
+    async fn handle(&self, req: Request<ServerState>, next: Next<'_, ServerState>) -> Result {
+        let start = Instant::now();
+        let method = req.method().to_string();
+        let path = req.url().path().to_string();
+        let language = LanguageName::from_request(&req).ok();
+        let project = req.param("project_name").ok().map(str::to_string);
+        let client = if req.state().options.log_clients {
+            let ip = proxy::client_ip(&req, &req.state().options.trusted_proxies);
+            Some((ip.map(|ip| ip.to_string()), user_agent(&req)))
+        } else {
+            None
+        };
+
+        let response = next.run(req).await;
+
+        let status = response.status() as u16;
+        let size = response
+            .len()
+            .map_or_else(|| NONE.to_string(), |len| len.to_string());
+        let duration = format!("{:?}", start.elapsed());
+        let error = response.error().map(|e| e.to_string());
+        let level = if status >= 500 {
+            log::Level::Error
+        } else if status >= 400 {
+            log::Level::Warn
+        } else {
+            log::Level::Info
+        };
+        let language = language
+            .as_ref()
+            .map_or(NONE, |language| language.as_str().as_str());
+        let project = project.as_deref().unwrap_or(NONE);
+        let error = error.as_deref().unwrap_or(NONE);
+        match client {
+            Some((ip, user_agent)) => log::log!(level, "Request", {
+                method: method,
+                language: language,
+                project: project,
+                path: path,
+                status: status,
+                size: size,
+                duration: duration,
+                error: error,
+                client: ip.as_deref().unwrap_or(NONE),
+                user_agent: user_agent.as_deref().unwrap_or(NONE),
+            }),
+            None => log::log!(level, "Request", {
+                method: method,
+                language: language,
+                project: project,
+                path: path,
+                status: status,
+                size: size,
+                duration: duration,
+                error: error,
+            }),
+        }
+        Ok(response)
+    }
+}
+
+fn user_agent<State>(req: &Request<State>) -> Option<String> {
+    let user_agent = req.header("user-agent")?.last().as_str().trim();
+    if user_agent.is_empty() {
+        return None;
+    }
+    Some(match user_agent.char_indices().nth(MAX_USER_AGENT_LEN) {
+        Some((end, _)) => format!("{}...", &user_agent[..end]),
+        None => user_agent.to_string(),
+    })
+}
@@ -0,0 +1,33 @@
+//! JSON bodies for `/api` errors, so that clients can handle them all the
+//! same way: `{"error": {"code": 404, "message": "Project not found"}}`.
+//! Served pages keep their usual error responses.
+
+use tide::{http::mime, prelude::*, utils::async_trait, Body, Middleware, Next, Request, Result};
+
+#[derive(Debug, Default)]
+pub struct JsonErrors;
+
+#[async_trait]
+impl<State: Clone + Send + Sync + 'static> Middleware<State> for JsonErrors {
+    async fn handle(&self, req: Request<State>, next: Next<'_, State>) -> Result {
+        let mut response = next.run(req).await;
+        let status = response.status();
+        let already_json = response
+            .content_type()
+            .is_some_and(|content_type| content_type.essence() == mime::JSON.essence());
+        if !(status.is_client_error() || status.is_server_error()) || already_json {
+            return Ok(response);
+        }
+        let message = match response.error() {
+            Some(error) => error.to_string(),
+            None => status.canonical_reason().to_string(),
+        };
+        response.set_body(Body::from_json(&json!({
+            "error": {
+                "code": status as u16,
+                "message": message,
+            }
+        }))?);
+        Ok(response)
+    }
+}
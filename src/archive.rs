@@ -0,0 +1,441 @@
+//! Serving a project straight out of a `.zip`, `.tar.gz` or `.tgz` archive.
+//! Entries are indexed once when the archive is first used and then read one
+//! at a time; a `.tar.gz` has to be decompressed from the start to reach an
+//! entry, but nothing before it is kept in memory.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{self, BufReader, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::inflate::{self, Inflater};
+
+/// The most `read` sets aside for an entry before any of it has been read.
+const MAX_PREALLOCATION: u64 = 64 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    Zip,
+    TarGz,
+}
+
+impl Format {
+    fn detect(path: &Path) -> Option<Format> {
+        let name = path.file_name()?.to_str()?.to_ascii_lowercase();
+        if name.ends_with(".zip") {
+            Some(Format::Zip)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Format::TarGz)
+        } else {
+            None
+        }
+    }
+}
+
+pub fn is_archive(path: &Path) -> bool {
+    Format::detect(path).is_some()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Compression {
+    Stored,
+    Deflated,
+}
+
+#[derive(Debug)]
+pub struct Entry {
+    /// Where the entry's data starts: in the file for zip, and in the
+    /// decompressed stream for tar.
+    offset: u64,
+    compressed_len: u64,
+    compression: Compression,
+    pub len: u64,
+    pub modified: Option<SystemTime>,
+}
+
+#[derive(Debug)]
+pub struct Archive {
+    path: PathBuf,
+    format: Format,
+    /// Identifies the version of the file this index was built from.
+    stamp: (u64, Option<SystemTime>),
+    entries: HashMap<String, Entry>,
+    directories: HashSet<String>,
+}
+
+impl Archive {
+    pub fn open(path: &Path) -> io::Result<Archive> {
+        let format = Format::detect(path)
+            .ok_or_else(|| invalid(format!("{:?} is not a zip or tar.gz archive", path)))?;
+        let file = File::open(path)?;
+        let stamp = stamp(&file.metadata()?);
+        let mut archive = Archive {
+            path: path.to_path_buf(),
+            format,
+            stamp,
+            entries: HashMap::new(),
+            directories: HashSet::new(),
+        };
+        match format {
+            Format::Zip => archive.index_zip(file)?,
+            Format::TarGz => archive.index_tar_gz(file)?,
+        }
+        Ok(archive)
+    }
+
+    /// `name` is a `/` separated path with no leading slash.
+    pub fn entry(&self, name: &str) -> Option<&Entry> {
+        self.entries.get(name)
+    }
+
+    /// The names of every file entry, in no particular order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(String::as_str)
+    }
+
+    /// The root, `""`, is always a directory.
+    pub fn is_directory(&self, name: &str) -> bool {
+        name.is_empty() || self.directories.contains(name)
+    }
+
+    pub fn read(&self, entry: &Entry) -> io::Result<Vec<u8>> {
+        // The length comes from the archive's own headers, so it is only
+        // trusted as far as the data backs it up.
+        let mut contents = Vec::with_capacity(entry.len.min(MAX_PREALLOCATION) as usize);
+        match self.format {
+            Format::Zip => {
+                let mut file = File::open(&self.path)?;
+                file.seek(SeekFrom::Start(entry.offset))?;
+                let data = BufReader::new(file.take(entry.compressed_len));
+                match entry.compression {
+                    Compression::Stored => data.take(entry.len).read_to_end(&mut contents)?,
+                    Compression::Deflated => Inflater::new(data)
+                        .take(entry.len)
+                        .read_to_end(&mut contents)?,
+                };
+            }
+            Format::TarGz => {
+                let mut stream = tar_gz_stream(File::open(&self.path)?)?;
+                io::copy(&mut (&mut stream).take(entry.offset), &mut io::sink())?;
+                stream.take(entry.len).read_to_end(&mut contents)?;
+            }
+        }
+        if contents.len() as u64 != entry.len {
+            return Err(invalid(format!("truncated entry in {:?}", self.path)));
+        }
+        Ok(contents)
+    }
+
+    fn insert(&mut self, name: &str, entry: Option<Entry>) {
+        // Entries that would escape the archive root are never served.
+        let name = match normalize_entry_name(name) {
+            Some(name) => name,
+            None => return,
+        };
+        let mut parent = name.as_str();
+        while let Some((directory, _)) = parent.rsplit_once('/') {
+            self.directories.insert(directory.to_string());
+            parent = directory;
+        }
+        match entry {
+            Some(entry) => {
+                self.entries.insert(name, entry);
+            }
+            None => {
+                self.directories.insert(name);
+            }
+        }
+    }
+
+    fn index_zip(&mut self, mut file: File) -> io::Result<()> {
+        const END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4b50;
+        const CENTRAL_DIRECTORY_HEADER: u32 = 0x0201_4b50;
+        const LOCAL_FILE_HEADER: u32 = 0x0403_4b50;
+
+        // The end record is 22 bytes plus a comment of up to 64K.
+        let len = file.metadata()?.len();
+        let tail_len = len.min(22 + 0xffff);
+        file.seek(SeekFrom::Start(len - tail_len))?;
+        let mut tail = vec![0; tail_len as usize];
+        file.read_exact(&mut tail)?;
+        let end = (0..tail.len().saturating_sub(21))
+            .rev()
+            .find(|&i| u32_at(&tail, i) == END_OF_CENTRAL_DIRECTORY)
+            .map(|i| &tail[i..])
+            .ok_or_else(|| invalid("missing zip end of central directory record".to_string()))?;
+        let entry_count = u16_at(end, 10) as usize;
+        let directory_len = u32_at(end, 12) as u64;
+        let directory_offset = u32_at(end, 16) as u64;
+        if directory_offset == 0xffff_ffff || directory_offset + directory_len > len {
+            return Err(invalid("zip64 archives aren't supported".to_string()));
+        }
+
+        file.seek(SeekFrom::Start(directory_offset))?;
+        let mut directory = vec![0; directory_len as usize];
+        file.read_exact(&mut directory)?;
+        let mut position = 0;
+        for _ in 0..entry_count {
+            let header = directory
+                .get(position..position + 46)
+                .filter(|header| u32_at(header, 0) == CENTRAL_DIRECTORY_HEADER)
+                .ok_or_else(|| invalid("corrupt zip central directory".to_string()))?;
+            let flags = u16_at(header, 8);
+            let method = u16_at(header, 10);
+            let modified = dos_time(u16_at(header, 14), u16_at(header, 12));
+            let compressed_len = u32_at(header, 20) as u64;
+            let uncompressed_len = u32_at(header, 24) as u64;
+            let name_len = u16_at(header, 28) as usize;
+            let extra_len = u16_at(header, 30) as usize;
+            let comment_len = u16_at(header, 32) as usize;
+            let local_offset = u32_at(header, 42) as u64;
+            let name = directory
+                .get(position + 46..position + 46 + name_len)
+                .ok_or_else(|| invalid("corrupt zip central directory".to_string()))?;
+            let name = String::from_utf8_lossy(name).into_owned();
+            position += 46 + name_len + extra_len + comment_len;
+
+            if name.ends_with('/') {
+                self.insert(&name, None);
+                continue;
+            }
+            let compression = match method {
+                0 => Compression::Stored,
+                8 => Compression::Deflated,
+                _ => continue,
+            };
+            // Encrypted entries can't be served.
+            if flags & 1 != 0 {
+                continue;
+            }
+
+            let mut local_header = [0u8; 30];
+            file.seek(SeekFrom::Start(local_offset))?;
+            file.read_exact(&mut local_header)?;
+            if u32_at(&local_header, 0) != LOCAL_FILE_HEADER {
+                return Err(invalid("corrupt zip local file header".to_string()));
+            }
+            let offset = local_offset
+                + 30
+                + u16_at(&local_header, 26) as u64
+                + u16_at(&local_header, 28) as u64;
+            self.insert(
+                &name,
+                Some(Entry {
+                    offset,
+                    compressed_len,
+                    compression,
+                    len: uncompressed_len,
+                    modified,
+                }),
+            );
+        }
+        Ok(())
+    }
+
+    fn index_tar_gz(&mut self, file: File) -> io::Result<()> {
+        let mut stream = tar_gz_stream(file)?;
+        let mut offset = 0u64;
+        let mut long_name: Option<String> = None;
+        loop {
+            let mut header = [0u8; 512];
+            if read_full(&mut stream, &mut header)? < header.len() || header.iter().all(|&b| b == 0)
+            {
+                break;
+            }
+            offset += 512;
+            let len = parse_octal(&header[124..136])
+                .ok_or_else(|| invalid("corrupt tar header size".to_string()))?;
+            let padded_len = len.div_ceil(512) * 512;
+            let kind = header[156];
+
+            let name = match long_name.take() {
+                Some(name) => name,
+                None => {
+                    let name = c_string(&header[..100]);
+                    let is_ustar = &header[257..262] == b"ustar";
+                    let prefix = c_string(&header[345..500]);
+                    if is_ustar && !prefix.is_empty() {
+                        format!("{}/{}", prefix, name)
+                    } else {
+                        name
+                    }
+                }
+            };
+
+            match kind {
+                // GNU long names and pax headers carry the name of the next
+                // entry in their data.
+                b'L' | b'x' => {
+                    let mut data = Vec::new();
+                    (&mut stream).take(padded_len).read_to_end(&mut data)?;
+                    data.truncate(len as usize);
+                    long_name = if kind == b'L' {
+                        Some(c_string(&data))
+                    } else {
+                        pax_path(&data)
+                    };
+                }
+                b'0' | 0 => {
+                    let modified = parse_octal(&header[136..148])
+                        .map(|seconds| UNIX_EPOCH + Duration::from_secs(seconds));
+                    self.insert(
+                        &name,
+                        Some(Entry {
+                            offset,
+                            compressed_len: len,
+                            compression: Compression::Stored,
+                            len,
+                            modified,
+                        }),
+                    );
+                    io::copy(&mut (&mut stream).take(padded_len), &mut io::sink())?;
+                }
+                b'5' => {
+                    self.insert(&name, None);
+                    io::copy(&mut (&mut stream).take(padded_len), &mut io::sink())?;
+                }
+                _ => {
+                    io::copy(&mut (&mut stream).take(padded_len), &mut io::sink())?;
+                }
+            }
+            offset += padded_len;
+        }
+        Ok(())
+    }
+}
+
+/// Keeps the index of each archive until the file changes.
+#[derive(Debug, Default)]
+pub struct ArchiveCache {
+    archive: Mutex<Option<Arc<Archive>>>,
+}
+
+impl ArchiveCache {
+    pub async fn get(&self, path: &Path) -> io::Result<Arc<Archive>> {
+        let current = stamp(&std::fs::metadata(path)?);
+        if let Some(archive) = &*self.archive.lock().unwrap() {
+            if archive.stamp == current {
+                return Ok(archive.clone());
+            }
+        }
+        let owned_path = path.to_path_buf();
+        let archive = Arc::new(blocking::unblock(move || Archive::open(&owned_path)).await?);
+        *self.archive.lock().unwrap() = Some(archive.clone());
+        Ok(archive)
+    }
+}
+
+fn tar_gz_stream(file: File) -> io::Result<Inflater<BufReader<File>>> {
+    let mut input = BufReader::new(file);
+    inflate::skip_gzip_header(&mut input)?;
+    Ok(Inflater::new(input))
+}
+
+fn stamp(metadata: &std::fs::Metadata) -> (u64, Option<SystemTime>) {
+    (metadata.len(), metadata.modified().ok())
+}
+
+/// Rejects absolute names and any containing `..`, and drops empty and `.`
+/// segments, so entry names compare equal to normalized request paths.
+fn normalize_entry_name(name: &str) -> Option<String> {
+    if name.starts_with('/') || name.contains('\\') {
+        return None;
+    }
+    let mut segments = Vec::new();
+    for segment in name.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => return None,
+            segment => segments.push(segment),
+        }
+    }
+    Some(segments.join("/")).filter(|name| !name.is_empty())
+}
+
+fn read_full(input: &mut impl Read, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match input.read(&mut buffer[filled..])? {
+            0 => break,
+            read => filled += read,
+        }
+    }
+    Ok(filled)
+}
+
+fn c_string(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+fn parse_octal(field: &[u8]) -> Option<u64> {
+    let text = c_string(field);
+    let text = text.trim();
+    if text.is_empty() {
+        return Some(0);
+    }
+    u64::from_str_radix(text, 8).ok()
+}
+
+/// Pax records are `<length> <key>=<value>\n`.
+fn pax_path(data: &[u8]) -> Option<String> {
+    let mut rest = data;
+    while !rest.is_empty() {
+        let space = rest.iter().position(|&b| b == b' ')?;
+        let record_len: usize = std::str::from_utf8(&rest[..space]).ok()?.parse().ok()?;
+        let record = rest.get(space + 1..record_len)?;
+        if let Some(path) = record.strip_prefix(b"path=") {
+            let path = path.strip_suffix(b"\n").unwrap_or(path);
+            return Some(String::from_utf8_lossy(path).into_owned());
+        }
+        rest = &rest[record_len..];
+    }
+    None
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ])
+}
+
+/// Zip stores local MS-DOS timestamps with no zone, taken here as UTC.
+fn dos_time(date: u16, time: u16) -> Option<SystemTime> {
+    let year = 1980 + (date >> 9) as i64;
+    let month = ((date >> 5) & 0xf) as i64;
+    let day = (date & 0x1f) as i64;
+    if !(1..=12).contains(&month) || day == 0 {
+        return None;
+    }
+    let seconds = days_from_civil(year, month, day) * 86400
+        + (time >> 11) as i64 * 3600
+        + ((time >> 5) & 0x3f) as i64 * 60
+        + (time & 0x1f) as i64 * 2;
+    Some(UNIX_EPOCH + Duration::from_secs(seconds as u64))
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
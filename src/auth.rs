@@ -0,0 +1,153 @@
+//! Optional HTTP basic auth for languages listed in the `auth` table of the
+//! config file. Languages without credentials stay public.
+
+use std::{collections::HashMap, io};
+
+use sha2::{Digest, Sha256};
+use subtle::{Choice, ConstantTimeEq};
+use tide::{
+    http::auth::{AuthenticationScheme, BasicAuth, WwwAuthenticate},
+    log,
+    prelude::*,
+    Request, Response, StatusCode,
+};
+
+/// One accepted login, given either as a plain `password` or as the hex
+/// `password-sha256` digest of one.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct CredentialConfig {
+    pub username: String,
+    #[serde(default)]
+    pub password: Option<String>,
+    #[serde(default)]
+    pub password_sha256: Option<String>,
+}
+
+type Sha256Digest = [u8; 32];
+
+/// Usernames are hashed too so that every comparison is over the same
+/// number of bytes.
+#[derive(Debug)]
+struct Credential {
+    username: Sha256Digest,
+    password: Sha256Digest,
+}
+
+#[derive(Debug, Default)]
+pub struct Auth {
+    languages: HashMap<String, Vec<Credential>>,
+}
+
+impl Auth {
+    pub fn from_config(config: &HashMap<String, Vec<CredentialConfig>>) -> io::Result<Self> {
+        let mut languages = HashMap::new();
+        for (language, credentials) in config {
+            let credentials = credentials
+                .iter()
+                .map(|credential| Credential::from_config(language, credential))
+                .collect::<io::Result<Vec<_>>>()?;
+            languages.insert(language.clone(), credentials);
+        }
+        Ok(Auth { languages })
+    }
+
+    pub fn is_protected(&self, language: &str) -> bool {
+        self.languages.contains_key(language)
+    }
+
+    /// Returns the 401 challenge to send when `language` requires auth and
+    /// the request doesn't carry valid credentials for it.
+    pub fn challenge<State>(&self, req: &Request<State>, language: &str) -> Option<Response> {
+        let credentials = self.languages.get(language)?;
+        let authorized = match BasicAuth::from_headers(req) {
+            Ok(Some(basic_auth)) => {
+                let username = sha256(basic_auth.username());
+                let password = sha256(basic_auth.password());
+                // Every credential is checked so the time taken doesn't
+                // reveal which usernames exist.
+                let matched = credentials
+                    .iter()
+                    .fold(Choice::from(0), |matched, credential| {
+                        matched
+                            | (credential.username.ct_eq(&username)
+                                & credential.password.ct_eq(&password))
+                    });
+                bool::from(matched)
+            }
+            Ok(None) => false,
+            Err(_) => false,
+        };
+        if authorized {
+            return None;
+        }
+        if req.header("authorization").is_some() {
+            log::info!("Rejected credentials for language {}", language);
+        }
+        let mut response = Response::new(StatusCode::Unauthorized);
+        WwwAuthenticate::new(AuthenticationScheme::Basic, language.to_string())
+            .apply(&mut response);
+        Some(response)
+    }
+}
+
+/// Returns the 401 challenge to send unless the request carries
+/// `Authorization: Bearer <token>`.
+pub fn bearer_challenge<State>(req: &Request<State>, token: &str) -> Option<Response> {
+    let provided = req
+        .header("authorization")
+        .and_then(|values| values.last().as_str().strip_prefix("Bearer "))
+        .map(|provided| sha256(provided.trim()));
+    let authorized = provided.is_some_and(|provided| bool::from(provided.ct_eq(&sha256(token))));
+    if authorized {
+        return None;
+    }
+    let mut response = Response::new(StatusCode::Unauthorized);
+    response.insert_header("www-authenticate", "Bearer");
+    Some(response)
+}
+
+impl Credential {
+    fn from_config(language: &str, config: &CredentialConfig) -> io::Result<Self> {
+        let invalid = |message: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "auth for {} in language {}: {}",
+                    config.username, language, message
+                ),
+            )
+        };
+        let password = match (&config.password, &config.password_sha256) {
+            (Some(password), None) => sha256(password),
+            (None, Some(digest)) => {
+                parse_hex_digest(digest).ok_or_else(|| invalid("invalid password-sha256"))?
+            }
+            _ => {
+                return Err(invalid(
+                    "expected exactly one of password or password-sha256",
+                ))
+            }
+        };
+        Ok(Credential {
+            username: sha256(&config.username),
+            password,
+        })
+    }
+}
+
+fn sha256(text: &str) -> Sha256Digest {
+    Sha256::digest(text.as_bytes()).into()
+}
+
+fn parse_hex_digest(hex: &str) -> Option<Sha256Digest> {
+    let hex = hex.trim();
+    if hex.len() != 64 || !hex.is_ascii() {
+        return None;
+    }
+    let mut digest = [0; 32];
+    for (byte, pair) in digest.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()?;
+    }
+    Some(digest)
+}
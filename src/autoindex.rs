@@ -0,0 +1,182 @@
+//! HTML listings for project directories without an `index.html`, for
+//! the projects of a language at its root, and for the languages at the
+//! root of a host that names none.
+
+use std::{fmt::Write, io};
+
+use async_std::{fs, path::Path, prelude::*};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+
+use crate::walk;
+
+/// Everything but RFC 3986 unreserved characters is escaped in a segment.
+pub const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+struct Entry {
+    name: String,
+    is_dir: bool,
+    len: u64,
+}
+
+/// Renders the entries of `directory`, linking each one beneath `url_path`,
+/// the already percent-encoded path the directory was requested at. Entries
+/// whose real path lies outside the canonical `root` are left out, as are
+/// names that aren't UTF-8 and so couldn't be requested anyway. Hidden
+/// entries are left out too unless `show_hidden`.
+pub async fn render(
+    directory: &Path,
+    root: &Path,
+    url_path: &str,
+    show_hidden: bool,
+) -> io::Result<String> {
+    let relative_directory = directory.strip_prefix(root).unwrap_or(directory);
+    let mut entries = Vec::new();
+    let mut read_dir = fs::read_dir(directory).await?;
+    while let Some(entry) = read_dir.next().await {
+        let entry = entry?;
+        let name = match entry.file_name().into_string() {
+            Ok(name) => name,
+            Err(_) => continue,
+        };
+        if !show_hidden && walk::is_hidden(relative_directory.join(&name).as_ref()) {
+            continue;
+        }
+        match fs::canonicalize(entry.path()).await {
+            Ok(path) if path.starts_with(root) => {}
+            _ => continue,
+        }
+        let metadata = match fs::metadata(entry.path()).await {
+            Ok(metadata) => metadata,
+            Err(_) => continue,
+        };
+        entries.push(Entry {
+            name,
+            is_dir: metadata.is_dir(),
+            len: metadata.len(),
+        });
+    }
+    entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.cmp(&b.name)));
+
+    let base = url_path.trim_end_matches('/');
+    let title = escape_html(&format!(
+        "{}/",
+        percent_decode_str(base).decode_utf8_lossy()
+    ));
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Index of {0}</title>\n</head>\n<body>\n<h1>Index of {0}</h1>\n<table>\n",
+        title
+    );
+    if directory != root {
+        let parent = base.rsplit_once('/').map_or("", |(parent, _)| parent);
+        let _ = writeln!(
+            html,
+            "<tr><td><a href=\"{}/\">../</a></td><td></td></tr>",
+            escape_html(parent)
+        );
+    }
+    for entry in entries {
+        let suffix = if entry.is_dir { "/" } else { "" };
+        let href = format!(
+            "{}/{}{}",
+            base,
+            utf8_percent_encode(&entry.name, PATH_SEGMENT),
+            suffix
+        );
+        let size = if entry.is_dir {
+            "-".to_string()
+        } else {
+            entry.len.to_string()
+        };
+        let _ = writeln!(
+            html,
+            "<tr><td><a href=\"{}\">{}{}</a></td><td>{}</td></tr>",
+            escape_html(&href),
+            escape_html(&entry.name),
+            suffix,
+            size
+        );
+    }
+    html.push_str("</table>\n</body>\n</html>\n");
+    Ok(html)
+}
+
+/// Renders links to each of `project_names`, which should already be
+/// sorted, under `prefix`.
+pub fn render_projects(language: &str, prefix: &str, project_names: &[&str]) -> String {
+    let title = escape_html(language);
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{0} projects</title>\n</head>\n<body>\n<h1>{0} projects</h1>\n<ul>\n",
+        title
+    );
+    for project_name in project_names {
+        let _ = writeln!(
+            html,
+            "<li><a href=\"{}/{}/\">{}</a></li>",
+            prefix,
+            utf8_percent_encode(project_name, PATH_SEGMENT),
+            escape_html(project_name)
+        );
+    }
+    html.push_str("</ul>\n</body>\n</html>\n");
+    html
+}
+
+/// A language on the landing page, linked along with each of its projects.
+pub struct LanguageLinks<'a> {
+    pub name: &'a str,
+    pub href: String,
+    pub projects: Vec<(&'a str, String)>,
+}
+
+/// Renders `explanation` above `languages`, as `render_language_list` does.
+pub fn render_languages(explanation: &str, languages: &[LanguageLinks]) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>dapsd</title>\n</head>\n<body>\n<h1>dapsd</h1>\n<p>{}</p>\n{}</body>\n</html>\n",
+        escape_html(explanation),
+        render_language_list(languages)
+    )
+}
+
+/// A list linking to each of `languages`, with a list of its projects
+/// under each one.
+pub fn render_language_list(languages: &[LanguageLinks]) -> String {
+    let mut html = String::from("<ul>\n");
+    for language in languages {
+        let _ = writeln!(
+            html,
+            "<li><a href=\"{}\">{}</a>\n<ul>",
+            escape_html(&language.href),
+            escape_html(language.name)
+        );
+        for (project_name, href) in &language.projects {
+            let _ = writeln!(
+                html,
+                "<li><a href=\"{}\">{}</a></li>",
+                escape_html(href),
+                escape_html(project_name)
+            );
+        }
+        html.push_str("</ul>\n</li>\n");
+    }
+    html.push_str("</ul>\n");
+    html
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,56 @@
+use std::process;
+
+use doc_and_pony_show::{
+    build_app, check_config, serve, start_logging, Options, OptionsError, ServerState, USAGE,
+};
+
+#[async_std::main]
+async fn main() {
+    let options = match Options::from_env() {
+        Ok(options) => options,
+        Err(OptionsError::Help) => {
+            print!("{}", USAGE);
+            return;
+        }
+        Err(e) => {
+            eprintln!("dapsd: {}\n\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+
+    // Parsing makes sure a config was given.
+    if let (true, Some(config_path)) = (options.check_config, &options.config) {
+        let check = match check_config(config_path, options.language_codes).await {
+            Ok(check) => check,
+            Err(e) => {
+                eprintln!("dapsd: {}", e);
+                process::exit(1);
+            }
+        };
+        for skipped in &check.skipped {
+            eprintln!("dapsd: would skip {}", skipped);
+        }
+        for error in &check.errors {
+            eprintln!("dapsd: {}", error);
+        }
+        if !check.errors.is_empty() {
+            process::exit(1);
+        }
+        println!("{:?}: OK, {} projects", config_path, check.projects);
+        return;
+    }
+
+    start_logging();
+
+    let state = match ServerState::from_options(options).await {
+        Ok(state) => state,
+        Err(e) => {
+            eprintln!("dapsd: {}", e);
+            process::exit(1);
+        }
+    };
+    if let Err(e) = serve(build_app(state)).await {
+        eprintln!("dapsd: {}", e);
+        process::exit(1);
+    }
+}
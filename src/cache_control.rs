@@ -0,0 +1,106 @@
+//! `Cache-Control` values for served files, configured by the `cache-control`
+//! table of the config file.
+
+use std::path::Path;
+
+use tide::prelude::*;
+
+const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+const MIN_HASH_LEN: usize = 8;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct CacheControl {
+    /// Checked in order before anything else; the first match wins. Giving
+    /// any rules replaces the default one, which sends `no-cache` for HTML.
+    #[serde(default = "default_rules")]
+    pub rules: Vec<Rule>,
+    /// For file names containing a content hash, like `app-3f9a1b2c.js`.
+    #[serde(default = "immutable")]
+    pub hashed: String,
+    /// For everything in a project registered with a version.
+    #[serde(default = "immutable")]
+    pub versioned: String,
+    #[serde(default = "default_value")]
+    pub default: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Rule {
+    /// A glob where `*` matches any run of characters and `?` any single
+    /// one. Patterns containing `/` match the path within the project,
+    /// others just the file name.
+    pub pattern: String,
+    pub value: String,
+}
+
+fn default_rules() -> Vec<Rule> {
+    vec![Rule {
+        pattern: "*.html".to_string(),
+        value: "no-cache".to_string(),
+    }]
+}
+
+fn immutable() -> String {
+    IMMUTABLE.to_string()
+}
+
+fn default_value() -> String {
+    "public, max-age=300".to_string()
+}
+
+impl Default for CacheControl {
+    fn default() -> Self {
+        CacheControl {
+            rules: default_rules(),
+            hashed: immutable(),
+            versioned: immutable(),
+            default: default_value(),
+        }
+    }
+}
+
+impl CacheControl {
+    /// `path` is relative to the project directory.
+    pub fn for_path(&self, path: &Path, versioned: bool) -> &str {
+        let path = path.to_string_lossy();
+        let file_name = path.rsplit('/').next().unwrap_or(&path);
+        let rule = self.rules.iter().find(|rule| {
+            let target = if rule.pattern.contains('/') {
+                &path
+            } else {
+                file_name
+            };
+            glob_match(rule.pattern.as_bytes(), target.as_bytes())
+        });
+        if let Some(rule) = rule {
+            &rule.value
+        } else if is_hashed(file_name) {
+            &self.hashed
+        } else if versioned {
+            &self.versioned
+        } else {
+            &self.default
+        }
+    }
+}
+
+/// A name is hashed when some `-` or `.` separated part of its stem is a
+/// long run of hex digits.
+fn is_hashed(file_name: &str) -> bool {
+    let stem = file_name
+        .rsplit_once('.')
+        .map_or(file_name, |(stem, _)| stem);
+    stem.split(['-', '.'])
+        .any(|part| part.len() >= MIN_HASH_LEN && part.bytes().all(|b| b.is_ascii_hexdigit()))
+}
+
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some((b'*', rest)) => (0..=text.len()).any(|skip| glob_match(rest, &text[skip..])),
+        Some((b'?', rest)) => !text.is_empty() && glob_match(rest, &text[1..]),
+        Some((c, rest)) => text.first() == Some(c) && glob_match(rest, &text[1..]),
+    }
+}
@@ -0,0 +1,47 @@
+//! `--max-connections`, which bounds how many page requests are handled at
+//! once so that a burst can't exhaust a small instance's memory. Requests
+//! past the limit are turned away rather than queued.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[derive(Debug)]
+pub struct PageSlots {
+    limit: Option<usize>,
+    in_use: AtomicUsize,
+}
+
+impl PageSlots {
+    pub fn new(limit: Option<usize>) -> Self {
+        PageSlots {
+            limit,
+            in_use: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn in_use(&self) -> usize {
+        self.in_use.load(Ordering::SeqCst)
+    }
+
+    /// A slot to hold while handling a request, or None when all are taken.
+    pub fn try_acquire(&self) -> Option<PageSlot<'_>> {
+        let acquired = self
+            .in_use
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |in_use| {
+                match self.limit {
+                    Some(limit) if in_use >= limit => None,
+                    _ => Some(in_use + 1),
+                }
+            })
+            .is_ok();
+        acquired.then(|| PageSlot(&self.in_use))
+    }
+}
+
+/// Frees its slot on drop, so cancelled requests give theirs back too.
+pub struct PageSlot<'a>(&'a AtomicUsize);
+
+impl Drop for PageSlot<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,361 @@
+//! The JSON configuration file loaded at startup with `--config`.
+
+use std::{
+    collections::HashMap,
+    fmt, io, mem,
+    path::{Path, PathBuf},
+};
+
+use async_std::fs;
+use tide::{log, prelude::*};
+
+use crate::{
+    auth::{Auth, CredentialConfig},
+    cache_control::CacheControl,
+    cors::Cors,
+    directories::Directories,
+    fallbacks::Fallbacks,
+    language_code,
+    mime_types::MimeTypes,
+    rate_limit::{RateLimit, RateLimits},
+    robots::Robots,
+    LanguageDirectory, Project, ASSETS, ROOT_FILES,
+};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    pub projects: Vec<Project>,
+    #[serde(default)]
+    pub missing_directory: MissingDirectory,
+    /// Extra extension to MIME type mappings, overriding the built-in ones.
+    #[serde(default)]
+    pub mime_types: HashMap<String, String>,
+    #[serde(default)]
+    pub cache_control: CacheControl,
+    /// Cross-origin requests are refused unless this is given.
+    #[serde(default)]
+    pub cors: Option<Cors>,
+    /// Basic auth credentials by language name.
+    #[serde(default)]
+    pub auth: HashMap<String, Vec<CredentialConfig>>,
+    /// The project a bare language root redirects to, by language name.
+    /// Languages without one get a page listing their projects instead.
+    #[serde(default)]
+    pub default_projects: HashMap<String, String>,
+    /// A directory of files shared by a language's projects, such as logos
+    /// and themes, by language name. They are served under `/_assets/`.
+    #[serde(default)]
+    pub language_assets: HashMap<String, PathBuf>,
+    /// A directory with the `favicon.ico` and `robots.txt` to answer with
+    /// on every host, whether or not it names a language.
+    #[serde(default)]
+    pub root_files: Option<PathBuf>,
+    /// The languages to look for a missing page in, in order, by language
+    /// name. `--default-language` comes after all of them.
+    #[serde(default)]
+    pub language_fallbacks: Fallbacks,
+    #[serde(default)]
+    pub rate_limits: RateLimits,
+    #[serde(default)]
+    pub robots: Robots,
+}
+
+/// What to do with a configured project whose directory can't be read.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum MissingDirectory {
+    #[default]
+    Skip,
+    Abort,
+}
+
+impl Config {
+    pub async fn load(path: &Path) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)
+            .await
+            .map_err(|e| io::Error::new(e.kind(), format!("failed to read {:?}: {}", path, e)))?;
+        serde_json::from_str(&contents).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("failed to parse {:?}: {}", path, e),
+            )
+        })
+    }
+
+    /// Replaces every language, whether a project's or a key naming one,
+    /// with its language code, for `--language-codes`.
+    pub fn normalize_languages(&mut self) -> io::Result<()> {
+        for project in &mut self.projects {
+            project.language = language_code_of(&project.language)?;
+        }
+        self.auth = normalize_keys(mem::take(&mut self.auth))?;
+        self.default_projects = normalize_keys(mem::take(&mut self.default_projects))?;
+        self.language_assets = normalize_keys(mem::take(&mut self.language_assets))?;
+        self.language_fallbacks
+            .normalize_languages(language_code_of)?;
+        self.robots.disallow_languages = mem::take(&mut self.robots.disallow_languages)
+            .iter()
+            .map(|language| language_code_of(language))
+            .collect::<io::Result<_>>()?;
+        self.robots.disallow_projects =
+            normalize_keys(mem::take(&mut self.robots.disallow_projects))?;
+        Ok(())
+    }
+
+    /// The `language-assets` directories, each served as a project named
+    /// `_assets` that no registration can replace. Those that can't be read
+    /// follow the `missing-directory` policy too.
+    pub async fn asset_projects(&mut self) -> io::Result<HashMap<String, Project>> {
+        let mut asset_projects = HashMap::new();
+        for (language, directory) in mem::take(&mut self.language_assets) {
+            match asset_project(language, directory).await {
+                Ok(project) => {
+                    asset_projects.insert(project.language.clone(), project);
+                }
+                Err(problem) => self.skip_or_abort(problem)?,
+            }
+        }
+        Ok(asset_projects)
+    }
+
+    /// The `root-files` directory, served as a project that no registration
+    /// can replace, if it is given and readable.
+    pub async fn root_files_project(&mut self) -> io::Result<Option<Project>> {
+        match self.root_files.take() {
+            Some(directory) => match root_files_project(directory).await {
+                Ok(project) => Ok(Some(project)),
+                Err(problem) => self.skip_or_abort(problem).map(|()| None),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Registers every configured project whose directory is readable,
+    /// returning the directories of those skipped. Relative directories
+    /// are resolved against the working directory.
+    pub async fn populate(
+        self,
+        language_directory: &mut LanguageDirectory,
+    ) -> io::Result<Vec<Directories>> {
+        let ValidatedProjects { projects, skipped } = self.validated_projects().await?;
+        for project in projects {
+            language_directory.register(project);
+        }
+        Ok(skipped)
+    }
+
+    /// The configured projects whose directories are readable, honouring
+    /// the `missing-directory` policy for the rest.
+    pub async fn validated_projects(mut self) -> io::Result<ValidatedProjects> {
+        let mut projects = Vec::new();
+        let mut skipped = Vec::new();
+        for project in mem::take(&mut self.projects) {
+            match validated_project(project).await {
+                Ok(project) => projects.push(project),
+                Err(problem) => {
+                    if let Problem::Directory { directories, .. } = &problem {
+                        skipped.push(directories.clone());
+                    }
+                    self.skip_or_abort(problem)?;
+                }
+            }
+        }
+        Ok(ValidatedProjects { projects, skipped })
+    }
+
+    /// Logs a directory that can't be read when the policy is to skip it.
+    fn skip_or_abort(&self, problem: Problem) -> io::Result<()> {
+        match problem {
+            Problem::Directory { message, .. }
+                if self.missing_directory == MissingDirectory::Skip =>
+            {
+                log::warn!("Skipping {}", message);
+                Ok(())
+            }
+            problem => Err(problem.into()),
+        }
+    }
+
+    /// Everything startup would refuse, rather than only the first, along
+    /// with the directories it would skip, for `--check-config`.
+    pub async fn check(mut self) -> ConfigCheck {
+        let mut check = ConfigCheck::default();
+        if let Err(e) = MimeTypes::default().extend(&self.mime_types) {
+            check.errors.push(format!("mime-types: {}", e));
+        }
+        if let Err(e) = Auth::from_config(&self.auth) {
+            check.errors.push(format!("auth: {}", e));
+        }
+        if let Err(e) = self.language_fallbacks.check() {
+            check.errors.push(format!("language-fallbacks: {}", e));
+        }
+        let RateLimits {
+            serve,
+            api,
+            max_clients,
+        } = mem::take(&mut self.rate_limits);
+        for (name, limit) in &[("serve", serve), ("api", api)] {
+            if let Some(limit) = limit {
+                if let Err(e) = RateLimit::new(name, *limit, max_clients) {
+                    check.errors.push(format!("rate-limits: {}", e));
+                }
+            }
+        }
+        let mut assets = Vec::new();
+        for (language, directory) in mem::take(&mut self.language_assets) {
+            assets.push(asset_project(language, directory).await.map(|_| ()));
+        }
+        if let Some(directory) = self.root_files.take() {
+            assets.push(root_files_project(directory).await.map(|_| ()));
+        }
+        let mut projects = Vec::new();
+        for project in mem::take(&mut self.projects) {
+            projects.push(validated_project(project).await.map(|_| ()));
+        }
+        check.projects = projects.iter().filter(|result| result.is_ok()).count();
+        for problem in assets.into_iter().chain(projects).filter_map(Result::err) {
+            match problem {
+                Problem::Directory { message, .. }
+                    if self.missing_directory == MissingDirectory::Skip =>
+                {
+                    check.skipped.push(message)
+                }
+                problem => check.errors.push(problem.to_string()),
+            }
+        }
+        check
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ConfigCheck {
+    pub errors: Vec<String>,
+    /// Projects and assets whose directories would be skipped.
+    pub skipped: Vec<String>,
+    /// Those that would be registered.
+    pub projects: usize,
+}
+
+/// Why a configured project or asset directory can't be served.
+enum Problem {
+    Invalid(String),
+    Directory {
+        message: String,
+        kind: io::ErrorKind,
+        directories: Directories,
+    },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::Invalid(message) | Problem::Directory { message, .. } => f.write_str(message),
+        }
+    }
+}
+
+impl From<Problem> for io::Error {
+    fn from(problem: Problem) -> Self {
+        match problem {
+            Problem::Invalid(message) => io::Error::new(io::ErrorKind::InvalidData, message),
+            Problem::Directory { message, kind, .. } => io::Error::new(kind, message),
+        }
+    }
+}
+
+fn language_code_of(language: &str) -> io::Result<String> {
+    language_code::normalize(language).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            language_code::invalid(language).to_string(),
+        )
+    })
+}
+
+/// Keys that normalize alike are an error rather than one replacing the
+/// other.
+fn normalize_keys<V>(by_language: HashMap<String, V>) -> io::Result<HashMap<String, V>> {
+    let mut by_language: Vec<_> = by_language.into_iter().collect();
+    by_language.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+    let mut normalized = HashMap::new();
+    let mut named = HashMap::new();
+    for (language, value) in by_language {
+        let code = language_code_of(&language)?;
+        if let Some(first) = named.insert(code.clone(), language.clone()) {
+            return Err(language_code::clash(&first, &language, &code));
+        }
+        normalized.insert(code, value);
+    }
+    Ok(normalized)
+}
+
+async fn validated_project(mut project: Project) -> Result<Project, Problem> {
+    if let Err(e) = project.validate() {
+        return Err(Problem::Invalid(format!(
+            "{} in language {}: {}",
+            project.project_name, project.language, e
+        )));
+    }
+    if let Err(e) = project.canonicalize_directory().await {
+        return Err(Problem::Directory {
+            message: format!(
+                "{} in language {} has invalid directory {:?}: {}",
+                project.project_name, project.language, project.directories, e
+            ),
+            kind: e.kind(),
+            directories: project.directories,
+        });
+    }
+    Ok(project)
+}
+
+async fn asset_project(language: String, directory: PathBuf) -> Result<Project, Problem> {
+    let mut project = Project {
+        language,
+        project_name: ASSETS.to_string(),
+        directories: directory.into(),
+        ..Project::default()
+    };
+    if let Err(e) = crate::check_name("language", &project.language) {
+        return Err(Problem::Invalid(format!("language assets: {}", e)));
+    }
+    if let Err(e) = project.canonicalize_directory().await {
+        return Err(Problem::Directory {
+            message: format!(
+                "assets of language {} have invalid directory {:?}: {}",
+                project.language, project.directories, e
+            ),
+            kind: e.kind(),
+            directories: project.directories,
+        });
+    }
+    Ok(project)
+}
+
+async fn root_files_project(directory: PathBuf) -> Result<Project, Problem> {
+    let mut project = Project {
+        project_name: ROOT_FILES.to_string(),
+        directories: directory.into(),
+        ..Project::default()
+    };
+    if let Err(e) = project.canonicalize_directory().await {
+        return Err(Problem::Directory {
+            message: format!(
+                "root files have invalid directory {:?}: {}",
+                project.directories, e
+            ),
+            kind: e.kind(),
+            directories: project.directories,
+        });
+    }
+    Ok(project)
+}
+
+pub struct ValidatedProjects {
+    pub projects: Vec<Project>,
+    /// The directories of projects left out by the `missing-directory`
+    /// policy.
+    pub skipped: Vec<Directories>,
+}
@@ -0,0 +1,136 @@
+//! Opt-in CORS for served pages, configured by the `cors` table of the config
+//! file. Requests from origins that aren't allowed are served as usual, just
+//! without the headers that would let a browser share the response.
+
+use tide::{
+    http::{
+        headers::{
+            HeaderValue, ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS,
+            ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_EXPOSE_HEADERS, ACCESS_CONTROL_MAX_AGE,
+            ACCESS_CONTROL_REQUEST_HEADERS, ACCESS_CONTROL_REQUEST_METHOD, ORIGIN,
+        },
+        Method,
+    },
+    prelude::*,
+    utils::async_trait,
+    Middleware, Next, Request, Response, Result, StatusCode,
+};
+
+use crate::add_vary;
+
+const ANY: &str = "*";
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Cors {
+    /// Exact origins such as `https://example.com`, or `*` for any.
+    pub allowed_origins: Vec<String>,
+    #[serde(default = "default_allowed_methods")]
+    pub allowed_methods: Vec<String>,
+    /// Request headers allowed in preflight, or `*` to allow whatever the
+    /// preflight asks for.
+    #[serde(default = "default_allowed_headers")]
+    pub allowed_headers: Vec<String>,
+    #[serde(default = "default_exposed_headers")]
+    pub exposed_headers: Vec<String>,
+    #[serde(default = "default_max_age")]
+    pub max_age: u64,
+}
+
+fn default_allowed_methods() -> Vec<String> {
+    vec!["GET".to_string(), "HEAD".to_string()]
+}
+
+fn default_allowed_headers() -> Vec<String> {
+    ["If-Modified-Since", "If-None-Match", "Range"]
+        .iter()
+        .map(|header| header.to_string())
+        .collect()
+}
+
+fn default_exposed_headers() -> Vec<String> {
+    ["Content-Range", "ETag"]
+        .iter()
+        .map(|header| header.to_string())
+        .collect()
+}
+
+fn default_max_age() -> u64 {
+    86400
+}
+
+impl Cors {
+    fn allows_any_origin(&self) -> bool {
+        self.allowed_origins.iter().any(|origin| origin == ANY)
+    }
+
+    fn allows_origin(&self, origin: &str) -> bool {
+        self.allows_any_origin() || self.allowed_origins.iter().any(|allowed| allowed == origin)
+    }
+
+    fn allow_origin_value(&self, origin: &HeaderValue) -> HeaderValue {
+        if self.allows_any_origin() {
+            ANY.parse().unwrap()
+        } else {
+            origin.clone()
+        }
+    }
+
+    fn preflight<State>(&self, req: &Request<State>, origin: &HeaderValue) -> Response {
+        let allowed_headers = if self.allowed_headers.iter().any(|header| header == ANY) {
+            req.header(ACCESS_CONTROL_REQUEST_HEADERS)
+                .map(|headers| headers.last().as_str().to_string())
+                .unwrap_or_default()
+        } else {
+            self.allowed_headers.join(", ")
+        };
+        let mut response = Response::builder(StatusCode::NoContent)
+            .header(ACCESS_CONTROL_ALLOW_ORIGIN, self.allow_origin_value(origin))
+            .header(
+                ACCESS_CONTROL_ALLOW_METHODS,
+                self.allowed_methods.join(", "),
+            )
+            .header(ACCESS_CONTROL_MAX_AGE, self.max_age.to_string())
+            .build();
+        if !allowed_headers.is_empty() {
+            response.insert_header(ACCESS_CONTROL_ALLOW_HEADERS, allowed_headers);
+        }
+        response
+    }
+}
+
+#[async_trait]
+impl<State: Clone + Send + Sync + 'static> Middleware<State> for Cors {
+    async fn handle(&self, req: Request<State>, next: Next<'_, State>) -> Result {
+        let origin = req
+            .header(ORIGIN)
+            .map(|origins| origins.last().clone())
+            .filter(|origin| self.allows_origin(origin.as_str()));
+        let is_preflight =
+            req.method() == Method::Options && req.header(ACCESS_CONTROL_REQUEST_METHOD).is_some();
+
+        let mut response = match &origin {
+            Some(origin) if is_preflight => self.preflight(&req, origin),
+            _ => {
+                let mut response = next.run(req).await;
+                if let Some(origin) = &origin {
+                    response.insert_header(
+                        ACCESS_CONTROL_ALLOW_ORIGIN,
+                        self.allow_origin_value(origin),
+                    );
+                    if !self.exposed_headers.is_empty() {
+                        response.insert_header(
+                            ACCESS_CONTROL_EXPOSE_HEADERS,
+                            self.exposed_headers.join(", "),
+                        );
+                    }
+                }
+                response
+            }
+        };
+        if !self.allows_any_origin() {
+            add_vary(&mut response, ORIGIN)?;
+        }
+        Ok(response)
+    }
+}
@@ -0,0 +1,81 @@
+//! The directories a project is served from. Registrations give either one
+//! `directory` or a list of them, overlaid in order: a file in an earlier
+//! directory shadows the file at the same path in any later one.
+
+use std::{fmt, path::PathBuf, result::Result as StdResult, slice};
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// Never empty.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Directories(Vec<PathBuf>);
+
+impl Directories {
+    /// The directory with the highest precedence, and the only one that
+    /// listings, search, sitemaps, warmup and live reload look at.
+    pub fn primary(&self) -> &PathBuf {
+        &self.0[0]
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, PathBuf> {
+        self.0.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> slice::IterMut<'_, PathBuf> {
+        self.0.iter_mut()
+    }
+
+    pub fn is_overlay(&self) -> bool {
+        self.0.len() > 1
+    }
+}
+
+impl Default for Directories {
+    fn default() -> Self {
+        PathBuf::new().into()
+    }
+}
+
+impl From<PathBuf> for Directories {
+    fn from(directory: PathBuf) -> Self {
+        Directories(vec![directory])
+    }
+}
+
+/// A single directory is shown as a path, like it is written.
+impl fmt::Debug for Directories {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0.as_slice() {
+            [directory] => directory.fmt(f),
+            directories => directories.fmt(f),
+        }
+    }
+}
+
+impl Serialize for Directories {
+    fn serialize<S: Serializer>(&self, serializer: S) -> StdResult<S::Ok, S::Error> {
+        match self.0.as_slice() {
+            [directory] => directory.serialize(serializer),
+            directories => directories.serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Directories {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> StdResult<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum OneOrMany {
+            One(PathBuf),
+            Many(Vec<PathBuf>),
+        }
+
+        match OneOrMany::deserialize(deserializer)? {
+            OneOrMany::One(directory) => Ok(directory.into()),
+            OneOrMany::Many(directories) if directories.is_empty() => Err(de::Error::custom(
+                "directory must be a path or a non-empty list of paths",
+            )),
+            OneOrMany::Many(directories) => Ok(Directories(directories)),
+        }
+    }
+}
@@ -0,0 +1,66 @@
+//! `?download` and `?download=<filename>`, which ask the browser to save a
+//! file rather than display it.
+
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use tide::Request;
+
+pub const CONTENT_DISPOSITION: &str = "content-disposition";
+
+/// Characters left as they are in an RFC 5987 `filename*` value.
+const ATTR_CHAR: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'!')
+    .remove(b'#')
+    .remove(b'$')
+    .remove(b'&')
+    .remove(b'+')
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'^')
+    .remove(b'_')
+    .remove(b'`')
+    .remove(b'|')
+    .remove(b'~');
+
+/// The `Content-Disposition` to send for a request with `?download`,
+/// naming the file `served_name` unless the query gives another name.
+pub fn disposition<State>(req: &Request<State>, served_name: &str) -> Option<String> {
+    let (_, requested) = req
+        .url()
+        .query_pairs()
+        .find(|(name, _)| name == "download")?;
+    let requested = sanitize(&requested);
+    let name = if requested.is_empty() {
+        sanitize(served_name)
+    } else {
+        requested
+    };
+    if name.is_empty() {
+        return Some("attachment".to_string());
+    }
+    let fallback: String = name
+        .chars()
+        .map(|c| if c.is_ascii() { c } else { '_' })
+        .collect();
+    Some(format!(
+        "attachment; filename=\"{}\"; filename*=UTF-8''{}",
+        fallback,
+        utf8_percent_encode(&name, ATTR_CHAR)
+    ))
+}
+
+/// Keeps only the last path segment, without control characters, quotes
+/// or backslashes, so the name can't break out of the header or point
+/// into another directory.
+fn sanitize(name: &str) -> String {
+    let name = name.rsplit(['/', '\\']).next().unwrap_or_default();
+    let name: String = name
+        .chars()
+        .filter(|c| !c.is_control() && *c != '"')
+        .collect();
+    let name = name.trim();
+    if name == "." || name == ".." {
+        String::new()
+    } else {
+        name.to_string()
+    }
+}
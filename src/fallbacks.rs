@@ -0,0 +1,101 @@
+//! The languages a missing page is looked for in next, configured per
+//! language with `language-fallbacks`. Each fallback's own fallbacks are
+//! tried right after it, so `pt-br` falling back to `pt` and `pt` to `en`
+//! tries `pt` and then `en` for `pt-br`.
+
+use std::{collections::HashMap, io};
+
+use tide::prelude::*;
+
+use crate::{is_valid_name, language_code};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(transparent)]
+pub struct Fallbacks(HashMap<String, Vec<String>>);
+
+impl Fallbacks {
+    /// Every name has to be a valid language name, and no language can be
+    /// its own fallback, directly or through others.
+    pub fn check(&self) -> io::Result<()> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+        for (language, fallbacks) in &self.0 {
+            for name in std::iter::once(language).chain(fallbacks) {
+                if !is_valid_name(name) {
+                    return Err(invalid(format!("invalid language name `{}`", name)));
+                }
+            }
+        }
+        let mut languages: Vec<&String> = self.0.keys().collect();
+        languages.sort_unstable();
+        for language in languages {
+            if let Some(cycle) = self.find_cycle(language, &mut Vec::new()) {
+                return Err(invalid(format!(
+                    "languages fall back to themselves: {}",
+                    cycle.join(" -> ")
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn find_cycle<'a>(
+        &'a self,
+        language: &'a str,
+        path: &mut Vec<&'a str>,
+    ) -> Option<Vec<&'a str>> {
+        if path.contains(&language) {
+            let mut cycle = path.clone();
+            cycle.push(language);
+            return Some(cycle);
+        }
+        path.push(language);
+        for fallback in self.0.get(language).into_iter().flatten() {
+            if let Some(cycle) = self.find_cycle(fallback, path) {
+                return Some(cycle);
+            }
+        }
+        path.pop();
+        None
+    }
+
+    /// Replaces every language with what `normalize` makes of it. Two
+    /// languages normalizing alike are an error, not merged.
+    pub fn normalize_languages(
+        &mut self,
+        normalize: impl Fn(&str) -> io::Result<String>,
+    ) -> io::Result<()> {
+        let mut languages: Vec<_> = self.0.drain().collect();
+        languages.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        let mut normalized = HashMap::new();
+        let mut named = HashMap::new();
+        for (language, fallbacks) in languages {
+            let fallbacks = fallbacks
+                .iter()
+                .map(|fallback| normalize(fallback))
+                .collect::<io::Result<_>>()?;
+            let code = normalize(&language)?;
+            if let Some(first) = named.insert(code.clone(), language.clone()) {
+                return Err(language_code::clash(&first, &language, &code));
+            }
+            normalized.insert(code, fallbacks);
+        }
+        self.0 = normalized;
+        Ok(())
+    }
+
+    /// The fallbacks of `language`, nearest first.
+    pub fn chain(&self, language: &str) -> Vec<&str> {
+        let mut chain = Vec::new();
+        self.extend_chain(language, &mut chain);
+        chain
+    }
+
+    fn extend_chain<'a>(&'a self, language: &str, chain: &mut Vec<&'a str>) {
+        for fallback in self.0.get(language).into_iter().flatten() {
+            if fallback != language && !chain.contains(&fallback.as_str()) {
+                chain.push(fallback);
+                self.extend_chain(fallback, chain);
+            }
+        }
+    }
+}
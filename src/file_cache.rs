@@ -0,0 +1,169 @@
+//! A bounded in-memory LRU cache of small files, enabled with
+//! `--file-cache-size`, so that hot pages aren't read from disk on every
+//! request.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use async_std::io::Cursor;
+use tide::{
+    http::{mime, Mime},
+    Body,
+};
+
+use crate::{
+    directories::Directories,
+    snapshot::{Snapshot, SNIFF_LEN},
+};
+
+#[derive(Debug)]
+struct Entry {
+    contents: Arc<[u8]>,
+    modified: Option<SystemTime>,
+    last_used: u64,
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    by_path: HashMap<PathBuf, Entry>,
+    /// Paths by the tick they were last used at, oldest first.
+    by_use: BTreeMap<u64, PathBuf>,
+    bytes: usize,
+    tick: u64,
+}
+
+impl Entries {
+    fn remove(&mut self, path: &Path) {
+        if let Some(entry) = self.by_path.remove(path) {
+            self.by_use.remove(&entry.last_used);
+            self.bytes -= entry.contents.len();
+        }
+    }
+}
+
+/// Keyed by canonical file path, which is unique to a project's file.
+#[derive(Debug)]
+pub struct FileCache {
+    max_bytes: usize,
+    max_file_bytes: usize,
+    entries: Mutex<Entries>,
+}
+
+impl FileCache {
+    pub fn new(max_bytes: usize, max_file_bytes: usize) -> Self {
+        FileCache {
+            max_bytes,
+            max_file_bytes,
+            entries: Mutex::default(),
+        }
+    }
+
+    pub fn max_bytes(&self) -> usize {
+        self.max_bytes
+    }
+
+    /// Whether a file of `len` bytes is small enough to be cached.
+    pub fn accepts(&self, len: u64) -> bool {
+        len <= self.max_file_bytes.min(self.max_bytes) as u64
+    }
+
+    /// The cached contents of `path`, unless it has been modified since or
+    /// its size has changed.
+    fn get(&self, path: &Path, len: u64, modified: Option<SystemTime>) -> Option<Arc<[u8]>> {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries.by_path.get(path)?;
+        if entry.modified != modified || entry.contents.len() as u64 != len {
+            entries.remove(path);
+            return None;
+        }
+        let (last_used, contents) = (entry.last_used, entry.contents.clone());
+        entries.tick += 1;
+        let tick = entries.tick;
+        entries.by_use.remove(&last_used);
+        entries.by_use.insert(tick, path.to_path_buf());
+        if let Some(entry) = entries.by_path.get_mut(path) {
+            entry.last_used = tick;
+        }
+        Some(contents)
+    }
+
+    /// The contents of `path`, read from its `snapshot` and cached on a
+    /// miss. Also reports whether it was a hit.
+    pub async fn read(&self, path: &Path, snapshot: Snapshot) -> io::Result<(Arc<[u8]>, bool)> {
+        let modified = snapshot.metadata().modified().ok();
+        if let Some(contents) = self.get(path, snapshot.metadata().len(), modified) {
+            return Ok((contents, true));
+        }
+        let contents: Arc<[u8]> = snapshot.read_to_end().await?.into();
+        self.insert(path.to_path_buf(), contents.clone(), modified);
+        Ok((contents, false))
+    }
+
+    /// Evicts every file, or only those in one of `directories`, and
+    /// returns how many there were.
+    pub fn flush(&self, directories: Option<&Directories>) -> usize {
+        let mut entries = self.entries.lock().unwrap();
+        let flushed: Vec<PathBuf> = entries
+            .by_path
+            .keys()
+            .filter(|path| {
+                directories.is_none_or(|directories| {
+                    directories
+                        .iter()
+                        .any(|directory| path.starts_with(directory))
+                })
+            })
+            .cloned()
+            .collect();
+        for path in &flushed {
+            entries.remove(path);
+        }
+        flushed.len()
+    }
+
+    /// Evicts the least recently used files until `contents` fits.
+    fn insert(&self, path: PathBuf, contents: Arc<[u8]>, modified: Option<SystemTime>) {
+        if !self.accepts(contents.len() as u64) {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        entries.remove(&path);
+        while entries.bytes + contents.len() > self.max_bytes {
+            let oldest = match entries.by_use.values().next() {
+                Some(oldest) => oldest.clone(),
+                None => break,
+            };
+            entries.remove(&oldest);
+        }
+        entries.tick += 1;
+        let tick = entries.tick;
+        entries.bytes += contents.len();
+        entries.by_use.insert(tick, path.clone());
+        entries.by_path.insert(
+            path,
+            Entry {
+                contents,
+                modified,
+                last_used: tick,
+            },
+        );
+    }
+}
+
+/// A body of cached `contents` typed the way `Body::from_file` would type
+/// the file at `path`.
+pub fn body(path: &Path, contents: Arc<[u8]>) -> Body {
+    let mime = Mime::sniff(&contents[..contents.len().min(SNIFF_LEN)])
+        .ok()
+        .or_else(|| Mime::from_extension(path.extension()?.to_str()?))
+        .unwrap_or(mime::BYTE_STREAM);
+    let len = contents.len();
+    let mut body = Body::from_reader(Cursor::new(contents), Some(len));
+    body.set_mime(mime);
+    body
+}
@@ -0,0 +1,217 @@
+//! Trees of a project's files for `/api/projects/:project_name/files`.
+
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use tide::prelude::*;
+
+use crate::{archive::Archive, datetime, walk};
+
+/// Listing stops after this many entries, so one request can't build an
+/// unbounded response.
+const MAX_ENTRIES: usize = 100_000;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Node {
+    name: String,
+    /// Relative to the project root, with `/` separators.
+    path: String,
+    #[serde(rename = "type")]
+    kind: Kind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    modified: Option<String>,
+    /// Left out for files, and for directories deeper than was asked for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    children: Option<Vec<Node>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum Kind {
+    File,
+    Directory,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct Tree {
+    pub files: Vec<Node>,
+    pub truncated: bool,
+}
+
+/// Lists what `root` holds, `depth` levels down when given. Symlinks to
+/// files inside `root` are listed as those files; other symlinks aren't
+/// followed. Hidden entries are left out unless `show_hidden`.
+pub fn from_directory(root: &Path, depth: Option<usize>, show_hidden: bool) -> io::Result<Tree> {
+    let root = fs::canonicalize(root)?;
+    let mut tree = Tree::default();
+    let mut entries = 0;
+    tree.files = list_directory(
+        &root,
+        &root,
+        PathBuf::new(),
+        depth,
+        show_hidden,
+        &mut entries,
+        &mut tree.truncated,
+    )?;
+    Ok(tree)
+}
+
+fn list_directory(
+    root: &Path,
+    directory: &Path,
+    relative: PathBuf,
+    depth: Option<usize>,
+    show_hidden: bool,
+    entries: &mut usize,
+    truncated: &mut bool,
+) -> io::Result<Vec<Node>> {
+    let mut nodes = Vec::new();
+    let mut dir_entries: Vec<_> = fs::read_dir(directory)?
+        .filter_map(|entry| entry.ok())
+        .collect();
+    dir_entries.sort_by_key(|entry| entry.file_name());
+    for entry in dir_entries {
+        if *entries >= MAX_ENTRIES {
+            *truncated = true;
+            break;
+        }
+        let name = match entry.file_name().into_string() {
+            Ok(name) => name,
+            Err(_) => continue,
+        };
+        let path = relative.join(&name);
+        if !show_hidden && walk::is_hidden(&path) {
+            continue;
+        }
+        let mut metadata = match fs::symlink_metadata(entry.path()) {
+            Ok(metadata) => metadata,
+            Err(_) => continue,
+        };
+        if metadata.file_type().is_symlink() {
+            let inside_root =
+                fs::canonicalize(entry.path()).is_ok_and(|target| target.starts_with(root));
+            metadata = match fs::metadata(entry.path()) {
+                Ok(target) if inside_root && target.is_file() => target,
+                _ => continue,
+            };
+        }
+        *entries += 1;
+        let path_string = path.to_string_lossy().replace('\\', "/");
+        if metadata.is_dir() {
+            let children = match depth {
+                Some(depth) if depth <= 1 => None,
+                _ => Some(list_directory(
+                    root,
+                    &entry.path(),
+                    path,
+                    depth.map(|depth| depth - 1),
+                    show_hidden,
+                    entries,
+                    truncated,
+                )?),
+            };
+            nodes.push(Node::directory(name, path_string, children));
+        } else if metadata.is_file() {
+            nodes.push(Node::file(
+                name,
+                path_string,
+                metadata.len(),
+                metadata.modified().ok(),
+            ));
+        }
+    }
+    Ok(nodes)
+}
+
+/// Lists an archive's files, with directories implied by their paths.
+pub fn from_archive(archive: &Archive, depth: Option<usize>, show_hidden: bool) -> Tree {
+    #[derive(Default)]
+    struct Directory {
+        directories: BTreeMap<String, Directory>,
+        files: BTreeMap<String, (u64, Option<SystemTime>)>,
+    }
+
+    let mut root = Directory::default();
+    let mut names: Vec<&str> = archive
+        .names()
+        .filter(|name| show_hidden || !walk::is_hidden(Path::new(name)))
+        .collect();
+    names.sort_unstable();
+    let truncated = names.len() > MAX_ENTRIES;
+    for name in names.into_iter().take(MAX_ENTRIES) {
+        let entry = match archive.entry(name) {
+            Some(entry) => entry,
+            None => continue,
+        };
+        let mut segments: Vec<&str> = name.split('/').collect();
+        let file_name = segments.pop().unwrap_or_default();
+        let directory = segments.into_iter().fold(&mut root, |directory, segment| {
+            directory
+                .directories
+                .entry(segment.to_string())
+                .or_default()
+        });
+        directory
+            .files
+            .insert(file_name.to_string(), (entry.len, entry.modified));
+    }
+
+    fn into_nodes(directory: Directory, prefix: &str, depth: Option<usize>) -> Vec<Node> {
+        let mut nodes = Vec::new();
+        for (name, subdirectory) in directory.directories {
+            let path = format!("{}{}", prefix, name);
+            let children = match depth {
+                Some(depth) if depth <= 1 => None,
+                _ => Some(into_nodes(
+                    subdirectory,
+                    &format!("{}/", path),
+                    depth.map(|depth| depth - 1),
+                )),
+            };
+            nodes.push(Node::directory(name, path, children));
+        }
+        for (name, (len, modified)) in directory.files {
+            let path = format!("{}{}", prefix, name);
+            nodes.push(Node::file(name, path, len, modified));
+        }
+        nodes.sort_by(|a, b| a.name.cmp(&b.name));
+        nodes
+    }
+
+    Tree {
+        files: into_nodes(root, "", depth),
+        truncated,
+    }
+}
+
+impl Node {
+    fn directory(name: String, path: String, children: Option<Vec<Node>>) -> Self {
+        Node {
+            name,
+            path,
+            kind: Kind::Directory,
+            size: None,
+            modified: None,
+            children,
+        }
+    }
+
+    fn file(name: String, path: String, len: u64, modified: Option<SystemTime>) -> Self {
+        Node {
+            name,
+            path,
+            kind: Kind::File,
+            size: Some(len),
+            modified: modified.and_then(datetime::utc),
+            children: None,
+        }
+    }
+}
@@ -0,0 +1,345 @@
+//! A small gzip encoder: greedy LZ77 matching over a 32KiB window, emitted
+//! as fixed-Huffman deflate blocks. It trades some compression ratio for
+//! staying dependency free, which is plenty for HTML, CSS and JavaScript.
+
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_lite::{ready, AsyncRead};
+
+const WINDOW_SIZE: usize = 32 * 1024;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const MAX_CHAIN: usize = 64;
+const HASH_BITS: u32 = 15;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+const GZIP_HEADER: [u8; 10] = [0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff];
+
+/// How much of the source a `GzipReader` compresses at a time.
+const READ_CHUNK_SIZE: usize = 16 * 1024;
+
+/// Gzips a reader's bytes as they are read, so a large body is compressed
+/// a chunk at a time rather than held in memory.
+pub struct GzipReader<R> {
+    inner: R,
+    encoder: Option<GzipEncoder>,
+    chunk: Vec<u8>,
+    pending: Vec<u8>,
+    position: usize,
+}
+
+impl<R> GzipReader<R> {
+    pub fn new(inner: R) -> Self {
+        GzipReader {
+            inner,
+            encoder: Some(GzipEncoder::new()),
+            chunk: vec![0; READ_CHUNK_SIZE],
+            pending: Vec::new(),
+            position: 0,
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for GzipReader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        loop {
+            if this.position < this.pending.len() {
+                let len = buf.len().min(this.pending.len() - this.position);
+                buf[..len].copy_from_slice(&this.pending[this.position..this.position + len]);
+                this.position += len;
+                return Poll::Ready(Ok(len));
+            }
+            if this.encoder.is_none() {
+                return Poll::Ready(Ok(0));
+            }
+            let read = ready!(Pin::new(&mut this.inner).poll_read(cx, &mut this.chunk))?;
+            this.pending = if read == 0 {
+                this.encoder.take().unwrap().finish()
+            } else {
+                this.encoder.as_mut().unwrap().write(&this.chunk[..read])
+            };
+            this.position = 0;
+        }
+    }
+}
+
+/// Incremental gzip encoder. Each call to `write` emits a deflate block
+/// covering its input, so output is available as soon as input arrives.
+pub struct GzipEncoder {
+    deflater: Deflater,
+    crc: Crc32,
+    size: u32,
+    header_written: bool,
+}
+
+impl GzipEncoder {
+    pub fn new() -> Self {
+        GzipEncoder {
+            deflater: Deflater::new(),
+            crc: Crc32::new(),
+            size: 0,
+            header_written: false,
+        }
+    }
+
+    pub fn write(&mut self, data: &[u8]) -> Vec<u8> {
+        let mut output = self.header();
+        self.crc.update(data);
+        self.size = self.size.wrapping_add(data.len() as u32);
+        self.deflater.deflate(data, false, &mut output);
+        output
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        let mut output = self.header();
+        self.deflater.deflate(&[], true, &mut output);
+        output.extend(&self.crc.finish().to_le_bytes());
+        output.extend(&self.size.to_le_bytes());
+        output
+    }
+
+    fn header(&mut self) -> Vec<u8> {
+        if self.header_written {
+            Vec::new()
+        } else {
+            self.header_written = true;
+            GZIP_HEADER.to_vec()
+        }
+    }
+}
+
+struct Deflater {
+    /// The last `WINDOW_SIZE` bytes seen followed by any pending input.
+    buffer: Vec<u8>,
+    /// Absolute stream position of `buffer[0]`.
+    offset: usize,
+    head: Vec<usize>,
+    prev: Vec<usize>,
+    bits: BitWriter,
+}
+
+impl Deflater {
+    fn new() -> Self {
+        Deflater {
+            buffer: Vec::new(),
+            offset: 0,
+            head: vec![usize::MAX; 1 << HASH_BITS],
+            prev: vec![usize::MAX; WINDOW_SIZE],
+            bits: BitWriter::default(),
+        }
+    }
+
+    fn deflate(&mut self, data: &[u8], last: bool, output: &mut Vec<u8>) {
+        let start = self.buffer.len();
+        self.buffer.extend_from_slice(data);
+
+        // BFINAL followed by BTYPE 01, fixed Huffman codes.
+        self.bits.write(last as u32, 1);
+        self.bits.write(1, 2);
+
+        let mut position = start;
+        while position < self.buffer.len() {
+            match self.longest_match(position) {
+                Some((length, distance)) => {
+                    self.write_match(length, distance);
+                    for p in position..position + length {
+                        self.insert(p);
+                    }
+                    position += length;
+                }
+                None => {
+                    self.write_literal(self.buffer[position] as u16);
+                    self.insert(position);
+                    position += 1;
+                }
+            }
+        }
+        self.write_literal(256);
+
+        if last {
+            self.bits.flush();
+        }
+        output.append(&mut self.bits.bytes);
+
+        if self.buffer.len() > WINDOW_SIZE {
+            let excess = self.buffer.len() - WINDOW_SIZE;
+            self.buffer.drain(..excess);
+            self.offset += excess;
+        }
+    }
+
+    fn hash(&self, position: usize) -> Option<usize> {
+        let bytes = self.buffer.get(position..position + MIN_MATCH)?;
+        let value = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+        Some((value.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize)
+    }
+
+    fn insert(&mut self, position: usize) {
+        if let Some(hash) = self.hash(position) {
+            let absolute = self.offset + position;
+            self.prev[absolute % WINDOW_SIZE] = self.head[hash];
+            self.head[hash] = absolute;
+        }
+    }
+
+    fn longest_match(&self, position: usize) -> Option<(usize, usize)> {
+        let hash = self.hash(position)?;
+        let absolute = self.offset + position;
+        let max_length = (self.buffer.len() - position).min(MAX_MATCH);
+        let mut best: Option<(usize, usize)> = None;
+        let mut candidate = self.head[hash];
+        for _ in 0..MAX_CHAIN {
+            if candidate == usize::MAX
+                || candidate >= absolute
+                || candidate < self.offset
+                || absolute - candidate > WINDOW_SIZE
+            {
+                break;
+            }
+            let from = candidate - self.offset;
+            let length = self.buffer[from..]
+                .iter()
+                .zip(&self.buffer[position..position + max_length])
+                .take_while(|(a, b)| a == b)
+                .count();
+            if length >= MIN_MATCH && best.is_none_or(|(best_length, _)| length > best_length) {
+                best = Some((length, absolute - candidate));
+                if length == max_length {
+                    break;
+                }
+            }
+            let next = self.prev[candidate % WINDOW_SIZE];
+            if next >= candidate {
+                break;
+            }
+            candidate = next;
+        }
+        best
+    }
+
+    fn write_literal(&mut self, symbol: u16) {
+        let (code, length) = match symbol {
+            0..=143 => (0x30 + symbol as u32, 8),
+            144..=255 => (0x190 + (symbol as u32 - 144), 9),
+            256..=279 => (symbol as u32 - 256, 7),
+            _ => (0xc0 + (symbol as u32 - 280), 8),
+        };
+        self.bits.write_huffman(code, length);
+    }
+
+    fn write_match(&mut self, length: usize, distance: usize) {
+        let index = LENGTH_BASE
+            .iter()
+            .rposition(|&base| base as usize <= length)
+            .expect("match shorter than MIN_MATCH");
+        self.write_literal(257 + index as u16);
+        self.bits.write(
+            (length - LENGTH_BASE[index] as usize) as u32,
+            LENGTH_EXTRA[index] as u32,
+        );
+
+        let index = DISTANCE_BASE
+            .iter()
+            .rposition(|&base| base as usize <= distance)
+            .expect("distance of zero");
+        self.bits.write_huffman(index as u32, 5);
+        self.bits.write(
+            (distance - DISTANCE_BASE[index] as usize) as u32,
+            DISTANCE_EXTRA[index] as u32,
+        );
+    }
+}
+
+/// Deflate packs bits least significant first; Huffman codes are stored
+/// most significant bit first, so they are reversed on the way in.
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, count: u32) {
+        self.buffer |= value << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    fn write_huffman(&mut self, code: u32, length: u32) {
+        self.write(code.reverse_bits() >> (32 - length), length);
+    }
+
+    fn flush(&mut self) {
+        if self.count > 0 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer = 0;
+            self.count = 0;
+        }
+    }
+}
+
+struct Crc32 {
+    table: [u32; 256],
+    value: u32,
+}
+
+impl Crc32 {
+    fn new() -> Self {
+        let mut table = [0; 256];
+        for (n, entry) in table.iter_mut().enumerate() {
+            let mut c = n as u32;
+            for _ in 0..8 {
+                c = if c & 1 == 1 {
+                    0xedb8_8320 ^ (c >> 1)
+                } else {
+                    c >> 1
+                };
+            }
+            *entry = c;
+        }
+        Crc32 {
+            table,
+            value: 0xffff_ffff,
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        for &byte in data {
+            self.value =
+                self.table[((self.value ^ byte as u32) & 0xff) as usize] ^ (self.value >> 8);
+        }
+    }
+
+    fn finish(&self) -> u32 {
+        self.value ^ 0xffff_ffff
+    }
+}
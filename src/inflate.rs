@@ -0,0 +1,312 @@
+//! A streaming DEFLATE (RFC 1951) decoder, the counterpart to `gzip`, for
+//! reading compressed archive entries without unpacking them first.
+
+use std::io::{self, BufRead, Read};
+
+const WINDOW_SIZE: usize = 32 * 1024;
+const WINDOW_MASK: usize = WINDOW_SIZE - 1;
+const MAX_BITS: usize = 15;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// The order in which code length code lengths are sent.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// A canonical Huffman code, decoded a bit at a time by counting codes of
+/// each length.
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> io::Result<Self> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut left = 1i32;
+        for &count in &counts[1..] {
+            left = (left << 1) - count as i32;
+            if left < 0 {
+                return Err(invalid("oversubscribed Huffman code"));
+            }
+        }
+        let mut offsets = [0u16; MAX_BITS + 2];
+        for length in 1..=MAX_BITS {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0; offsets[MAX_BITS + 1] as usize];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+        Ok(Huffman { counts, symbols })
+    }
+
+    fn fixed() -> (Huffman, Huffman) {
+        let mut lengths = [0u8; 288];
+        lengths[..144].fill(8);
+        lengths[144..256].fill(9);
+        lengths[256..280].fill(7);
+        lengths[280..].fill(8);
+        let literals = Huffman::new(&lengths).unwrap();
+        let distances = Huffman::new(&[5; 30]).unwrap();
+        (literals, distances)
+    }
+}
+
+enum Block {
+    Header,
+    Stored(usize),
+    Compressed(Box<(Huffman, Huffman)>),
+    Done,
+}
+
+/// Decompresses a raw DEFLATE stream read from `input`.
+pub struct Inflater<R> {
+    input: R,
+    bit_buffer: u64,
+    bit_count: u32,
+    window: Vec<u8>,
+    written: usize,
+    block: Block,
+    final_block: bool,
+    copy_length: usize,
+    copy_distance: usize,
+}
+
+impl<R: BufRead> Inflater<R> {
+    pub fn new(input: R) -> Self {
+        Inflater {
+            input,
+            bit_buffer: 0,
+            bit_count: 0,
+            window: vec![0; WINDOW_SIZE],
+            written: 0,
+            block: Block::Header,
+            final_block: false,
+            copy_length: 0,
+            copy_distance: 0,
+        }
+    }
+
+    fn bits(&mut self, count: u32) -> io::Result<u32> {
+        while self.bit_count < count {
+            let byte = {
+                let buffer = self.input.fill_buf()?;
+                *buffer
+                    .first()
+                    .ok_or_else(|| invalid("unexpected end of compressed data"))?
+            };
+            self.input.consume(1);
+            self.bit_buffer |= (byte as u64) << self.bit_count;
+            self.bit_count += 8;
+        }
+        let value = (self.bit_buffer & ((1 << count) - 1)) as u32;
+        self.bit_buffer >>= count;
+        self.bit_count -= count;
+        Ok(value)
+    }
+
+    fn decode(&mut self, huffman: &Huffman) -> io::Result<u16> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for length in 1..=MAX_BITS {
+            code |= self.bits(1)? as i32;
+            let count = huffman.counts[length] as i32;
+            if code - first < count {
+                return Ok(huffman.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(invalid("invalid Huffman code"))
+    }
+
+    fn read_header(&mut self) -> io::Result<Block> {
+        if self.final_block {
+            return Ok(Block::Done);
+        }
+        self.final_block = self.bits(1)? == 1;
+        match self.bits(2)? {
+            0 => {
+                // Stored blocks start on a byte boundary.
+                let partial = self.bit_count % 8;
+                self.bit_buffer >>= partial;
+                self.bit_count -= partial;
+                let length = self.bits(16)?;
+                let complement = self.bits(16)?;
+                if length != !complement & 0xffff {
+                    return Err(invalid("corrupt stored block length"));
+                }
+                Ok(Block::Stored(length as usize))
+            }
+            1 => Ok(Block::Compressed(Box::new(Huffman::fixed()))),
+            2 => self.read_dynamic_codes(),
+            _ => Err(invalid("invalid block type")),
+        }
+    }
+
+    fn read_dynamic_codes(&mut self) -> io::Result<Block> {
+        let literal_count = self.bits(5)? as usize + 257;
+        let distance_count = self.bits(5)? as usize + 1;
+        let code_length_count = self.bits(4)? as usize + 4;
+        if literal_count > 286 || distance_count > 30 {
+            return Err(invalid("too many length or distance codes"));
+        }
+        let mut code_length_lengths = [0u8; 19];
+        for &index in &CODE_LENGTH_ORDER[..code_length_count] {
+            code_length_lengths[index] = self.bits(3)? as u8;
+        }
+        let code_lengths = Huffman::new(&code_length_lengths)?;
+
+        let mut lengths = vec![0u8; literal_count + distance_count];
+        let mut index = 0;
+        while index < lengths.len() {
+            let symbol = self.decode(&code_lengths)?;
+            let (length, repeat) = match symbol {
+                0..=15 => (symbol as u8, 1),
+                16 => {
+                    let previous = *index
+                        .checked_sub(1)
+                        .and_then(|previous| lengths.get(previous))
+                        .ok_or_else(|| invalid("repeated length with no previous length"))?;
+                    (previous, 3 + self.bits(2)? as usize)
+                }
+                17 => (0, 3 + self.bits(3)? as usize),
+                _ => (0, 11 + self.bits(7)? as usize),
+            };
+            if index + repeat > lengths.len() {
+                return Err(invalid("too many code lengths"));
+            }
+            lengths[index..index + repeat].fill(length);
+            index += repeat;
+        }
+        if lengths[256] == 0 {
+            return Err(invalid("missing end of block code"));
+        }
+        let literals = Huffman::new(&lengths[..literal_count])?;
+        let distances = Huffman::new(&lengths[literal_count..])?;
+        Ok(Block::Compressed(Box::new((literals, distances))))
+    }
+
+    fn emit(&mut self, byte: u8) {
+        self.window[self.written & WINDOW_MASK] = byte;
+        self.written += 1;
+    }
+}
+
+impl<R: BufRead> Read for Inflater<R> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let mut filled = 0;
+        while filled < buffer.len() {
+            if self.copy_length > 0 {
+                let byte = self.window[(self.written - self.copy_distance) & WINDOW_MASK];
+                self.emit(byte);
+                buffer[filled] = byte;
+                filled += 1;
+                self.copy_length -= 1;
+                continue;
+            }
+            match std::mem::replace(&mut self.block, Block::Done) {
+                Block::Header => self.block = self.read_header()?,
+                Block::Stored(0) => self.block = Block::Header,
+                Block::Stored(remaining) => {
+                    let byte = self.bits(8)? as u8;
+                    self.emit(byte);
+                    buffer[filled] = byte;
+                    filled += 1;
+                    self.block = Block::Stored(remaining - 1);
+                }
+                Block::Compressed(codes) => {
+                    let symbol = self.decode(&codes.0)?;
+                    match symbol {
+                        0..=255 => {
+                            self.emit(symbol as u8);
+                            buffer[filled] = symbol as u8;
+                            filled += 1;
+                            self.block = Block::Compressed(codes);
+                        }
+                        256 => self.block = Block::Header,
+                        257..=285 => {
+                            let index = symbol as usize - 257;
+                            let length = LENGTH_BASE[index] as usize
+                                + self.bits(LENGTH_EXTRA[index] as u32)? as usize;
+                            let index = self.decode(&codes.1)? as usize;
+                            if index >= DISTANCE_BASE.len() {
+                                return Err(invalid("invalid distance code"));
+                            }
+                            let distance = DISTANCE_BASE[index] as usize
+                                + self.bits(DISTANCE_EXTRA[index] as u32)? as usize;
+                            if distance > self.written.min(WINDOW_SIZE) {
+                                return Err(invalid("distance too far back"));
+                            }
+                            self.copy_length = length;
+                            self.copy_distance = distance;
+                            self.block = Block::Compressed(codes);
+                        }
+                        _ => return Err(invalid("invalid literal or length code")),
+                    }
+                }
+                Block::Done => break,
+            }
+        }
+        Ok(filled)
+    }
+}
+
+/// Skips the gzip (RFC 1952) member header, leaving `input` at the start of
+/// the DEFLATE stream.
+pub fn skip_gzip_header(input: &mut impl BufRead) -> io::Result<()> {
+    const FHCRC: u8 = 0x02;
+    const FEXTRA: u8 = 0x04;
+    const FNAME: u8 = 0x08;
+    const FCOMMENT: u8 = 0x10;
+
+    let mut header = [0u8; 10];
+    input.read_exact(&mut header)?;
+    if header[..3] != [0x1f, 0x8b, 8] {
+        return Err(invalid("not a gzip file"));
+    }
+    let flags = header[3];
+    if flags & FEXTRA != 0 {
+        let mut length = [0u8; 2];
+        input.read_exact(&mut length)?;
+        io::copy(
+            &mut input.take(u16::from_le_bytes(length) as u64),
+            &mut io::sink(),
+        )?;
+    }
+    for flag in [FNAME, FCOMMENT] {
+        if flags & flag != 0 {
+            input.read_until(0, &mut Vec::new())?;
+        }
+    }
+    if flags & FHCRC != 0 {
+        input.read_exact(&mut [0u8; 2])?;
+    }
+    Ok(())
+}
@@ -0,0 +1,75 @@
+//! Rustdoc JSON served in place of a project's HTML pages to clients that
+//! prefer `application/json`.
+
+use std::path::{Path, PathBuf};
+
+use tide::{http::content::Accept, prelude::*, Error, Request, Result, StatusCode};
+
+use crate::is_relative_path;
+
+/// Where the JSON for a page is: its path under `directory`, if given,
+/// with its extension replaced by `suffix`. By default `struct.Foo.html`
+/// has its JSON beside it, in `struct.Foo.json`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct JsonVariants {
+    /// Relative to the project directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub directory: Option<PathBuf>,
+    #[serde(default = "default_suffix")]
+    pub suffix: String,
+}
+
+fn default_suffix() -> String {
+    ".json".to_string()
+}
+
+impl JsonVariants {
+    pub fn check(&self) -> Result<()> {
+        if let Some(directory) = &self.directory {
+            if !is_relative_path(directory) {
+                return Err(Error::from_str(
+                    StatusCode::BadRequest,
+                    "json-variants directory must be a relative path inside the project",
+                ));
+            }
+        }
+        if self.suffix.is_empty() || self.suffix.contains('/') {
+            return Err(Error::from_str(
+                StatusCode::BadRequest,
+                "json-variants suffix must be a non-empty file name suffix",
+            ));
+        }
+        Ok(())
+    }
+
+    /// The variant of `relative_path`, an HTML page, relative to the same
+    /// root.
+    pub fn path_for(&self, relative_path: &Path) -> PathBuf {
+        let mut path = self.directory.clone().unwrap_or_default();
+        path.push(relative_path);
+        let mut path = path.with_extension("").into_os_string();
+        path.push(&self.suffix);
+        path.into()
+    }
+}
+
+/// Whether `Accept` weighs `application/json` above HTML. Ties go to HTML,
+/// as does a missing or malformed header.
+pub fn prefers_json<State>(req: &Request<State>) -> bool {
+    let accept = match Accept::from_headers(req) {
+        Ok(Some(accept)) => accept,
+        _ => return false,
+    };
+    let mut json = 0.0;
+    let mut html = 0.0;
+    for proposal in accept.iter() {
+        let weight = proposal.weight().unwrap_or(1.0);
+        match proposal.essence() {
+            "application/json" => json = weight.max(json),
+            "text/html" | "text/*" | "*/*" => html = weight.max(html),
+            _ => {}
+        }
+    }
+    json > html
+}
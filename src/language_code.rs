@@ -0,0 +1,43 @@
+//! Languages named by BCP 47 tags, with `--language-codes`, so that `EN`
+//! and `en` are one language and `english` isn't one at all.
+
+use std::io;
+
+use tide::{Error, StatusCode};
+
+/// `tag` lowercased, taking `_` for `-`, if it is shaped like a BCP 47 tag:
+/// an ISO 639 language code of two or three letters followed by subtags of
+/// up to eight letters or digits, as in `pt-br` or `zh-hant-tw`.
+pub fn normalize(tag: &str) -> Option<String> {
+    let tag = tag.to_ascii_lowercase().replace('_', "-");
+    let mut subtags = tag.split('-');
+    let language = subtags.next()?;
+    let valid = (2..=3).contains(&language.len())
+        && language.bytes().all(|b| b.is_ascii_lowercase())
+        && subtags.all(|subtag| {
+            (1..=8).contains(&subtag.len()) && subtag.bytes().all(|b| b.is_ascii_alphanumeric())
+        });
+    Some(tag).filter(|_| valid)
+}
+
+pub fn invalid(tag: &str) -> Error {
+    Error::from_str(
+        StatusCode::BadRequest,
+        format!(
+            "invalid language code `{}`, expected a BCP 47 tag such as `en` or `pt-br`",
+            tag
+        ),
+    )
+}
+
+/// Two configured languages that normalize to the same `code`, which would
+/// otherwise quietly have one's settings replace the other's.
+pub fn clash(first: &str, second: &str, code: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!(
+            "languages `{}` and `{}` are both the language code `{}`",
+            first, second, code
+        ),
+    )
+}
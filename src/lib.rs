@@ -0,0 +1,3917 @@
+//! dapsd, which serves documentation by language, from the host name,
+//! and project, from the first path segment. The binary only parses
+//! options and calls `build_app` and `serve`, so tests can drive the same
+//! app in-process.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    ffi::OsStr,
+    io, mem,
+    path::{Component, Path, PathBuf},
+    result::Result as StdResult,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use async_std::{
+    fs,
+    io::BufReader,
+    path::{Path as AsyncPath, PathBuf as AsyncPathBuf},
+    prelude::*,
+    sync::RwLock,
+    task,
+};
+use futures_lite::future;
+use percent_encoding::{percent_decode_str, utf8_percent_encode};
+use serde::{de::DeserializeOwned, Deserializer, Serializer};
+use tide::{
+    http::{
+        conditional::{ETag, IfModifiedSince, IfNoneMatch, LastModified, Vary},
+        content::{AcceptEncoding, ContentEncoding, Encoding},
+        headers::{
+            HeaderName, HeaderValue, ACCEPT, ACCEPT_ENCODING, ACCEPT_LANGUAGE, ACCEPT_RANGES,
+            ALLOW, AUTHORIZATION, CACHE_CONTROL, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_RANGE,
+            LOCATION, RETRY_AFTER,
+        },
+        mime, Method, Mime, Url,
+    },
+    listener::ConcurrentListener,
+    log,
+    prelude::*,
+    utils::async_trait,
+    Body, Endpoint, Error, Middleware, Next, Request, Response, Result, Route, Server, StatusCode,
+};
+
+mod access_log;
+mod api_error;
+mod archive;
+mod auth;
+mod autoindex;
+mod cache_control;
+mod concurrency;
+mod config;
+mod cors;
+mod datetime;
+mod directories;
+mod download;
+mod fallbacks;
+mod file_cache;
+mod files;
+mod gzip;
+mod inflate;
+mod json_variants;
+mod language_code;
+mod live_reload;
+mod maintenance;
+mod metrics;
+mod mime_types;
+mod options;
+mod proxy;
+mod range;
+mod rate_limit;
+mod redirects;
+mod robots;
+mod search;
+mod shutdown;
+mod sitemap;
+mod snapshot;
+mod state_file;
+mod timeout;
+mod unix_socket;
+mod version;
+mod walk;
+mod warmup;
+
+pub use access_log::start as start_logging;
+use access_log::AccessLog;
+use archive::ArchiveCache;
+use auth::Auth;
+use cache_control::CacheControl;
+use concurrency::PageSlots;
+use config::Config;
+pub use config::ConfigCheck;
+use cors::Cors;
+use directories::Directories;
+use fallbacks::Fallbacks;
+use file_cache::FileCache;
+use gzip::GzipReader;
+use json_variants::JsonVariants;
+use live_reload::LiveReload;
+use maintenance::Maintenance;
+use metrics::Metrics;
+use mime_types::MimeTypes;
+pub use options::{Options, OptionsError, USAGE};
+use range::ByteRange;
+use rate_limit::{RateLimit, RateLimits};
+use redirects::RedirectRule;
+use robots::Robots;
+use search::{SearchCache, SearchIndex};
+use shutdown::InFlight;
+use sitemap::{Pages, SitemapCache};
+use snapshot::Snapshot;
+use state_file::StateFile;
+use timeout::RequestTimeout;
+use warmup::Warmup;
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct LanguageName(String);
+
+impl LanguageName {
+    fn as_str(&self) -> &String {
+        &self.0
+    }
+}
+
+impl LanguageName {
+    /// The language named by the host the client asked for, in lowercase,
+    /// or by the first path segment with `--language-in-path`. With
+    /// `--language-codes` either is taken as a language code.
+    fn from_request(req: &Request<ServerState>) -> Result<Self> {
+        let options = &req.state().options;
+        let segment = req
+            .param("language")
+            .ok()
+            .filter(|_| options.language_in_path);
+        if !options.language_codes {
+            return match segment {
+                Some(segment) => Self::from_path_segment(segment),
+                None => Self::from_host_name(requested_host(req), &options.host_suffix),
+            };
+        }
+        let language = match segment {
+            Some(segment) => segment.to_string(),
+            None => Self::from_host_name(requested_host(req), &options.host_suffix)?.0,
+        };
+        language_code::normalize(&language)
+            .map(LanguageName)
+            .ok_or_else(|| language_code::invalid(&language))
+    }
+
+    fn from_path_segment(segment: &str) -> Result<Self> {
+        if !is_valid_name(segment) {
+            return Err(Error::from_str(
+                StatusCode::BadRequest,
+                "improper language name",
+            ));
+        }
+        Ok(LanguageName(segment.to_string()))
+    }
+
+    fn from_host_name(host_name_opt: Option<&str>, host_suffix: &str) -> Result<Self> {
+        host_name_opt
+            .ok_or(Error::from_str(
+                StatusCode::BadRequest,
+                "no hostname specified",
+            ))
+            .and_then(|host_name| {
+                strip_port(&host_name.to_ascii_lowercase())
+                    .strip_suffix(host_suffix)
+                    .map(String::from)
+                    .ok_or(Error::from_str(
+                        StatusCode::BadRequest,
+                        "improper domain name",
+                    ))
+            })
+            .map(LanguageName)
+    }
+}
+
+/// What page paths start with before the project name: the language's
+/// segment with `--language-in-path`, and nothing otherwise.
+fn mount_prefix(req: &Request<ServerState>, language_name: &LanguageName) -> String {
+    let options = &req.state().options;
+    if options.language_in_path {
+        format!("{}/{}", options.base_path, language_name.as_str())
+    } else {
+        options.base_path.clone()
+    }
+}
+
+/// The host the client asked for, which is the `X-Forwarded-Host` of
+/// requests from trusted proxies.
+fn requested_host(req: &Request<ServerState>) -> Option<&str> {
+    proxy::forwarded_host(req, &req.state().options.trusted_proxies)
+        .or_else(|| req.header("host").map(|host| host.last().as_str()))
+}
+
+/// The scheme and host, with any port, that pages in `language_name` are
+/// reached at: those of `--canonical-base`, with the language as a
+/// subdomain unless it is in the path, or else those of the request, as
+/// a trusted proxy forwarded them or as the listener received them.
+fn public_origin(req: &Request<ServerState>, language_name: &LanguageName) -> Option<String> {
+    let options = &req.state().options;
+    let base = match &options.canonical_base {
+        Some(base) => base,
+        None => {
+            let scheme = proxy::forwarded_proto(req, &options.trusted_proxies)
+                .unwrap_or_else(|| req.url().scheme());
+            return Some(format!("{}://{}", scheme, requested_host(req)?));
+        }
+    };
+    let host = base.host_str()?;
+    let port = base
+        .port()
+        .map_or_else(String::new, |port| format!(":{}", port));
+    Some(if options.language_in_path {
+        format!("{}://{}{}", base.scheme(), host, port)
+    } else {
+        format!(
+            "{}://{}.{}{}",
+            base.scheme(),
+            language_name.as_str(),
+            host,
+            port
+        )
+    })
+}
+
+/// Removes a trailing `:port`, leaving bracketed IPv6 literals and bare
+/// IPv6 addresses intact.
+fn strip_port(host: &str) -> &str {
+    if host.starts_with('[') {
+        return host.find(']').map_or(host, |end| &host[..=end]);
+    }
+    match host.split_once(':') {
+        Some((name, port)) if port.bytes().all(|b| b.is_ascii_digit()) => name,
+        _ => host,
+    }
+}
+
+type SharedLanguageDirectory = Arc<RwLock<LanguageDirectory>>;
+
+/// Everything handlers share: the registry and what was configured at
+/// startup.
+#[derive(Clone, Debug)]
+pub struct ServerState {
+    language_directory: SharedLanguageDirectory,
+    state_file: Option<Arc<StateFile>>,
+    options: Arc<Options>,
+    mime_types: Arc<MimeTypes>,
+    cache_control: Arc<CacheControl>,
+    auth: Arc<Auth>,
+    metrics: Arc<Metrics>,
+    live_reload: Option<Arc<LiveReload>>,
+    file_cache: Option<Arc<FileCache>>,
+    default_projects: Arc<HashMap<String, String>>,
+    /// The project serving each language's shared assets.
+    language_assets: Arc<HashMap<String, Project>>,
+    root_files: Option<Arc<Project>>,
+    landing_template: Option<Arc<str>>,
+    forbidden_page: Option<Arc<[u8]>>,
+    language_fallbacks: Arc<Fallbacks>,
+    robots: Arc<Robots>,
+    maintenance: Arc<Maintenance>,
+    page_slots: Arc<PageSlots>,
+    cors: Option<Cors>,
+    serve_limit: Option<RateLimit>,
+    api_limit: Option<RateLimit>,
+    in_flight: InFlight,
+}
+
+impl ServerState {
+    /// The 401 to send from API endpoints that change registrations when a
+    /// `--register-token` is set and the request doesn't carry it.
+    fn register_token_challenge(&self, req: &Request<ServerState>) -> Option<Response> {
+        let token = self.options.register_token.as_deref()?;
+        auth::bearer_challenge(req, token)
+    }
+
+    /// Drops the sitemaps of projects that changed since they were listed.
+    async fn invalidate_changed_sitemaps(&self) {
+        let sitemaps: Vec<(PathBuf, Arc<SitemapCache>)> = self
+            .language_directory
+            .read()
+            .await
+            .projects()
+            .map(|project| {
+                (
+                    project.directories.primary().clone(),
+                    project.sitemap.clone(),
+                )
+            })
+            .collect();
+        for (directory, sitemap) in sitemaps {
+            let listed_at = match sitemap.fingerprint().await {
+                Some(fingerprint) => fingerprint,
+                None => continue,
+            };
+            if walk::fingerprint(directory.clone().into()).await != listed_at {
+                log::debug!("Sitemap of {:?} is stale", directory);
+                sitemap.clear().await;
+            }
+        }
+    }
+
+    /// Unregisters projects whose directories can no longer be read.
+    /// Directories are checked before taking the write lock so that
+    /// requests aren't held up by IO.
+    async fn evict_vanished(&self) -> Result<Vec<Project>> {
+        let registered: Vec<Project> = self
+            .language_directory
+            .read()
+            .await
+            .projects()
+            .cloned()
+            .collect();
+        let vanished = vanished(registered).await;
+        if vanished.is_empty() {
+            return Ok(vanished);
+        }
+
+        let mut evicted = Vec::new();
+        let mut language_directory = self.language_directory.write().await;
+        for project in vanished {
+            let language_name = LanguageName(project.language.clone());
+            let version = project.version.as_deref();
+            // It may have been registered again while we were looking.
+            let unchanged = language_directory
+                .get(&language_name, &project.project_name, version)
+                .is_some_and(|current| current.directories == project.directories);
+            if unchanged {
+                let removed = language_directory.unregister(
+                    &language_name,
+                    &project.project_name,
+                    version,
+                )?;
+                log::info!(
+                    "Evicted {} with language {}, {:?} is gone",
+                    removed.project_name,
+                    removed.language,
+                    removed.directories
+                );
+                evicted.push(removed);
+            }
+        }
+        if !evicted.is_empty() {
+            self.persist(&language_directory).await;
+        }
+        Ok(evicted)
+    }
+
+    /// Saves the registry if persistence is enabled. The caller holds the
+    /// write lock so that concurrent changes are saved in order.
+    async fn persist(&self, language_directory: &LanguageDirectory) {
+        if let Some(state_file) = &self.state_file {
+            if let Err(e) = state_file.save(language_directory).await {
+                log::error!("Failed to save state to {:?}: {}", state_file.path(), e);
+            }
+        }
+    }
+}
+
+/// The projects whose directories can no longer be read.
+async fn vanished(projects: Vec<Project>) -> Vec<Project> {
+    let mut vanished = Vec::new();
+    for project in projects {
+        let mut readable = true;
+        for directory in project.directories.iter() {
+            readable = readable && check_directory(AsyncPath::new(directory)).await.is_ok();
+        }
+        if !readable {
+            vanished.push(project);
+        }
+    }
+    vanished
+}
+
+type LanguageMap = HashMap<String, Language>;
+
+#[derive(Clone, Debug, Default)]
+struct LanguageDirectory {
+    languages: LanguageMap,
+    /// Counts changes, so a copy can tell whether it is still current.
+    revision: u64,
+}
+
+impl LanguageDirectory {
+    fn language(&self, language_name: &LanguageName) -> Result<&Language> {
+        self.languages
+            .get(language_name.as_str())
+            .ok_or(Error::from_str(
+                StatusCode::NotFound,
+                "language has no registered projects",
+            ))
+    }
+
+    fn language_mut(&mut self, language_name: &LanguageName) -> Result<&mut Language> {
+        self.languages
+            .get_mut(language_name.as_str())
+            .ok_or(Error::from_str(
+                StatusCode::NotFound,
+                "language has no registered projects",
+            ))
+    }
+
+    fn projects(&self) -> impl Iterator<Item = &Project> {
+        self.languages
+            .values()
+            .flat_map(|language| language.projects.values())
+            .flat_map(ProjectVersions::iter)
+    }
+
+    fn into_projects(self) -> impl Iterator<Item = Project> {
+        self.languages
+            .into_values()
+            .flat_map(|language| language.projects.into_values())
+            .flat_map(ProjectVersions::into_iter)
+    }
+
+    /// Registration is last-writer-wins: registering a project name (and
+    /// version) that already exists for a language replaces the previous
+    /// entry.
+    fn register(&mut self, project: Project) {
+        self.revision += 1;
+        let language = self
+            .languages
+            .entry(project.language.clone())
+            .or_insert_with(|| Language {
+                name: project.language.clone(),
+                ..Language::default()
+            });
+        let project_name = project.project_name.clone();
+        let aliases = project.aliases.clone();
+        let versions = language.projects.entry(project_name.clone()).or_default();
+        if let Some(previous) = versions.insert(project) {
+            log::info!(
+                "Replaced {} in language {} previously located at {:?}",
+                previous.project_name,
+                language.name,
+                previous.directories
+            );
+            language.forget_aliases(&previous);
+        }
+        for alias in aliases {
+            if let Some(other) = language.aliases.insert(alias.clone(), project_name.clone()) {
+                if other != project_name {
+                    log::warn!(
+                        "Alias {} in language {} moved from {} to {}",
+                        alias,
+                        language.name,
+                        other,
+                        project_name
+                    );
+                }
+            }
+        }
+    }
+
+    fn get(
+        &self,
+        language_name: &LanguageName,
+        project_name: &str,
+        version: Option<&str>,
+    ) -> Option<&Project> {
+        self.languages
+            .get(language_name.as_str())?
+            .projects
+            .get(project_name)?
+            .get(version)
+    }
+
+    /// Projects are dropped once their last version is unregistered, and
+    /// languages once their last project is.
+    fn unregister(
+        &mut self,
+        language_name: &LanguageName,
+        project_name: &str,
+        version: Option<&str>,
+    ) -> Result<Project> {
+        let language = self.language_mut(language_name)?;
+        let versions = language
+            .projects
+            .get_mut(project_name)
+            .ok_or(Error::from_str(StatusCode::NotFound, "Project not found"))?;
+        let project = versions
+            .remove(version)
+            .ok_or(Error::from_str(StatusCode::NotFound, "Version not found"))?;
+        if versions.is_empty() {
+            language.projects.remove(project_name);
+        }
+        language.forget_aliases(&project);
+        if language.projects.is_empty() {
+            self.languages.remove(language_name.as_str());
+        }
+        self.revision += 1;
+        Ok(project)
+    }
+}
+
+/// Serialized as a flat list of projects, ordered by language and then
+/// project name so the output is stable.
+impl Serialize for LanguageDirectory {
+    fn serialize<S: Serializer>(&self, serializer: S) -> StdResult<S::Ok, S::Error> {
+        let mut projects: Vec<&Project> = self.projects().collect();
+        sort_projects(&mut projects);
+        serializer.collect_seq(projects)
+    }
+}
+
+impl<'de> Deserialize<'de> for LanguageDirectory {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> StdResult<Self, D::Error> {
+        let mut language_directory = LanguageDirectory::default();
+        for project in Vec::<Project>::deserialize(deserializer)? {
+            language_directory.register(project);
+        }
+        Ok(language_directory)
+    }
+}
+
+fn sort_projects(projects: &mut [&Project]) {
+    projects.sort_by(|a, b| {
+        (&a.language, &a.project_name, &a.version).cmp(&(&b.language, &b.project_name, &b.version))
+    });
+}
+
+type ProjectMap = HashMap<String, ProjectVersions>;
+
+#[derive(Clone, Debug, Default)]
+struct Language {
+    name: String,
+    projects: ProjectMap,
+    /// Other names projects answer to, mapped to the project name.
+    aliases: HashMap<String, String>,
+}
+
+impl Language {
+    /// Aliases are only consulted when no project has the name itself.
+    fn project(&self, project_name: &str) -> Result<&ProjectVersions> {
+        self.projects
+            .get(project_name)
+            .or_else(|| self.projects.get(self.aliases.get(project_name)?))
+            .ok_or(Error::from_str(StatusCode::NotFound, "Project not found"))
+    }
+
+    fn project_mut(&mut self, project_name: &str) -> Result<&mut ProjectVersions> {
+        let project_name = match self.aliases.get(project_name) {
+            Some(target) if !self.projects.contains_key(project_name) => target.clone(),
+            _ => project_name.to_string(),
+        };
+        self.projects
+            .get_mut(&project_name)
+            .ok_or(Error::from_str(StatusCode::NotFound, "Project not found"))
+    }
+
+    /// Drops the aliases of a removed or replaced registration, except those
+    /// another version of the same project still claims.
+    fn forget_aliases(&mut self, removed: &Project) {
+        let versions = self.projects.get(&removed.project_name);
+        for alias in &removed.aliases {
+            let still_claimed = versions.is_some_and(|versions| {
+                versions
+                    .iter()
+                    .any(|project| project.aliases.contains(alias))
+            });
+            if !still_claimed && self.aliases.get(alias) == Some(&removed.project_name) {
+                self.aliases.remove(alias);
+            }
+        }
+    }
+}
+
+/// Headers a project's `headers` may not set.
+const FRAMING_HEADERS: &[&str] = &["connection", "content-length", "transfer-encoding"];
+
+/// Path segment that resolves to whichever version is currently latest.
+const LATEST: &str = "latest";
+
+/// Every registration sharing a project name: an optional unversioned
+/// default plus any number of named versions.
+#[derive(Clone, Debug, Default)]
+struct ProjectVersions {
+    unversioned: Option<Project>,
+    versions: BTreeMap<String, Project>,
+}
+
+impl ProjectVersions {
+    fn insert(&mut self, project: Project) -> Option<Project> {
+        match project.version.clone() {
+            Some(version) => {
+                if project.latest {
+                    for other in self.versions.values_mut() {
+                        other.latest = false;
+                    }
+                }
+                self.versions.insert(version, project)
+            }
+            None => self.unversioned.replace(project),
+        }
+    }
+
+    fn remove(&mut self, version: Option<&str>) -> Option<Project> {
+        match version {
+            Some(version) => self.versions.remove(version),
+            None => self.unversioned.take(),
+        }
+    }
+
+    fn get(&self, version: Option<&str>) -> Option<&Project> {
+        match version {
+            Some(version) => self.versions.get(version),
+            None => self.unversioned.as_ref(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.unversioned.is_none() && self.versions.is_empty()
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &Project> {
+        self.unversioned.iter().chain(self.versions.values())
+    }
+
+    fn into_iter(self) -> impl Iterator<Item = Project> {
+        self.unversioned
+            .into_iter()
+            .chain(self.versions.into_values())
+    }
+
+    /// Version labels in `version::compare` order.
+    fn sorted_versions(&self) -> Vec<&str> {
+        let mut versions: Vec<&str> = self.versions.keys().map(String::as_str).collect();
+        versions.sort_by(|a, b| version::compare(a, b));
+        versions
+    }
+
+    /// The version registered or marked with `latest`, or else the highest
+    /// semantic version, preferring releases.
+    fn latest(&self) -> Option<&Project> {
+        self.versions
+            .values()
+            .find(|project| project.latest)
+            .or_else(|| {
+                self.versions
+                    .get(version::latest(self.versions.keys().map(String::as_str))?)
+            })
+    }
+
+    /// Marks `version` as the one `latest` resolves to. Returns false when
+    /// there is no such version.
+    fn set_latest(&mut self, version: &str) -> bool {
+        if !self.versions.contains_key(version) {
+            return false;
+        }
+        for (name, project) in &mut self.versions {
+            project.latest = name == version;
+        }
+        true
+    }
+
+    /// The named version, or else the unversioned registration, falling
+    /// back to the latest version when there is none.
+    fn for_version(&self, version: Option<&str>) -> Option<&Project> {
+        match version {
+            Some(LATEST) => self.latest(),
+            Some(version) => self.versions.get(version),
+            None => self.unversioned.as_ref().or_else(|| self.latest()),
+        }
+    }
+
+    /// Splits a leading version segment off `path`, falling back to the
+    /// unversioned registration with the whole path when there is none.
+    fn resolve<'a>(&self, path: &'a str) -> Option<(&Project, &'a str)> {
+        let (segment, rest) = path.split_once('/').unwrap_or((path, ""));
+        let versioned = if segment == LATEST {
+            self.latest()
+        } else {
+            self.versions.get(segment)
+        };
+        match versioned {
+            Some(project) => Some((project, rest)),
+            None => self.unversioned.as_ref().map(|project| (project, path)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct Project {
+    language: String,
+    project_name: String,
+    /// Earlier directories shadow later ones.
+    #[serde(rename = "directory")]
+    directories: Directories,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    version: Option<String>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    latest: bool,
+    /// List directories that have no index file instead of returning 404.
+    #[serde(default, skip_serializing_if = "is_false")]
+    autoindex: bool,
+    /// Former names, so links made before a rename keep working.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    aliases: Vec<String>,
+    /// Serve the root index file for missing pages, so that a client-side
+    /// router can handle them.
+    #[serde(default, skip_serializing_if = "is_false")]
+    spa: bool,
+    /// A page, relative to `directory`, to send with the 404 for files that
+    /// don't exist.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    not_found: Option<String>,
+    /// A page, relative to `directory`, to send with the 403 for paths
+    /// outside the project, in place of any `--forbidden-page`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    forbidden: Option<String>,
+    /// The root this project was found in by `/api/register/scan`, which
+    /// unregisters it once its directory is no longer there.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    scanned_from: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    metadata: Option<Metadata>,
+    /// Serve files and directories whose names start with a dot, which are
+    /// otherwise 404 so that a checkout's `.git` stays private. A leading
+    /// `.well-known` is always served.
+    #[serde(default, skip_serializing_if = "is_false")]
+    dotfiles: bool,
+    /// When a path doesn't exist, look for one that differs only in case,
+    /// for docs built on case-insensitive filesystems. Each miss then
+    /// costs a directory listing per path segment.
+    #[serde(default, skip_serializing_if = "is_false")]
+    case_insensitive: bool,
+    /// Files to serve for a directory, in order of preference, instead of
+    /// those given by `--index-files`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    index_files: Option<Vec<String>>,
+    /// Extra headers for successful responses, such as a
+    /// `Content-Security-Policy`. They replace any dapsd would send.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    headers: HashMap<String, String>,
+    /// Files to read into the file cache once registered.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    warmup: Option<Warmup>,
+    /// Consulted in order before looking for a file.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    redirects: Vec<RedirectRule>,
+    /// For a version other than the latest, give each HTML page a canonical
+    /// link to the same page in `latest`, when it is there.
+    #[serde(default, skip_serializing_if = "is_false")]
+    canonical_latest: bool,
+    /// Serve rustdoc JSON instead of a page to clients that prefer it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    json_variants: Option<JsonVariants>,
+    #[serde(skip)]
+    archive: Arc<ArchiveCache>,
+    #[serde(skip)]
+    search: Arc<SearchCache>,
+    #[serde(skip)]
+    sitemap: Arc<SitemapCache>,
+}
+
+/// Details for tooling such as a portal page, served by
+/// `/api/projects/:project_name`.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct Metadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    /// An `http` or `https` URL of the project's source.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    repository: Option<String>,
+    /// The version readers should start from, which may be `latest`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    default_version: Option<String>,
+}
+
+/// Project names and aliases become path segments and languages become
+/// part of the host name, so they are limited to `[a-z0-9._-]+`, and can't
+/// be dots alone.
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .bytes()
+            .all(|b| matches!(b, b'a'..=b'z' | b'0'..=b'9' | b'.' | b'_' | b'-'))
+        && !name.bytes().all(|b| b == b'.')
+}
+
+fn check_name(field: &str, name: &str) -> Result<()> {
+    if !is_valid_name(name) {
+        return Err(Error::from_str(
+            StatusCode::BadRequest,
+            format!(
+                "{} {:?} must be lowercase letters, digits, `.`, `_` and `-`, not only dots",
+                field, name
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Like `check_name`, for names that become a project's first path segment.
+fn check_project_name(field: &str, name: &str) -> Result<()> {
+    check_name(field, name)?;
+    if RESERVED_NAMES.contains(&name) {
+        return Err(Error::from_str(
+            StatusCode::BadRequest,
+            format!("{} {:?} is reserved for dapsd's own routes", field, name),
+        ));
+    }
+    Ok(())
+}
+
+/// Whether `path` names something inside a directory it is joined to.
+fn is_relative_path(path: &Path) -> bool {
+    path.components().next().is_some()
+        && path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+impl Project {
+    async fn serve_path(&self, req: &Request<ServerState>, path: &str) -> Result {
+        if archive::is_archive(self.directories.primary()) {
+            return self.serve_archive_path(req, path).await;
+        }
+        let primary = self.directories.primary();
+        let file_path = full_path_to(primary, path, req.state().options.clamp_parent_segments)?;
+        let relative_path = match file_path.strip_prefix(primary) {
+            Ok(relative_path) => relative_path.to_path_buf(),
+            Err(_) => return self.forbidden_page(req, &file_path).await,
+        };
+        if self.hides(&relative_path) {
+            return self.not_found_page(file_path.as_path().into()).await;
+        }
+        let mut directory = self.directory_with(&relative_path).await;
+        let mut file_path = AsyncPathBuf::from(directory.join(&relative_path));
+        if self.case_insensitive && !file_path.exists().await {
+            if let Some(found) = find_ignoring_case(directory, &relative_path).await {
+                file_path = found.into();
+            }
+        }
+        let url_path = req.url().path();
+        if file_path.is_dir().await {
+            if !url_path.ends_with('/') {
+                return Ok(permanent_redirect(req, format!("{}/", url_path)));
+            }
+            match self.index_file(req, &file_path).await {
+                Some(index_path) => file_path = index_path,
+                None if self.autoindex => {
+                    return self.list_directory(req, directory, &file_path).await
+                }
+                None => file_path.push(&self.index_names(req)[0]),
+            }
+        } else if url_path.ends_with('/') && file_path.is_file().await {
+            return Ok(permanent_redirect(req, url_path.trim_end_matches('/')));
+        }
+        if self.spa && is_client_route(file_path.as_ref()) && !file_path.exists().await {
+            let mut root_index = None;
+            for root in self.directories.iter() {
+                if let Some(index_path) = self.index_file(req, root.as_path().into()).await {
+                    root_index = Some((root, index_path));
+                    break;
+                }
+            }
+            (directory, file_path) = root_index
+                .unwrap_or_else(|| (primary, primary.join(&self.index_names(req)[0]).into()));
+        }
+        let cache_control = file_path
+            .strip_prefix(directory)
+            .map(|relative_path| {
+                req.state()
+                    .cache_control
+                    .for_path(relative_path.as_ref(), self.version.is_some())
+                    .to_string()
+            })
+            .ok();
+        // Downloads are named as requested, not after a symlink's target.
+        let mut file_name = file_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let file_path = match canonical_path_to(directory, &file_path).await {
+            Ok(Some(canonical_path)) => canonical_path,
+            Ok(None) => return self.forbidden_page(req, &file_path).await,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return self.not_found_page(&file_path).await
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let (json_exists, json_path) = self.json_variant(req, directory, &file_path).await?;
+        let file_path = match json_path {
+            Some(json_path) => {
+                if let Some(name) = json_path.file_name() {
+                    file_name = name.to_string_lossy().into_owned();
+                }
+                json_path
+            }
+            None => file_path,
+        };
+        // Live reload rewrites HTML, which a precompressed copy can't reflect.
+        let (variants_exist, variant) = if req.state().live_reload.is_some() {
+            (false, None)
+        } else {
+            self.precompressed_variant(req, directory, &file_path)
+                .await?
+        };
+        let served_path = variant.as_ref().map_or(&file_path, |(path, _)| path);
+        // Everything below describes the file as it was opened, however a
+        // rebuild changes it meanwhile.
+        let mut snapshot = match Snapshot::open(served_path).await {
+            Ok(snapshot) => snapshot,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return self.not_found_page(served_path).await
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let metadata = snapshot.metadata().clone();
+        let etag = weak_etag(metadata.len(), metadata.modified().ok());
+        let last_modified = last_modified(metadata.modified().ok());
+        let mut response = if not_modified(req, &etag, last_modified.as_ref())? {
+            Response::new(StatusCode::NotModified)
+        } else {
+            let len = metadata.len();
+            let last_modified_value = last_modified.as_ref().map(LastModified::value);
+            let range = ByteRange::from_request(
+                req,
+                len,
+                last_modified_value.as_ref().map(HeaderValue::as_str),
+            );
+            let mime_types = &req.state().mime_types;
+            // A precompressed copy would only sniff as its encoding.
+            let sniffed = match (mime_types.for_path(file_path.as_ref()), &variant) {
+                (None, None) => snapshot.sniff().await?,
+                _ => None,
+            };
+            let mime = mime_types
+                .for_path(file_path.as_ref())
+                .or(sniffed)
+                .unwrap_or_else(|| mime_types.guess(file_path.as_ref()));
+            let mime = mime_types::with_html_charset(mime);
+            let file_cache = req.state().file_cache.as_ref().filter(|file_cache| {
+                variant.is_none()
+                    && req.state().live_reload.is_none()
+                    && range == ByteRange::Full
+                    && file_cache.accepts(len)
+            });
+            let read = match range {
+                ByteRange::Full => match file_cache {
+                    Some(file_cache) => file_cache.read(file_path.as_ref(), snapshot).await.map(
+                        |(contents, hit)| {
+                            req.state().metrics.record_file_cache(hit);
+                            file_cache::body(file_path.as_ref(), contents)
+                        },
+                    ),
+                    None if variant.is_none()
+                        && req.state().live_reload.is_some()
+                        && mime.essence() == mime::HTML.essence() =>
+                    {
+                        match snapshot.read_to_end().await {
+                            Ok(html) => {
+                                let mut html = String::from_utf8_lossy(&html).into_owned();
+                                live_reload::inject_client(
+                                    &mut html,
+                                    &req.state().options.base_path,
+                                    &self.project_name,
+                                );
+                                Ok(Body::from_string(html))
+                            }
+                            Err(e) => Err(e),
+                        }
+                    }
+                    None => snapshot.body(0, len).await,
+                },
+                ByteRange::Partial(first, last) => snapshot.body(first, last - first + 1).await,
+                ByteRange::Unsatisfiable => Ok(Body::empty()),
+            };
+            let mut body = match read {
+                Ok(body) => body,
+                Err(e) if snapshot::is_changed(&e) => {
+                    log::warn!("{:?} changed while it was being read", served_path);
+                    return Ok(service_unavailable());
+                }
+                Err(e) => return Err(e.into()),
+            };
+            body.set_mime(mime);
+            let mut response = match range {
+                ByteRange::Full => Response::builder(StatusCode::Ok).body(body).build(),
+                ByteRange::Partial(first, last) => Response::builder(StatusCode::PartialContent)
+                    .body(body)
+                    .header(CONTENT_RANGE, format!("bytes {}-{}/{}", first, last, len))
+                    .build(),
+                ByteRange::Unsatisfiable => {
+                    return Ok(Response::builder(StatusCode::RequestedRangeNotSatisfiable)
+                        .header(CONTENT_RANGE, format!("bytes */{}", len))
+                        .build());
+                }
+            };
+            if let Some((_, encoding)) = &variant {
+                ContentEncoding::new(*encoding).apply(&mut response);
+            }
+            // A satisfied `Range` wins over compression: `Compression` leaves
+            // 206s alone and only gzips 200s, dropping this header as it does,
+            // so a range is always of the raw file.
+            response.insert_header(ACCEPT_RANGES, "bytes");
+            response
+        };
+        etag.apply(&mut response);
+        if let Some(cache_control) = cache_control {
+            response.insert_header(CACHE_CONTROL, cache_control);
+        }
+        if let Some(last_modified) = &last_modified {
+            last_modified.apply(&mut response);
+        }
+        if variants_exist {
+            add_vary(&mut response, ACCEPT_ENCODING)?;
+        }
+        if json_exists {
+            add_vary(&mut response, ACCEPT)?;
+        }
+        if let Some(disposition) = download::disposition(req, &file_name) {
+            response.insert_header(download::CONTENT_DISPOSITION, disposition);
+        }
+        Ok(response)
+    }
+
+    /// Serves an entry of an archive project. Entry names are normalized
+    /// when the archive is indexed, so the same lexical guard as for
+    /// directories keeps requests inside the archive.
+    async fn serve_archive_path(&self, req: &Request<ServerState>, path: &str) -> Result {
+        let directory = self.directories.primary();
+        let file_path = full_path_to(directory, path, req.state().options.clamp_parent_segments)?;
+        let name = match file_path.strip_prefix(directory) {
+            Ok(name) => name.to_string_lossy().into_owned(),
+            Err(_) => return self.forbidden_page(req, &file_path).await,
+        };
+        if self.hides(Path::new(&name)) {
+            return self.not_found_page(file_path.as_path().into()).await;
+        }
+        let archive = match self.archive.get(directory).await {
+            Ok(archive) => archive,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Ok(not_found(file_path.as_path().into()))
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let url_path = req.url().path();
+        let is_directory = name.is_empty() || archive.is_directory(&name);
+        if is_directory && !url_path.ends_with('/') {
+            return Ok(permanent_redirect(req, format!("{}/", url_path)));
+        }
+        if !is_directory && url_path.ends_with('/') && archive.entry(&name).is_some() {
+            return Ok(permanent_redirect(req, url_path.trim_end_matches('/')));
+        }
+        let name = match archive.entry(&name) {
+            None if self.case_insensitive && !is_directory => archive
+                .names()
+                .find(|entry_name| entry_name.to_lowercase() == name.to_lowercase())
+                .map_or(name, String::from),
+            _ => name,
+        };
+        let archive_index = |directory: &str| {
+            let index_names = self.index_names(req);
+            let candidates = index_names.iter().map(|index_name| {
+                Path::new(directory)
+                    .join(index_name)
+                    .to_string_lossy()
+                    .into_owned()
+            });
+            let mut first = None;
+            for candidate in candidates {
+                if archive.entry(&candidate).is_some() {
+                    return candidate;
+                }
+                first.get_or_insert(candidate);
+            }
+            first.unwrap_or_default()
+        };
+        let name = if is_directory {
+            archive_index(&name)
+        } else {
+            name
+        };
+        let name =
+            if self.spa && is_client_route(Path::new(&name)) && archive.entry(&name).is_none() {
+                archive_index("")
+            } else {
+                name
+            };
+        let entry = match archive.entry(&name) {
+            Some(entry) => entry,
+            None => return self.not_found_page(file_path.as_path().into()).await,
+        };
+
+        let etag = weak_etag(entry.len, entry.modified);
+        let last_modified = last_modified(entry.modified);
+        let mut response = if not_modified(req, &etag, last_modified.as_ref())? {
+            Response::new(StatusCode::NotModified)
+        } else {
+            let len = entry.len;
+            let last_modified_value = last_modified.as_ref().map(LastModified::value);
+            let range = ByteRange::from_request(
+                req,
+                len,
+                last_modified_value.as_ref().map(HeaderValue::as_str),
+            );
+            if range == ByteRange::Unsatisfiable {
+                return Ok(Response::builder(StatusCode::RequestedRangeNotSatisfiable)
+                    .header(CONTENT_RANGE, format!("bytes */{}", len))
+                    .build());
+            }
+            let contents = {
+                let (archive, name) = (archive.clone(), name.clone());
+                blocking::unblock(move || match archive.entry(&name) {
+                    Some(entry) => archive.read(entry),
+                    None => Ok(Vec::new()),
+                })
+                .await?
+            };
+            let mime = req.state().mime_types.guess(Path::new(&name));
+            let mut response = match range {
+                ByteRange::Partial(first, last) => {
+                    let mut body = Body::from(&contents[first as usize..=last as usize]);
+                    body.set_mime(mime);
+                    Response::builder(StatusCode::PartialContent)
+                        .body(body)
+                        .header(CONTENT_RANGE, format!("bytes {}-{}/{}", first, last, len))
+                        .build()
+                }
+                _ => {
+                    let mut body = Body::from(contents);
+                    body.set_mime(mime);
+                    Response::builder(StatusCode::Ok).body(body).build()
+                }
+            };
+            response.insert_header(ACCEPT_RANGES, "bytes");
+            response
+        };
+        etag.apply(&mut response);
+        let cache_control = req
+            .state()
+            .cache_control
+            .for_path(Path::new(&name), self.version.is_some());
+        response.insert_header(CACHE_CONTROL, cache_control);
+        if let Some(last_modified) = &last_modified {
+            last_modified.apply(&mut response);
+        }
+        let file_name = name.rsplit('/').next().unwrap_or_default();
+        if let Some(disposition) = download::disposition(req, file_name) {
+            response.insert_header(download::CONTENT_DISPOSITION, disposition);
+        }
+        Ok(response)
+    }
+
+    /// Lists `directory`, a directory under `root`, which is one of the
+    /// project's directories.
+    async fn list_directory(
+        &self,
+        req: &Request<ServerState>,
+        root: &Path,
+        directory: &AsyncPath,
+    ) -> Result {
+        let directory = match canonical_path_to(root, directory).await {
+            Ok(Some(canonical_path)) => canonical_path,
+            Ok(None) => return self.forbidden_page(req, directory).await,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return self.not_found_page(directory).await
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let root = fs::canonicalize(root).await?;
+        let listing = autoindex::render(&directory, &root, req.url().path(), self.dotfiles).await?;
+        Ok(Response::builder(StatusCode::Ok)
+            .body(listing)
+            .content_type(tide::http::mime::HTML)
+            .build())
+    }
+
+    /// The 404 for a missing `file_path`, with the project's `not-found`
+    /// page as its body when one is configured and can be read. The page
+    /// goes through the same traversal guards as any other file.
+    async fn not_found_page(&self, file_path: &AsyncPath) -> Result {
+        let response = not_found(file_path);
+        let page = match &self.not_found {
+            Some(page) => page,
+            None => return Ok(response),
+        };
+        match self.read_page(page).await? {
+            Some(contents) => Ok(error_page(StatusCode::NotFound, contents)),
+            None => {
+                log::warn!("Missing not-found page {:?} in {}", page, self.project_name);
+                Ok(response)
+            }
+        }
+    }
+
+    /// The 403 for `file_path`, which is outside the project, with the
+    /// project's `forbidden` page as its body, or else `--forbidden-page`.
+    /// Without either the body is empty.
+    async fn forbidden_page(
+        &self,
+        req: &Request<ServerState>,
+        file_path: impl AsRef<Path>,
+    ) -> Result {
+        log::info!("Unauthorized attempt to read: {:?}", file_path.as_ref());
+        if let Some(page) = &self.forbidden {
+            match self.read_page(page).await? {
+                Some(contents) => return Ok(error_page(StatusCode::Forbidden, contents)),
+                None => log::warn!("Missing forbidden page {:?} in {}", page, self.project_name),
+            }
+        }
+        Ok(match &req.state().forbidden_page {
+            Some(contents) => error_page(StatusCode::Forbidden, contents.to_vec()),
+            None => Response::new(StatusCode::Forbidden),
+        })
+    }
+
+    /// The contents of `page`, a path inside the project, unless it is
+    /// missing or leads out of the project.
+    async fn read_page(&self, page: &str) -> Result<Option<Vec<u8>>> {
+        if archive::is_archive(self.directories.primary()) {
+            let archive = self.archive.get(self.directories.primary()).await?;
+            let page = page.to_string();
+            return Ok(blocking::unblock(move || {
+                archive.entry(&page).map(|entry| archive.read(entry))
+            })
+            .await
+            .transpose()?);
+        }
+        let directory = self.directory_with(Path::new(page)).await;
+        let page_path = AsyncPathBuf::from(directory.join(page));
+        match canonical_path_to(directory, &page_path).await {
+            Ok(Some(page_path)) => Ok(Some(fs::read(page_path).await?)),
+            Ok(None) => {
+                log::info!("Unauthorized attempt to read: {:?}", &page_path);
+                Ok(None)
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Indexes the project's HTML pages off the async executor, since it
+    /// reads and tokenizes every page.
+    async fn build_search_index(&self) -> io::Result<SearchIndex> {
+        let directory = self.directories.primary();
+        if archive::is_archive(directory) {
+            let archive = self.archive.get(directory).await?;
+            blocking::unblock(move || SearchIndex::build_from_archive(&archive)).await
+        } else {
+            let directory = directory.clone();
+            blocking::unblock(move || SearchIndex::build_from_directory(&directory)).await
+        }
+    }
+
+    async fn list_sitemap_pages(&self) -> io::Result<Pages> {
+        let directory = self.directories.primary();
+        let fingerprint = walk::fingerprint(directory.clone().into()).await;
+        if archive::is_archive(directory) {
+            let archive = self.archive.get(directory).await?;
+            Ok(blocking::unblock(move || Pages::from_archive(&archive, fingerprint)).await)
+        } else {
+            let directory = directory.clone();
+            blocking::unblock(move || Pages::from_directory(&directory, fingerprint)).await
+        }
+    }
+
+    /// Whether the page at `file_path`, under `root`, has a JSON variant,
+    /// and that variant if the request prefers it.
+    async fn json_variant(
+        &self,
+        req: &Request<ServerState>,
+        root: &Path,
+        file_path: &AsyncPath,
+    ) -> io::Result<(bool, Option<AsyncPathBuf>)> {
+        let json_variants = match &self.json_variants {
+            Some(json_variants) => json_variants,
+            None => return Ok((false, None)),
+        };
+        let relative_path = match file_path.strip_prefix(root) {
+            Ok(relative_path) if walk::is_html(&relative_path.to_string_lossy()) => relative_path,
+            _ => return Ok((false, None)),
+        };
+        let json_path = root.join(json_variants.path_for(relative_path.as_ref()));
+        match canonical_path_to(root, json_path.as_path().into()).await {
+            Ok(Some(json_path)) if json_path.is_file().await => Ok((
+                true,
+                Some(json_path).filter(|_| json_variants::prefers_json(req)),
+            )),
+            Ok(_) => Ok((false, None)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok((false, None)),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Looks for a `.br` or `.gz` file alongside `file_path`, in `root`,
+    /// preferring brotli when the client accepts both. Also reports whether
+    /// any variant exists at all, since the response then varies by encoding.
+    async fn precompressed_variant(
+        &self,
+        req: &Request<ServerState>,
+        root: &Path,
+        file_path: &AsyncPath,
+    ) -> io::Result<(bool, Option<(AsyncPathBuf, Encoding)>)> {
+        let mut variants_exist = false;
+        for (extension, encoding) in PRECOMPRESSED_VARIANTS {
+            let mut variant_path = file_path.as_os_str().to_owned();
+            variant_path.push(extension);
+            match canonical_path_to(root, variant_path.as_ref()).await {
+                Ok(Some(variant_path)) => {
+                    variants_exist = true;
+                    if accepts_encoding(req, *encoding) {
+                        return Ok((true, Some((variant_path, *encoding))));
+                    }
+                }
+                Ok(None) => {}
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok((variants_exist, None))
+    }
+
+    /// Replaces the language with its language code, for `--language-codes`.
+    fn normalize_language(&mut self) -> Result<()> {
+        self.language = language_code::normalize(&self.language)
+            .ok_or_else(|| language_code::invalid(&self.language))?;
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<()> {
+        check_name("language", &self.language)?;
+        check_project_name("project-name", &self.project_name)?;
+        self.check_version()?;
+        self.check_aliases()?;
+        self.check_error_pages()?;
+        self.check_index_files()?;
+        self.check_headers()?;
+        self.check_warmup()?;
+        for redirect in &self.redirects {
+            redirect.check()?;
+        }
+        if let Some(json_variants) = &self.json_variants {
+            json_variants.check()?;
+        }
+        self.check_metadata()
+    }
+
+    /// Versions become a path segment, and `latest` is reserved.
+    fn check_version(&self) -> Result<()> {
+        match self.version.as_deref() {
+            Some(version) if version.is_empty() || version.contains('/') || version == LATEST => {
+                Err(Error::from_str(
+                    StatusCode::BadRequest,
+                    "version must be a single path segment other than `latest`",
+                ))
+            }
+            None if self.latest => Err(Error::from_str(
+                StatusCode::BadRequest,
+                "only a versioned registration can be marked latest",
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Aliases share the URL namespace with project names, so they must be
+    /// path segments too.
+    fn check_aliases(&self) -> Result<()> {
+        for alias in &self.aliases {
+            check_project_name("alias", alias)?;
+            if *alias == self.project_name {
+                return Err(Error::from_str(
+                    StatusCode::BadRequest,
+                    format!("alias {:?} can't be the project name", alias),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn check_error_pages(&self) -> Result<()> {
+        for (field, page) in &[
+            ("not-found", &self.not_found),
+            ("forbidden", &self.forbidden),
+        ] {
+            if page
+                .as_deref()
+                .is_some_and(|page| !is_relative_path(Path::new(page)))
+            {
+                return Err(Error::from_str(
+                    StatusCode::BadRequest,
+                    format!("{} must be a path inside the project directory", field),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn check_index_files(&self) -> Result<()> {
+        let index_files = match &self.index_files {
+            Some(index_files) => index_files,
+            None => return Ok(()),
+        };
+        let valid = !index_files.is_empty()
+            && index_files
+                .iter()
+                .all(|name| !name.is_empty() && !name.contains('/') && !name.starts_with('.'));
+        if !valid {
+            return Err(Error::from_str(
+                StatusCode::BadRequest,
+                "index-files must list at least one file name",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Names must be RFC 7230 tokens and values printable, so a header
+    /// can't split the response. Headers that frame the response are
+    /// dapsd's to send.
+    fn check_headers(&self) -> Result<()> {
+        for (name, value) in &self.headers {
+            let valid_name = !name.is_empty()
+                && name
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b));
+            if !valid_name {
+                return Err(Error::from_str(
+                    StatusCode::BadRequest,
+                    format!("invalid header name {:?}", name),
+                ));
+            }
+            if FRAMING_HEADERS.contains(&name.to_ascii_lowercase().as_str()) {
+                return Err(Error::from_str(
+                    StatusCode::BadRequest,
+                    format!("header {:?} can't be set per project", name),
+                ));
+            }
+            let valid_value = value
+                .bytes()
+                .all(|b| b == b'\t' || (b' '..=b'~').contains(&b));
+            if !valid_value {
+                return Err(Error::from_str(
+                    StatusCode::BadRequest,
+                    format!("invalid value for header {:?}", name),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn check_warmup(&self) -> Result<()> {
+        let warmup = match &self.warmup {
+            Some(warmup) => warmup,
+            None => return Ok(()),
+        };
+        if warmup.paths.is_empty() && warmup.max_size.is_none() {
+            return Err(Error::from_str(
+                StatusCode::BadRequest,
+                "warmup must give paths or a max-size",
+            ));
+        }
+        for path in &warmup.paths {
+            if !is_relative_path(Path::new(path)) {
+                return Err(Error::from_str(
+                    StatusCode::BadRequest,
+                    format!(
+                        "warmup path {:?} must be inside the project directory",
+                        path
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn check_metadata(&self) -> Result<()> {
+        let metadata = match &self.metadata {
+            Some(metadata) => metadata,
+            None => return Ok(()),
+        };
+        if let Some(repository) = &metadata.repository {
+            let is_web_url = Url::parse(repository)
+                .is_ok_and(|url| url.scheme() == "http" || url.scheme() == "https");
+            if !is_web_url {
+                return Err(Error::from_str(
+                    StatusCode::BadRequest,
+                    "repository must be an http or https URL",
+                ));
+            }
+        }
+        match metadata.default_version.as_deref() {
+            Some(version) if version.is_empty() || version.contains('/') => Err(Error::from_str(
+                StatusCode::BadRequest,
+                "default-version must be a single path segment",
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Reads the files `warmup` names into the file cache in the background.
+    fn start_warmup(&self, state: &ServerState, warmup: Warmup) {
+        let file_cache = match &state.file_cache {
+            Some(file_cache) => file_cache.clone(),
+            None => {
+                log::warn!(
+                    "Not warming {}, there is no --file-cache-size",
+                    self.project_name
+                );
+                return;
+            }
+        };
+        if archive::is_archive(self.directories.primary()) {
+            log::warn!(
+                "Not warming {}, archives aren't file cached",
+                self.project_name
+            );
+            return;
+        }
+        task::spawn(warmup::run(
+            warmup,
+            self.directories.primary().clone(),
+            self.dotfiles,
+            file_cache,
+        ));
+    }
+
+    /// Makes the project directories absolute, so that the traversal guards
+    /// in `serve_path` compare against a real root, and checks that they are
+    /// directories that can be listed. An archive can't be overlaid.
+    async fn canonicalize_directory(&mut self) -> io::Result<()> {
+        let overlay = self.directories.is_overlay();
+        for directory in self.directories.iter_mut() {
+            let canonical = fs::canonicalize(&directory).await?;
+            check_directory(&canonical).await?;
+            if archive::is_archive(canonical.as_ref()) {
+                if overlay {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "an archive can't be overlaid with other directories",
+                    ));
+                }
+                self.archive.get(canonical.as_ref()).await?;
+            }
+            *directory = canonical.into();
+        }
+        Ok(())
+    }
+
+    /// The first of the project's directories holding `relative_path`, so
+    /// that earlier directories shadow later ones, or else the first.
+    async fn directory_with(&self, relative_path: &Path) -> &Path {
+        if self.directories.is_overlay() {
+            for directory in self.directories.iter() {
+                if AsyncPath::new(&directory.join(relative_path))
+                    .exists()
+                    .await
+                    || (self.case_insensitive
+                        && find_ignoring_case(directory, relative_path).await.is_some())
+                {
+                    return directory;
+                }
+            }
+        }
+        self.directories.primary()
+    }
+
+    fn index_names<'a>(&'a self, req: &'a Request<ServerState>) -> &'a [String] {
+        self.index_files
+            .as_deref()
+            .unwrap_or(&req.state().options.index_files)
+    }
+
+    /// The first of the index files that exists in `directory`.
+    async fn index_file(
+        &self,
+        req: &Request<ServerState>,
+        directory: &AsyncPath,
+    ) -> Option<AsyncPathBuf> {
+        for index_name in self.index_names(req) {
+            let index_path = directory.join(index_name);
+            if index_path.is_file().await {
+                return Some(index_path);
+            }
+        }
+        None
+    }
+
+    /// What is known of the file `serve_path` would send for `path`,
+    /// without reading it, or None when there is no such page. Client
+    /// routes of single page apps don't count as pages.
+    async fn stat(&self, req: &Request<ServerState>, path: &str) -> Result<Option<FileStat>> {
+        let primary = self.directories.primary();
+        if archive::is_archive(primary) {
+            return self.stat_archive_path(req, path).await;
+        }
+        let file_path = full_path_to(primary, path, req.state().options.clamp_parent_segments)?;
+        let relative_path = match file_path.strip_prefix(primary) {
+            Ok(relative_path) => relative_path.to_path_buf(),
+            Err(_) => return Err(outside_project(&file_path)),
+        };
+        if self.hides(&relative_path) {
+            return Ok(None);
+        }
+        let directory = self.directory_with(&relative_path).await;
+        let mut file_path = AsyncPathBuf::from(directory.join(&relative_path));
+        if self.case_insensitive && !file_path.exists().await {
+            if let Some(found) = find_ignoring_case(directory, &relative_path).await {
+                file_path = found.into();
+            }
+        }
+        if file_path.is_dir().await {
+            match self.index_file(req, &file_path).await {
+                Some(index_path) => file_path = index_path,
+                None => return Ok(None),
+            }
+        }
+        let file_path = match canonical_path_to(directory, &file_path).await {
+            Ok(Some(canonical_path)) => canonical_path,
+            Ok(None) => return Err(outside_project(file_path.as_ref())),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let metadata = fs::metadata(&file_path).await?;
+        Ok(Some(FileStat {
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+            mime: req.state().mime_types.guess(file_path.as_ref()),
+        }))
+    }
+
+    async fn stat_archive_path(
+        &self,
+        req: &Request<ServerState>,
+        path: &str,
+    ) -> Result<Option<FileStat>> {
+        let directory = self.directories.primary();
+        let file_path = full_path_to(directory, path, req.state().options.clamp_parent_segments)?;
+        let name = match file_path.strip_prefix(directory) {
+            Ok(name) => name.to_string_lossy().into_owned(),
+            Err(_) => return Err(outside_project(&file_path)),
+        };
+        if self.hides(Path::new(&name)) {
+            return Ok(None);
+        }
+        let archive = match self.archive.get(directory).await {
+            Ok(archive) => archive,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let found = if name.is_empty() || archive.is_directory(&name) {
+            self.index_names(req).iter().find_map(|index_name| {
+                let candidate = Path::new(&name).join(index_name);
+                let candidate = candidate.to_string_lossy();
+                archive
+                    .entry(&candidate)
+                    .map(|entry| (candidate.into_owned(), entry))
+            })
+        } else {
+            archive
+                .entry(&name)
+                .map(|entry| (name.clone(), entry))
+                .or_else(|| {
+                    let wanted = name.to_lowercase();
+                    let found = archive
+                        .names()
+                        .find(|entry_name| entry_name.to_lowercase() == wanted)
+                        .filter(|_| self.case_insensitive)?;
+                    Some((found.to_string(), archive.entry(found)?))
+                })
+        };
+        Ok(found.map(|(name, entry)| FileStat {
+            len: entry.len,
+            modified: entry.modified,
+            mime: req.state().mime_types.guess(Path::new(&name)),
+        }))
+    }
+
+    /// Whether a path relative to the project root is kept from clients.
+    fn hides(&self, relative_path: &Path) -> bool {
+        !self.dotfiles && walk::is_hidden(relative_path)
+    }
+}
+
+/// `path` joined to `root`. Segments are percent-decoded before `.` and
+/// `..` are normalized so that encoded traversal is caught by the same
+/// checks as plain traversal. A `..` is a 400 unless `clamp_parent` lets it
+/// go up a directory, which it never does above `root`.
+fn full_path_to(root: &Path, path: &str, clamp_parent: bool) -> Result<PathBuf> {
+    let mut file_path = root.to_path_buf();
+    let mut depth = 0;
+    for segment in path.split('/') {
+        let segment = percent_decode_str(segment)
+            .decode_utf8()
+            .map_err(|_| Error::from_str(StatusCode::BadRequest, "path is not valid UTF-8"))?;
+        for p in Path::new(segment.as_ref()) {
+            if p == OsStr::new(".") {
+                continue;
+            } else if p == OsStr::new("..") {
+                if !clamp_parent {
+                    return Err(Error::from_str(
+                        StatusCode::BadRequest,
+                        "path must not contain `..`",
+                    ));
+                }
+                if depth > 0 {
+                    file_path.pop();
+                    depth -= 1;
+                }
+            } else {
+                file_path.push(p);
+                depth += 1;
+            }
+        }
+    }
+    Ok(file_path)
+}
+
+/// Resolves symlinks in both `root`, one of a project's directories, and
+/// `file_path`, returning `None` when the real target lies outside it.
+async fn canonical_path_to(root: &Path, file_path: &AsyncPath) -> io::Result<Option<AsyncPathBuf>> {
+    let root = fs::canonicalize(root).await?;
+    let canonical_path = fs::canonicalize(file_path).await?;
+    Ok(Some(canonical_path).filter(|path| path.starts_with(&root)))
+}
+
+/// The path under `root` matching `relative` segment by segment, each
+/// exactly or else ignoring case. Only entries of directories under `root`
+/// are considered, and symlinks are still checked against the root when
+/// the file is opened.
+async fn find_ignoring_case(root: &Path, relative: &Path) -> Option<PathBuf> {
+    let mut found = root.to_path_buf();
+    for segment in relative.iter() {
+        let exact = found.join(segment);
+        if AsyncPath::new(&exact).exists().await {
+            found = exact;
+            continue;
+        }
+        let wanted = segment.to_str()?.to_lowercase();
+        let mut entries = fs::read_dir(&found).await.ok()?;
+        let mut matched = None;
+        while let Some(Ok(entry)) = entries.next().await {
+            let name = entry.file_name();
+            if name
+                .to_str()
+                .is_some_and(|name| name.to_lowercase() == wanted)
+            {
+                matched = Some(name);
+                break;
+            }
+        }
+        found.push(matched?);
+    }
+    Some(found)
+}
+
+/// Whether a missing path may be a route for a single page app to handle,
+/// rather than an asset whose 404 shouldn't be masked with HTML.
+fn is_client_route(path: &Path) -> bool {
+    match path.extension().and_then(OsStr::to_str) {
+        Some(extension) => {
+            extension.eq_ignore_ascii_case("html") || extension.eq_ignore_ascii_case("htm")
+        }
+        None => true,
+    }
+}
+
+const PRECOMPRESSED_VARIANTS: &[(&str, Encoding)] =
+    &[(".br", Encoding::Brotli), (".gz", Encoding::Gzip)];
+
+/// Archives stand in for directories, so a `.zip` or `.tar.gz` file passes.
+async fn check_directory(directory: &AsyncPath) -> io::Result<()> {
+    let metadata = fs::metadata(directory).await?;
+    if archive::is_archive(directory.as_ref()) && metadata.is_file() {
+        return Ok(());
+    }
+    if !metadata.is_dir() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "not a directory",
+        ));
+    }
+    fs::read_dir(directory).await.map(drop)
+}
+
+/// An HTML page of `contents` sent with `status`.
+fn error_page(status: StatusCode, contents: Vec<u8>) -> Response {
+    let mut body = Body::from(contents);
+    body.set_mime(mime_types::with_html_charset(mime::HTML));
+    Response::builder(status).body(body).build()
+}
+
+fn not_found(file_path: &AsyncPath) -> Response {
+    log::warn!("File not found: {:?}", file_path);
+    Response::new(StatusCode::NotFound)
+}
+
+/// Weak because size and modification time only approximate the content.
+fn weak_etag(len: u64, modified: Option<SystemTime>) -> ETag {
+    let modified = modified
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |modified| modified.as_nanos());
+    ETag::new_weak(format!("{:x}-{:x}", len, modified))
+}
+
+/// Clamped to the present, since a modification time in the future is
+/// meaningless to clients and may not be representable as an HTTP-date.
+fn last_modified(modified: Option<SystemTime>) -> Option<LastModified> {
+    let modified = modified?.min(SystemTime::now());
+    modified.duration_since(UNIX_EPOCH).ok()?;
+    Some(LastModified::new(modified))
+}
+
+/// If-Modified-Since is only consulted when If-None-Match is absent, and
+/// is ignored when it can't be parsed.
+fn not_modified(
+    req: &Request<ServerState>,
+    etag: &ETag,
+    last_modified: Option<&LastModified>,
+) -> Result<bool> {
+    if let Some(if_none_match) = IfNoneMatch::from_headers(req)? {
+        return Ok(if_none_match.wildcard() || if_none_match.iter().any(|tag| same_tag(tag, etag)));
+    }
+    let if_modified_since = IfModifiedSince::from_headers(req).ok().flatten();
+    Ok(match (if_modified_since, last_modified) {
+        (Some(if_modified_since), Some(last_modified)) => {
+            let seconds = |time: SystemTime| {
+                time.duration_since(UNIX_EPOCH)
+                    .map_or(0, |duration| duration.as_secs())
+            };
+            seconds(last_modified.modified()) <= seconds(if_modified_since.modified())
+        }
+        _ => false,
+    })
+}
+
+/// If-None-Match uses the weak comparison function, which ignores the
+/// `W/` prefix.
+fn same_tag(a: &ETag, b: &ETag) -> bool {
+    fn tag(etag: &ETag) -> &str {
+        match etag {
+            ETag::Strong(tag) | ETag::Weak(tag) => tag,
+        }
+    }
+    tag(a) == tag(b)
+}
+
+/// Responses smaller than this aren't worth the CPU time to compress.
+const MIN_COMPRESSED_SIZE: usize = 1024;
+
+/// Compressible besides `text/*`. Images other than SVG, fonts and
+/// archives are already compressed.
+const COMPRESSIBLE_TYPES: &[&str] = &[
+    "application/javascript",
+    "application/json",
+    "image/svg+xml",
+];
+
+/// Gzips successful text responses for clients that accept it. Brotli isn't
+/// offered: none of the crates this builds against implement its encoder.
+///
+/// Ranges win over compression: a satisfied `Range` is a 206 of the raw
+/// bytes, which is left alone, while a compressed 200 drops the
+/// `Accept-Ranges` that `serve_path` added, as its ranges would be of the
+/// file rather than of the gzip stream the client sees.
+struct Compression;
+
+#[async_trait]
+impl<State: Clone + Send + Sync + 'static> Middleware<State> for Compression {
+    async fn handle(&self, req: Request<State>, next: Next<'_, State>) -> Result {
+        let accepts_gzip = accepts_encoding(&req, Encoding::Gzip);
+        let head = req.method() == Method::Head;
+        let mut response = next.run(req).await;
+        if response.status() != StatusCode::Ok
+            || response.header(CONTENT_ENCODING).is_some()
+            || !is_compressible(response.content_type())
+            || response.len().is_none_or(|len| len < MIN_COMPRESSED_SIZE)
+        {
+            return Ok(response);
+        }
+        add_vary(&mut response, ACCEPT_ENCODING)?;
+        if accepts_gzip {
+            // The compressed length isn't known until the end, so the
+            // response is sent chunked, without a Content-Length.
+            let body = response.take_body();
+            let mime = body.mime().clone();
+            // A HEAD body is already empty, and compressing the real one only
+            // to report its length isn't worth reading the file for.
+            let mut body = if head {
+                Body::from_reader(async_std::io::empty(), None)
+            } else {
+                Body::from_reader(BufReader::new(GzipReader::new(body)), None)
+            };
+            body.set_mime(mime);
+            response.remove_header(CONTENT_LENGTH);
+            response.remove_header(ACCEPT_RANGES);
+            ContentEncoding::new(Encoding::Gzip).apply(&mut response);
+            response.set_body(body);
+        }
+        Ok(response)
+    }
+}
+
+fn add_vary(response: &mut Response, header_name: HeaderName) -> Result<()> {
+    let mut vary = Vary::from_headers(&*response)?.unwrap_or_else(Vary::new);
+    if !vary.iter().any(|name| *name == header_name) {
+        vary.push(header_name)?;
+        vary.apply(response);
+    }
+    Ok(())
+}
+
+fn accepts_encoding<State>(req: &Request<State>, encoding: Encoding) -> bool {
+    match AcceptEncoding::from_headers(req).ok().flatten() {
+        Some(accept_encoding) => {
+            accept_encoding.wildcard()
+                || accept_encoding.iter().any(|proposal| {
+                    *proposal.encoding() == encoding && proposal.weight() != Some(0.0)
+                })
+        }
+        None => false,
+    }
+}
+
+fn is_compressible(mime: Option<Mime>) -> bool {
+    mime.is_some_and(|mime| {
+        mime.basetype() == "text" || COMPRESSIBLE_TYPES.contains(&mime.essence())
+    })
+}
+
+impl ServerState {
+    /// Registers the projects of the config file and the state file, the
+    /// latter winning since runtime registrations are newer.
+    pub async fn from_options(options: Options) -> io::Result<Self> {
+        let mut language_directory = LanguageDirectory::default();
+        let mut mime_types = MimeTypes::default();
+        let mut cache_control = CacheControl::default();
+        let mut cors = None;
+        let mut auth = Auth::default();
+        let mut default_projects = HashMap::new();
+        let mut language_assets = HashMap::new();
+        let mut root_files = None;
+        let mut language_fallbacks = Fallbacks::default();
+        let mut robots = Robots::default();
+        let mut serve_limit = None;
+        let mut api_limit = None;
+        let mut skipped = Vec::new();
+        if let Some(config_path) = &options.config {
+            let mut config = Config::load(config_path).await?;
+            if options.language_codes {
+                config.normalize_languages()?;
+            }
+            mime_types.extend(&config.mime_types)?;
+            cache_control = mem::take(&mut config.cache_control);
+            cors = config.cors.take();
+            auth = Auth::from_config(&config.auth)?;
+            default_projects = mem::take(&mut config.default_projects);
+            language_assets = config.asset_projects().await?;
+            root_files = config.root_files_project().await?.map(Arc::new);
+            language_fallbacks = mem::take(&mut config.language_fallbacks);
+            language_fallbacks.check().map_err(|e| {
+                io::Error::new(e.kind(), format!("invalid language-fallbacks: {}", e))
+            })?;
+            robots = mem::take(&mut config.robots);
+            let RateLimits {
+                serve,
+                api,
+                max_clients,
+            } = mem::take(&mut config.rate_limits);
+            serve_limit = serve
+                .map(|limit| RateLimit::new("serve", limit, max_clients))
+                .transpose()?;
+            api_limit = api
+                .map(|limit| RateLimit::new("api", limit, max_clients))
+                .transpose()?;
+            skipped = config.populate(&mut language_directory).await?;
+        }
+
+        let state_file = options.state_file.clone().map(StateFile::new);
+        if let Some(state_file) = &state_file {
+            let saved = state_file.load().await.map_err(|e| {
+                io::Error::new(
+                    e.kind(),
+                    format!("failed to load {:?}: {}", state_file.path(), e),
+                )
+            })?;
+            for project in saved.into_iter().flat_map(LanguageDirectory::into_projects) {
+                language_directory.register(project);
+            }
+        }
+
+        log_loaded(&language_directory, &skipped, options.config.as_deref());
+
+        let file_cache = options
+            .file_cache_size
+            .map(|max_bytes| Arc::new(FileCache::new(max_bytes, options.file_cache_max_file)));
+        let landing_template = match &options.landing_template {
+            Some(path) => Some(load_landing_template(path).await?),
+            None => None,
+        };
+        let forbidden_page = match &options.forbidden_page {
+            Some(path) => Some(fs::read(path).await.map_err(|e| {
+                io::Error::new(e.kind(), format!("failed to read {:?}: {}", path, e))
+            })?),
+            None => None,
+        };
+        Ok(ServerState {
+            language_directory: Arc::new(RwLock::new(language_directory)),
+            state_file: state_file.map(Arc::new),
+            mime_types: Arc::new(mime_types),
+            cache_control: Arc::new(cache_control),
+            auth: Arc::new(auth),
+            metrics: Arc::default(),
+            live_reload: options.dev.then(Arc::default),
+            file_cache,
+            default_projects: Arc::new(default_projects),
+            language_assets: Arc::new(language_assets),
+            root_files,
+            landing_template,
+            forbidden_page: forbidden_page.map(Arc::from),
+            language_fallbacks: Arc::new(language_fallbacks),
+            robots: Arc::new(robots),
+            maintenance: Arc::default(),
+            page_slots: Arc::new(PageSlots::new(options.max_connections)),
+            cors,
+            serve_limit,
+            api_limit,
+            in_flight: InFlight::default(),
+            options: Arc::new(options),
+        })
+    }
+}
+
+/// Summarizes the registry at startup, so that a misconfigured deployment
+/// shows up in the logs rather than as 404s later.
+fn log_loaded(
+    language_directory: &LanguageDirectory,
+    skipped: &[Directories],
+    config: Option<&Path>,
+) {
+    let mut languages: Vec<&Language> = language_directory.languages.values().collect();
+    languages.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+    let projects: usize = languages
+        .iter()
+        .map(|language| language.projects.len())
+        .sum();
+    let per_language: Vec<String> = languages
+        .iter()
+        .map(|language| format!("{} ({})", language.name, language.projects.len()))
+        .collect();
+    log::info!(
+        "Loaded {} projects in {} languages{}",
+        projects,
+        languages.len(),
+        if per_language.is_empty() {
+            String::new()
+        } else {
+            format!(": {}", per_language.join(", "))
+        }
+    );
+    if !skipped.is_empty() {
+        log::warn!(
+            "Skipped {} configured projects with invalid directories: {:?}",
+            skipped.len(),
+            skipped
+        );
+    }
+    if let Some(config_path) = config.filter(|_| projects == 0) {
+        log::warn!(
+            "No projects were loaded, though {:?} was given",
+            config_path
+        );
+    }
+}
+
+/// First path segments that `build_app` routes ahead of projects, so that
+/// a project or alias of the same name could never be served.
+const RESERVED_NAMES: &[&str] = &[
+    "api",
+    "healthz",
+    "metrics",
+    "__live_reload",
+    "robots.txt",
+    "favicon.ico",
+    "sitemap.xml",
+    ASSETS,
+    ROOT_FILES,
+];
+
+/// The server with every route, ready to listen or, in tests, to be sent
+/// requests with `Server::respond`.
+pub fn build_app(state: ServerState) -> Server<ServerState> {
+    let request_timeout = state.options.request_timeout.map(RequestTimeout::new);
+    let cors = state.cors.clone();
+    let serve_limit = state.serve_limit.clone();
+    let api_limit = state.api_limit.clone();
+    let dev = state.live_reload.is_some();
+    let language_in_path = state.options.language_in_path;
+    let in_flight = state.in_flight.clone();
+    let base_path = state.options.base_path.clone();
+    let at = |path: &str| format!("{}{}", base_path, path);
+    let mut app = tide::with_state(state);
+    app.with(in_flight);
+    app.with(AccessLog);
+    app.with(Compression);
+    read_only(&mut app.at(&at("/healthz")), healthz);
+    read_only(&mut app.at(&at("/metrics")), metrics);
+    if dev {
+        app.at(&at(live_reload::PATH)).get(live_reload::endpoint());
+    }
+    let mut api = app.at(&at("/api"));
+    api.with(api_error::JsonErrors);
+    if let Some(api_limit) = api_limit {
+        api.with(api_limit);
+    }
+    read_only(&mut api.at("/projects"), list_projects);
+    read_only(&mut api.at("/projects/:project_name"), project_metadata);
+    read_only(&mut api.at("/projects/:project_name/files"), project_files);
+    read_only(&mut api.at("/projects/:project_name/stat"), project_stat);
+    read_only(
+        &mut api.at("/projects/:project_name/versions"),
+        project_versions,
+    );
+    api.at("/projects/:project_name/latest")
+        .put(set_latest_version)
+        .all(allow("PUT, OPTIONS"));
+    api.at("/reload").post(reload).all(allow("POST, OPTIONS"));
+    api.at("/cache/flush")
+        .post(flush_file_cache)
+        .all(allow("POST, OPTIONS"));
+    let mut register_dir_route = api.at("/register/dir");
+    if let Some(request_timeout) = request_timeout {
+        register_dir_route.with(request_timeout);
+    }
+    register_dir_route
+        .post(register_dir)
+        .delete(unregister_dir)
+        .all(allow("POST, DELETE, OPTIONS"));
+    let mut register_dirs_route = api.at("/register/dirs");
+    if let Some(request_timeout) = request_timeout {
+        register_dirs_route.with(request_timeout);
+    }
+    register_dirs_route
+        .post(register_dirs)
+        .all(allow("POST, OPTIONS"));
+    let mut register_scan_route = api.at("/register/scan");
+    if let Some(request_timeout) = request_timeout {
+        register_scan_route.with(request_timeout);
+    }
+    register_scan_route
+        .post(register_scan)
+        .all(allow("POST, OPTIONS"));
+    api.at("/index/:project_name")
+        .post(index_project)
+        .all(allow("POST, OPTIONS"));
+    read_only(&mut api.at("/version"), build_info);
+    let mut robots_txt = app.at(&at("/robots.txt"));
+    if let Some(serve_limit) = &serve_limit {
+        robots_txt.with(serve_limit.clone());
+    }
+    read_only(&mut robots_txt, robots_txt_for_host);
+    let mut favicon_route = app.at(&at("/favicon.ico"));
+    if let Some(serve_limit) = &serve_limit {
+        favicon_route.with(serve_limit.clone());
+    }
+    read_only(&mut favicon_route, favicon);
+    let roots = if base_path.is_empty() {
+        vec![at("/")]
+    } else {
+        vec![base_path.clone(), at("/")]
+    };
+    for path in &roots {
+        let mut root = app.at(path);
+        if let Some(serve_limit) = &serve_limit {
+            root.with(serve_limit.clone());
+        }
+        if let Some(request_timeout) = request_timeout {
+            root.with(request_timeout);
+        }
+        read_only(&mut root, language_root);
+    }
+    let prefix = if language_in_path {
+        at("/:language")
+    } else {
+        base_path.clone()
+    };
+    if language_in_path {
+        for path in &[at("/:language"), at("/:language/")] {
+            let mut route = app.at(path);
+            if let Some(serve_limit) = &serve_limit {
+                route.with(serve_limit.clone());
+            }
+            if let Some(request_timeout) = request_timeout {
+                route.with(request_timeout);
+            }
+            read_only(&mut route, language_root);
+        }
+    }
+    let search_path = format!("{}/:project_name/search", prefix);
+    let sitemap_path = format!("{}/:project_name/sitemap.xml", prefix);
+    for path in &[
+        format!("{}/:project_name", prefix),
+        format!("{}/:project_name/", prefix),
+        format!("{}/:project_name/*path", prefix),
+        search_path.clone(),
+        sitemap_path.clone(),
+    ] {
+        let mut route = app.at(path);
+        if let Some(serve_limit) = &serve_limit {
+            route.with(serve_limit.clone());
+        }
+        if let Some(request_timeout) = request_timeout {
+            route.with(request_timeout);
+        }
+        if let Some(cors) = &cors {
+            route.with(cors.clone());
+        }
+        if *path == search_path {
+            read_only(&mut route, search);
+        } else if *path == sitemap_path {
+            read_only(&mut route, sitemap);
+        } else {
+            route.all(serve_page);
+        }
+    }
+    app
+}
+
+/// Loads the config file at `config_path` and validates it as startup
+/// would, with or without `--language-codes`, without registering or
+/// serving anything.
+pub async fn check_config(config_path: &Path, language_codes: bool) -> io::Result<ConfigCheck> {
+    let mut config = Config::load(config_path).await?;
+    if language_codes {
+        if let Err(e) = config.normalize_languages() {
+            return Ok(ConfigCheck {
+                errors: vec![e.to_string()],
+                ..ConfigCheck::default()
+            });
+        }
+    }
+    Ok(config.check().await)
+}
+
+/// Listens on the configured address and Unix socket until SIGINT or
+/// SIGTERM, then waits for in-flight requests and saves the registry.
+pub async fn serve(app: Server<ServerState>) -> io::Result<()> {
+    let state = app.state().clone();
+    let mut listener = ConcurrentListener::new();
+    if let Some(listen) = state.options.listen {
+        listener.add(listen)?;
+    }
+    if let Some(socket_path) = &state.options.unix_socket {
+        unix_socket::remove_stale(socket_path).map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("cannot listen on {:?}: {}", socket_path, e),
+            )
+        })?;
+        listener.add(socket_path.clone())?;
+    }
+    if state.options.register_token.is_none() {
+        log::warn!("No --register-token set, anyone who can connect may register directories");
+    }
+    if let Some(live_reload) = &state.live_reload {
+        log::info!("Development mode, serving HTML with live reload");
+        task::spawn(live_reload.clone().watch(state.language_directory.clone()));
+    }
+    if let Some(watch_interval) = state.options.watch_interval {
+        task::spawn(watch_directories(state.clone(), watch_interval));
+    }
+
+    // Dropping the listen future closes the listener, so once a signal
+    // arrives no new connections are accepted.
+    let shutdown_signal = shutdown::signal()?;
+    let server = async { app.listen(listener).await.map(|()| None) };
+    let signal = async { Ok(Some(shutdown_signal.await)) };
+    let signal = future::or(server, signal).await;
+    if let Some(socket_path) = &state.options.unix_socket {
+        unix_socket::remove(socket_path);
+    }
+    let signal = match signal? {
+        Some(signal) => signal,
+        None => return Ok(()),
+    };
+
+    log::info!("Shutting down", { signal: signal, in_flight: state.in_flight.count() });
+    let timeout = state.options.shutdown_timeout;
+    let remaining = state.in_flight.drain(timeout).await;
+    if remaining > 0 {
+        // Their connections close when the process exits.
+        log::warn!("Gave up waiting for in-flight requests", {
+            remaining: remaining,
+            timeout: format!("{:?}", timeout),
+        });
+    }
+    let language_directory = state.language_directory.write().await;
+    state.persist(&language_directory).await;
+    log::info!("Shutdown complete");
+    Ok(())
+}
+
+/// Polls every registered directory, so projects registered after startup
+/// are watched too, and evicts those that have been deleted or moved.
+/// Sitemaps of projects that have changed are listed again on next request.
+async fn watch_directories(state: ServerState, interval: Duration) {
+    loop {
+        task::sleep(interval).await;
+        if let Err(e) = state.evict_vanished().await {
+            log::error!("Failed to check project directories: {}", e);
+        }
+        state.invalidate_changed_sitemaps().await;
+    }
+}
+
+/// Parses the JSON body of a registration endpoint, refusing with 413 one
+/// bigger than `--max-register-body` before reading past the limit.
+async fn registration_json<T: DeserializeOwned>(req: &mut Request<ServerState>) -> Result<T> {
+    let limit = req.state().options.max_register_body;
+    let too_large = || {
+        Error::from_str(
+            StatusCode::PayloadTooLarge,
+            format!("registration body is larger than {} bytes", limit),
+        )
+    };
+    if req.len().is_some_and(|len| len > limit) {
+        return Err(too_large());
+    }
+    let mut body = Vec::new();
+    req.take_body()
+        .take(limit as u64 + 1)
+        .read_to_end(&mut body)
+        .await?;
+    if body.len() > limit {
+        return Err(too_large());
+    }
+    serde_json::from_slice(&body).map_err(|e| Error::new(StatusCode::UnprocessableEntity, e))
+}
+
+/// What `/api/register/dir` does when the project, or the version of it, is
+/// already registered, chosen with `?mode=`.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+enum RegisterMode {
+    /// 409 if it is.
+    Create,
+    /// 404 unless it is.
+    Update,
+    Upsert,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct RegisterQuery {
+    mode: Option<RegisterMode>,
+}
+
+/// Answers 201 when the registration is new and 200 when it replaces one.
+async fn register_dir(mut req: Request<ServerState>) -> Result {
+    if let Some(challenge) = req.state().register_token_challenge(&req) {
+        return Ok(challenge);
+    }
+    let RegisterQuery { mode } = req.query()?;
+    let mode = mode.unwrap_or(RegisterMode::Upsert);
+    let project = registration_json(&mut req).await?;
+    let project = validated_registration(req.state(), project).await?;
+    let state = req.state();
+    let mut language_directory = state.language_directory.write().await;
+    let exists = is_registered(&language_directory, &project);
+    let (status, message) = registration_outcome(&project, mode, exists)?;
+    if let Some(warmup) = project.warmup.clone() {
+        project.start_warmup(state, warmup);
+    }
+    language_directory.register(project);
+    state.persist(&language_directory).await;
+    Ok(Response::builder(status).body(message).build())
+}
+
+/// Checks a registration as both `/api/register/dir` and
+/// `/api/register/dirs` do.
+async fn validated_registration(state: &ServerState, mut project: Project) -> Result<Project> {
+    if state.options.language_codes {
+        project.normalize_language()?;
+    }
+    project.validate()?;
+    if let Err(e) = project.canonicalize_directory().await {
+        return Err(Error::from_str(
+            StatusCode::BadRequest,
+            format!("invalid directory {:?}: {}", project.directories, e),
+        ));
+    }
+    Ok(project)
+}
+
+fn is_registered(language_directory: &LanguageDirectory, project: &Project) -> bool {
+    language_directory
+        .get(
+            &LanguageName(project.language.clone()),
+            &project.project_name,
+            project.version.as_deref(),
+        )
+        .is_some()
+}
+
+/// The status and message for registering `project` under `mode`, or the
+/// error when `mode` forbids it.
+fn registration_outcome(
+    project: &Project,
+    mode: RegisterMode,
+    exists: bool,
+) -> Result<(StatusCode, String)> {
+    match mode {
+        RegisterMode::Create if exists => {
+            return Err(Error::from_str(
+                StatusCode::Conflict,
+                format!(
+                    "{} is already registered with language {}",
+                    project.project_name, project.language
+                ),
+            ))
+        }
+        RegisterMode::Update if !exists => {
+            return Err(Error::from_str(StatusCode::NotFound, "Project not found"))
+        }
+        _ => {}
+    }
+    let (status, message) = if exists {
+        (StatusCode::Ok, "Updated")
+    } else {
+        (StatusCode::Created, "Registered")
+    };
+    let message = format!(
+        "{} {} with language {} located at {:?}",
+        message, project.project_name, project.language, project.directories
+    );
+    Ok((status, message))
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct BulkRegisterQuery {
+    mode: Option<RegisterMode>,
+    /// Registers nothing if any project is invalid.
+    #[serde(default)]
+    atomic: bool,
+}
+
+/// What happened to one project of a bulk registration, with the status
+/// and message `/api/register/dir` would have answered.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct BulkResult {
+    /// Missing when the project couldn't be read at all.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    project_name: Option<String>,
+    status: u16,
+    message: String,
+}
+
+impl BulkResult {
+    fn new(project_name: Option<&str>, status: StatusCode, message: impl Into<String>) -> Self {
+        BulkResult {
+            project_name: project_name.map(str::to_string),
+            status: status as u16,
+            message: message.into(),
+        }
+    }
+}
+
+/// Registers an array of projects under a single lock and save, answering
+/// with a result for each one in order. Invalid projects are reported and
+/// the rest registered, unless `?atomic=true`, when any invalid project
+/// gets the whole batch rejected with a 400.
+async fn register_dirs(mut req: Request<ServerState>) -> Result {
+    if let Some(challenge) = req.state().register_token_challenge(&req) {
+        return Ok(challenge);
+    }
+    let BulkRegisterQuery { mode, atomic } = req.query()?;
+    let mode = mode.unwrap_or(RegisterMode::Upsert);
+    let items: Vec<serde_json::Value> = registration_json(&mut req).await?;
+    let mut checked = Vec::with_capacity(items.len());
+    for item in items {
+        let project_name = item
+            .get("project-name")
+            .and_then(serde_json::Value::as_str)
+            .map(str::to_string);
+        let project = match serde_json::from_value::<Project>(item) {
+            Ok(project) => validated_registration(req.state(), project).await,
+            Err(e) => Err(Error::new(StatusCode::UnprocessableEntity, e)),
+        };
+        checked.push((project_name, project));
+    }
+
+    let state = req.state();
+    let mut language_directory = state.language_directory.write().await;
+    // Earlier projects of the batch count as registered for later ones.
+    let mut batch = HashSet::new();
+    let mut outcomes = Vec::with_capacity(checked.len());
+    for (project_name, project) in checked {
+        let outcome = project.and_then(|project| {
+            let key = (
+                project.language.clone(),
+                project.project_name.clone(),
+                project.version.clone(),
+            );
+            let exists = batch.contains(&key) || is_registered(&language_directory, &project);
+            let outcome = registration_outcome(&project, mode, exists)?;
+            batch.insert(key);
+            Ok((project, outcome))
+        });
+        outcomes.push((project_name, outcome));
+    }
+    let rejected = atomic && outcomes.iter().any(|(_, outcome)| outcome.is_err());
+
+    let mut results = Vec::with_capacity(outcomes.len());
+    let mut registered = false;
+    for (project_name, outcome) in outcomes {
+        let result = match outcome {
+            Err(e) => BulkResult::new(project_name.as_deref(), e.status(), e.to_string()),
+            Ok((project, _)) if rejected => BulkResult::new(
+                Some(&project.project_name),
+                StatusCode::FailedDependency,
+                "Not registered, as another project in the batch is invalid",
+            ),
+            Ok((project, (status, message))) => {
+                let result = BulkResult::new(Some(&project.project_name), status, message);
+                if let Some(warmup) = project.warmup.clone() {
+                    project.start_warmup(state, warmup);
+                }
+                language_directory.register(project);
+                registered = true;
+                result
+            }
+        };
+        results.push(result);
+    }
+    if registered {
+        state.persist(&language_directory).await;
+    }
+    drop(language_directory);
+    let status = if rejected {
+        StatusCode::BadRequest
+    } else {
+        StatusCode::Ok
+    };
+    Ok(Response::builder(status)
+        .body(Body::from_json(&results)?)
+        .build())
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct ScanRequest {
+    language: String,
+    root: PathBuf,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct ScanSummary {
+    projects: Vec<String>,
+    added: Vec<Project>,
+    removed: Vec<Project>,
+}
+
+/// Registers each subdirectory of a root as a project named after it.
+/// Scanning the same root again registers new subdirectories and drops the
+/// projects of those that are gone.
+async fn register_scan(mut req: Request<ServerState>) -> Result {
+    if let Some(challenge) = req.state().register_token_challenge(&req) {
+        return Ok(challenge);
+    }
+    let ScanRequest { mut language, root } = registration_json(&mut req).await?;
+    if req.state().options.language_codes {
+        language =
+            language_code::normalize(&language).ok_or_else(|| language_code::invalid(&language))?;
+    }
+    check_name("language", &language)?;
+    let discovered = match scan_root(&language, &root).await {
+        Ok(discovered) => discovered,
+        Err(e) => {
+            return Err(Error::from_str(
+                StatusCode::BadRequest,
+                format!("invalid root {:?}: {}", root, e),
+            ))
+        }
+    };
+    let root = discovered.root;
+    let mut summary = ScanSummary {
+        projects: discovered
+            .projects
+            .iter()
+            .map(|project| project.project_name.clone())
+            .collect(),
+        ..ScanSummary::default()
+    };
+
+    let state = req.state();
+    let language_name = LanguageName(language.clone());
+    let mut language_directory = state.language_directory.write().await;
+    let gone: Vec<(String, Option<String>)> = language_directory
+        .projects()
+        .filter(|project| {
+            project.language == language
+                && project.scanned_from.as_ref() == Some(&root)
+                && !summary.projects.contains(&project.project_name)
+        })
+        .map(|project| (project.project_name.clone(), project.version.clone()))
+        .collect();
+    for (project_name, version) in gone {
+        summary.removed.push(language_directory.unregister(
+            &language_name,
+            &project_name,
+            version.as_deref(),
+        )?);
+    }
+    for project in discovered.projects {
+        let registered = language_directory
+            .get(&language_name, &project.project_name, None)
+            .is_some_and(|current| current.directories == project.directories);
+        if !registered {
+            summary.added.push(project.clone());
+            language_directory.register(project);
+        }
+    }
+    if !summary.added.is_empty() || !summary.removed.is_empty() {
+        state.persist(&language_directory).await;
+    }
+    drop(language_directory);
+    Ok(Body::from_json(&summary)?.into())
+}
+
+struct ScannedRoot {
+    root: PathBuf,
+    projects: Vec<Project>,
+}
+
+/// Hidden subdirectories, those not named like projects and those that
+/// can't be served are skipped.
+async fn scan_root(language: &str, root: &Path) -> io::Result<ScannedRoot> {
+    let root = fs::canonicalize(root).await?;
+    let mut projects = Vec::new();
+    let mut entries = fs::read_dir(&root).await?;
+    while let Some(entry) = entries.next().await {
+        let entry = entry?;
+        let project_name = match entry.file_name().into_string() {
+            Ok(name) if !name.starts_with('.') => name,
+            _ => continue,
+        };
+        if !entry.path().is_dir().await {
+            continue;
+        }
+        if let Err(e) = check_project_name("project-name", &project_name) {
+            log::warn!("Skipping a directory in language {}: {}", language, e);
+            continue;
+        }
+        let mut project = Project {
+            language: language.to_string(),
+            project_name,
+            directories: PathBuf::from(entry.path().into_os_string()).into(),
+            scanned_from: Some(root.clone().into()),
+            ..Project::default()
+        };
+        if let Err(e) = project.canonicalize_directory().await {
+            log::warn!(
+                "Skipping {} in language {}, {:?} can't be served: {}",
+                project.project_name,
+                language,
+                project.directories,
+                e
+            );
+            continue;
+        }
+        projects.push(project);
+    }
+    projects.sort_by(|a, b| a.project_name.cmp(&b.project_name));
+    Ok(ScannedRoot {
+        root: root.into(),
+        projects,
+    })
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct ProjectKey {
+    language: String,
+    project_name: String,
+    version: Option<String>,
+}
+
+async fn unregister_dir(mut req: Request<ServerState>) -> Result {
+    if let Some(challenge) = req.state().register_token_challenge(&req) {
+        return Ok(challenge);
+    }
+    let ProjectKey {
+        mut language,
+        project_name,
+        version,
+    } = registration_json(&mut req).await?;
+    let state = req.state();
+    if state.options.language_codes {
+        language =
+            language_code::normalize(&language).ok_or_else(|| language_code::invalid(&language))?;
+    }
+    let mut language_directory = state.language_directory.write().await;
+    let project = language_directory.unregister(
+        &LanguageName(language),
+        &project_name,
+        version.as_deref(),
+    )?;
+    state.persist(&language_directory).await;
+    Ok(format!(
+        "Unregistered {} with language {} located at {:?}",
+        project.project_name, project.language, project.directories
+    )
+    .into())
+}
+
+/// Narrows what `POST /api/cache/flush` drops.
+#[derive(Debug, Default, Deserialize)]
+struct FlushQuery {
+    project: Option<String>,
+    language: Option<String>,
+}
+
+/// Empties the file cache, or drops just the files of `?project=`, in every
+/// language unless `?language=` is given too, for files changed in a way
+/// the cache can't tell by their size and modification time.
+async fn flush_file_cache(req: Request<ServerState>) -> Result {
+    if let Some(challenge) = req.state().register_token_challenge(&req) {
+        return Ok(challenge);
+    }
+    let FlushQuery { project, language } = req.query()?;
+    let state = req.state();
+    let directories: Option<Vec<Directories>> = match &project {
+        Some(project_name) => {
+            let directories: Vec<Directories> = state
+                .language_directory
+                .read()
+                .await
+                .projects()
+                .filter(|project| {
+                    project.project_name == *project_name
+                        && language
+                            .as_ref()
+                            .is_none_or(|language| project.language == *language)
+                })
+                .map(|project| project.directories.clone())
+                .collect();
+            if directories.is_empty() {
+                return Err(Error::from_str(StatusCode::NotFound, "Project not found"));
+            }
+            Some(directories)
+        }
+        None => None,
+    };
+    let evicted = match (&state.file_cache, directories) {
+        (None, _) => 0,
+        (Some(file_cache), None) => file_cache.flush(None),
+        (Some(file_cache), Some(directories)) => directories
+            .iter()
+            .map(|directories| file_cache.flush(Some(directories)))
+            .sum(),
+    };
+    Ok(Body::from_json(&json!({ "evicted": evicted }))?.into())
+}
+
+#[derive(Debug, Default, Serialize)]
+struct ReloadSummary {
+    added: Vec<Project>,
+    removed: Vec<Project>,
+}
+
+/// Times `/api/reload` rebuilds the registry before giving up because
+/// registrations kept changing it meanwhile.
+const RELOAD_ATTEMPTS: usize = 3;
+
+/// Re-reads the config file and drops projects whose directories have
+/// vanished. As at startup, configured projects that are already registered
+/// are left alone, since runtime registrations are newer.
+///
+/// The new registry is built from a copy, without holding the lock, and
+/// swapped in during a maintenance window in which pages get 503.
+async fn reload(req: Request<ServerState>) -> Result {
+    if let Some(challenge) = req.state().register_token_challenge(&req) {
+        return Ok(challenge);
+    }
+    let state = req.state();
+    let configured = match &state.options.config {
+        Some(config_path) => {
+            let language_codes = state.options.language_codes;
+            let projects = async {
+                let mut config = Config::load(config_path).await?;
+                if language_codes {
+                    config.normalize_languages()?;
+                }
+                config.validated_projects().await
+            };
+            projects
+                .await
+                .map_err(|e| Error::from_str(StatusCode::InternalServerError, e.to_string()))?
+                .projects
+        }
+        None => Vec::new(),
+    };
+
+    for _ in 0..RELOAD_ATTEMPTS {
+        let mut rebuilt = state.language_directory.read().await.clone();
+        let revision = rebuilt.revision;
+        let summary = rebuild_directory(&mut rebuilt, configured.clone()).await?;
+
+        let _maintenance = state.maintenance.begin();
+        let mut language_directory = state.language_directory.write().await;
+        if language_directory.revision != revision {
+            log::debug!("Registrations changed during the reload, rebuilding");
+            continue;
+        }
+        *language_directory = rebuilt;
+        if !summary.added.is_empty() || !summary.removed.is_empty() {
+            state.persist(&language_directory).await;
+        }
+        let caches: Vec<(Arc<SearchCache>, Arc<SitemapCache>)> = language_directory
+            .projects()
+            .map(|project| (project.search.clone(), project.sitemap.clone()))
+            .collect();
+        drop(language_directory);
+        // Pages may have changed too, so indexes and sitemaps are rebuilt on
+        // next use.
+        for (search_cache, sitemap_cache) in caches {
+            search_cache.clear().await;
+            sitemap_cache.clear().await;
+        }
+        return Ok(Body::from_json(&summary)?.into());
+    }
+    Err(Error::from_str(
+        StatusCode::ServiceUnavailable,
+        "registrations kept changing during the reload",
+    ))
+}
+
+/// Drops vanished projects from `language_directory` and registers the
+/// configured ones it doesn't have.
+async fn rebuild_directory(
+    language_directory: &mut LanguageDirectory,
+    configured: Vec<Project>,
+) -> Result<ReloadSummary> {
+    let mut summary = ReloadSummary::default();
+    for project in vanished(language_directory.projects().cloned().collect()).await {
+        let removed = language_directory.unregister(
+            &LanguageName(project.language.clone()),
+            &project.project_name,
+            project.version.as_deref(),
+        )?;
+        log::info!(
+            "Evicted {} with language {}, {:?} is gone",
+            removed.project_name,
+            removed.language,
+            removed.directories
+        );
+        summary.removed.push(removed);
+    }
+    for project in configured {
+        let language_name = LanguageName(project.language.clone());
+        let registered = language_directory
+            .get(
+                &language_name,
+                &project.project_name,
+                project.version.as_deref(),
+            )
+            .is_some();
+        if !registered {
+            summary.added.push(project.clone());
+            language_directory.register(project);
+        }
+    }
+    Ok(summary)
+}
+
+#[derive(Debug, Deserialize)]
+struct IndexQuery {
+    language: String,
+    version: Option<String>,
+}
+
+/// Rebuilds a project's search index now, rather than when it is next
+/// searched.
+async fn index_project(req: Request<ServerState>) -> Result {
+    if let Some(challenge) = req.state().register_token_challenge(&req) {
+        return Ok(challenge);
+    }
+    let IndexQuery { language, version } = req.query()?;
+    let project = req
+        .state()
+        .language_directory
+        .read()
+        .await
+        .language(&LanguageName(language))?
+        .project(req.param("project_name")?)?
+        .for_version(version.as_deref())
+        .ok_or(Error::from_str(StatusCode::NotFound, "Version not found"))?
+        .clone();
+    let index = project.search.rebuild(project.build_search_index()).await?;
+    log::info!(
+        "Indexed {} pages of {} in language {}",
+        index.pages(),
+        project.project_name,
+        project.language
+    );
+    Ok(json!({
+        "pages": index.pages(),
+        "terms": index.terms(),
+        "truncated": index.truncated,
+    })
+    .into())
+}
+
+const DEFAULT_SEARCH_RESULTS: usize = 20;
+const MAX_SEARCH_RESULTS: usize = 100;
+
+#[derive(Debug, Deserialize)]
+struct SearchQuery {
+    q: String,
+    version: Option<String>,
+    limit: Option<usize>,
+}
+
+/// Ranked pages of a project matching every word of `?q=`, indexing the
+/// project first if need be.
+async fn search(req: Request<ServerState>) -> Result {
+    let language_name = LanguageName::from_request(&req)?;
+    if let Some(challenge) = req.state().auth.challenge(&req, language_name.as_str()) {
+        return Ok(challenge);
+    }
+    let SearchQuery { q, version, limit } = req.query()?;
+    let project = req
+        .state()
+        .language_directory
+        .read()
+        .await
+        .language(&language_name)?
+        .project(req.param("project_name")?)?
+        .for_version(version.as_deref())
+        .ok_or(Error::from_str(StatusCode::NotFound, "Version not found"))?
+        .clone();
+    let index = project.search.get(project.build_search_index()).await?;
+
+    let mut base = format!(
+        "{}/{}/",
+        mount_prefix(&req, &language_name),
+        utf8_percent_encode(&project.project_name, autoindex::PATH_SEGMENT)
+    );
+    if let Some(version) = &project.version {
+        base.push_str(&format!(
+            "{}/",
+            utf8_percent_encode(version, autoindex::PATH_SEGMENT)
+        ));
+    }
+    let limit = limit
+        .unwrap_or(DEFAULT_SEARCH_RESULTS)
+        .min(MAX_SEARCH_RESULTS);
+    let results: Vec<_> = index
+        .search(&q, limit)
+        .into_iter()
+        .map(|hit| {
+            let path: Vec<_> = hit
+                .path
+                .split('/')
+                .map(|segment| utf8_percent_encode(segment, autoindex::PATH_SEGMENT).to_string())
+                .collect();
+            json!({
+                "url": format!("{}{}", base, path.join("/")),
+                "title": hit.title,
+                "score": hit.score,
+            })
+        })
+        .collect();
+    Ok(json!({
+        "query": q,
+        "results": results,
+        "truncated": index.truncated,
+    })
+    .into())
+}
+
+#[derive(Debug, Deserialize)]
+struct SitemapQuery {
+    version: Option<String>,
+}
+
+/// The project's pages as a sitemap, with absolute URLs on the host the
+/// client asked for.
+async fn sitemap(req: Request<ServerState>) -> Result {
+    let language_name = LanguageName::from_request(&req)?;
+    if let Some(challenge) = req.state().auth.challenge(&req, language_name.as_str()) {
+        return Ok(challenge);
+    }
+    let SitemapQuery { version } = req.query()?;
+    let project = req
+        .state()
+        .language_directory
+        .read()
+        .await
+        .language(&language_name)?
+        .project(req.param("project_name")?)?
+        .for_version(version.as_deref())
+        .ok_or(Error::from_str(StatusCode::NotFound, "Version not found"))?
+        .clone();
+    let pages = project.sitemap.get(project.list_sitemap_pages()).await?;
+
+    let origin = public_origin(&req, &language_name).ok_or(Error::from_str(
+        StatusCode::InternalServerError,
+        "no hostname specified",
+    ))?;
+    let mut base = format!(
+        "{}{}/{}/",
+        origin,
+        mount_prefix(&req, &language_name),
+        utf8_percent_encode(&project.project_name, autoindex::PATH_SEGMENT)
+    );
+    if let Some(version) = &project.version {
+        base.push_str(&format!(
+            "{}/",
+            utf8_percent_encode(version, autoindex::PATH_SEGMENT)
+        ));
+    }
+    Ok(Response::builder(StatusCode::Ok)
+        .body(pages.render(&base))
+        .content_type("application/xml; charset=utf-8")
+        .build())
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ProjectsQuery {
+    language: Option<String>,
+    name_contains: Option<String>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+}
+
+/// Every registration, sorted by language, project name and version. Given
+/// any of `name_contains`, `limit` or `offset`, a page of them is wrapped in
+/// an object with the total that matched.
+async fn list_projects(req: Request<ServerState>) -> Result {
+    let ProjectsQuery {
+        mut language,
+        name_contains,
+        limit,
+        offset,
+    } = req.query()?;
+    if req.state().options.language_codes {
+        language = language
+            .map(|language| {
+                language_code::normalize(&language).ok_or_else(|| language_code::invalid(&language))
+            })
+            .transpose()?;
+    }
+    let paginated = name_contains.is_some() || limit.is_some() || offset.is_some();
+    let auth = &req.state().auth;
+    let language_directory = req.state().language_directory.read().await;
+    let mut projects: Vec<&Project> = match language {
+        Some(language_name) => {
+            if let Some(challenge) = auth.challenge(&req, &language_name) {
+                return Ok(challenge);
+            }
+            language_directory
+                .language(&LanguageName(language_name))?
+                .projects
+                .values()
+                .flat_map(ProjectVersions::iter)
+                .collect()
+        }
+        // Languages the request has no credentials for are left out, as
+        // their projects' directories are no more public than their pages.
+        None => {
+            let hidden: HashSet<&str> = language_directory
+                .languages
+                .values()
+                .map(|language| language.name.as_str())
+                .filter(|language| auth.challenge(&req, language).is_some())
+                .collect();
+            language_directory
+                .projects()
+                .filter(|project| !hidden.contains(project.language.as_str()))
+                .collect()
+        }
+    };
+    if let Some(name_contains) = name_contains {
+        let name_contains = name_contains.to_lowercase();
+        projects.retain(|project| project.project_name.contains(&name_contains));
+    }
+    sort_projects(&mut projects);
+    if !paginated {
+        return Ok(Body::from_json(&projects)?.into());
+    }
+    let total = projects.len();
+    let offset = offset.unwrap_or(0);
+    let page: Vec<_> = projects
+        .into_iter()
+        .skip(offset)
+        .take(limit.unwrap_or(usize::MAX))
+        .collect();
+    Ok(json!({
+        "projects": page,
+        "total": total,
+        "offset": offset,
+        "limit": limit,
+    })
+    .into())
+}
+
+/// The metadata of a project in the host's language, from its unversioned
+/// registration or else its latest version, along with its versions.
+async fn project_metadata(req: Request<ServerState>) -> Result {
+    let language_name = LanguageName::from_request(&req)?;
+    if let Some(challenge) = req.state().auth.challenge(&req, language_name.as_str()) {
+        return Ok(challenge);
+    }
+    let language_directory = req.state().language_directory.read().await;
+    let versions = language_directory
+        .language(&language_name)?
+        .project(req.param("project_name")?)?;
+    let project = versions
+        .for_version(None)
+        .ok_or(Error::from_str(StatusCode::NotFound, "Project not found"))?;
+    let metadata = project.metadata.clone().unwrap_or_default();
+    Ok(json!({
+        "language": project.language,
+        "project-name": project.project_name,
+        "title": metadata.title,
+        "description": metadata.description,
+        "repository": metadata.repository,
+        "default-version": metadata.default_version,
+        "versions": versions.sorted_versions(),
+        "latest": versions.latest().and_then(|latest| latest.version.as_deref()),
+    })
+    .into())
+}
+
+/// The registered versions of a project in the host's language, and which
+/// of them `latest` resolves to.
+async fn project_versions(req: Request<ServerState>) -> Result {
+    let language_name = LanguageName::from_request(&req)?;
+    if let Some(challenge) = req.state().auth.challenge(&req, language_name.as_str()) {
+        return Ok(challenge);
+    }
+    let language_directory = req.state().language_directory.read().await;
+    let versions = language_directory
+        .language(&language_name)?
+        .project(req.param("project_name")?)?;
+    Ok(versions_json(versions).into())
+}
+
+#[derive(Debug, Deserialize)]
+struct LatestRequest {
+    version: String,
+}
+
+/// Points `latest` at another registered version of a project in the
+/// host's language.
+async fn set_latest_version(mut req: Request<ServerState>) -> Result {
+    if let Some(challenge) = req.state().register_token_challenge(&req) {
+        return Ok(challenge);
+    }
+    let language_name = LanguageName::from_request(&req)?;
+    let LatestRequest { version } = registration_json(&mut req).await?;
+    let state = req.state();
+    let mut language_directory = state.language_directory.write().await;
+    let versions = language_directory
+        .language_mut(&language_name)?
+        .project_mut(req.param("project_name")?)?;
+    if !versions.set_latest(&version) {
+        return Err(Error::from_str(StatusCode::NotFound, "Version not found"));
+    }
+    let body = versions_json(versions);
+    log::info!(
+        "Marked version {} of {} in language {} latest",
+        version,
+        req.param("project_name")?,
+        language_name.as_str()
+    );
+    language_directory.revision += 1;
+    state.persist(&language_directory).await;
+    Ok(body.into())
+}
+
+fn versions_json(versions: &ProjectVersions) -> serde_json::Value {
+    let latest = versions
+        .latest()
+        .and_then(|latest| latest.version.as_deref());
+    let listed: Vec<_> = versions
+        .sorted_versions()
+        .into_iter()
+        .map(|version| json!({ "version": version, "latest": Some(version) == latest }))
+        .collect();
+    json!({
+        "versions": listed,
+        "latest": latest,
+        "unversioned": versions.unversioned.is_some(),
+    })
+}
+
+#[derive(Debug, Deserialize)]
+struct FilesQuery {
+    version: Option<String>,
+    depth: Option<usize>,
+}
+
+/// The tree of a project's files in the host's language, `?depth=` levels
+/// deep when given.
+async fn project_files(req: Request<ServerState>) -> Result {
+    let language_name = LanguageName::from_request(&req)?;
+    if let Some(challenge) = req.state().auth.challenge(&req, language_name.as_str()) {
+        return Ok(challenge);
+    }
+    let FilesQuery { version, depth } = req.query()?;
+    if depth == Some(0) {
+        return Err(Error::from_str(
+            StatusCode::BadRequest,
+            "depth must be at least 1",
+        ));
+    }
+    let project = req
+        .state()
+        .language_directory
+        .read()
+        .await
+        .language(&language_name)?
+        .project(req.param("project_name")?)?
+        .for_version(version.as_deref())
+        .ok_or(Error::from_str(StatusCode::NotFound, "Version not found"))?
+        .clone();
+    let directory = project.directories.primary();
+    let tree = if archive::is_archive(directory) {
+        let archive = project.archive.get(directory).await?;
+        let dotfiles = project.dotfiles;
+        blocking::unblock(move || files::from_archive(&archive, depth, dotfiles)).await
+    } else {
+        let (directory, dotfiles) = (directory.clone(), project.dotfiles);
+        blocking::unblock(move || files::from_directory(&directory, depth, dotfiles)).await?
+    };
+    Ok(Body::from_json(&tree)?.into())
+}
+
+#[derive(Debug, Deserialize)]
+struct StatQuery {
+    path: String,
+    version: Option<String>,
+}
+
+struct FileStat {
+    len: u64,
+    modified: Option<SystemTime>,
+    mime: Mime,
+}
+
+fn outside_project(file_path: &Path) -> Error {
+    log::info!("Unauthorized attempt to read: {:?}", file_path);
+    Error::from_str(StatusCode::Forbidden, "path is outside the project")
+}
+
+/// Whether `?path=` is a page of the project, and its size, modification
+/// time and type, for link checkers that don't need the contents.
+async fn project_stat(req: Request<ServerState>) -> Result {
+    let language_name = LanguageName::from_request(&req)?;
+    if let Some(challenge) = req.state().auth.challenge(&req, language_name.as_str()) {
+        return Ok(challenge);
+    }
+    let StatQuery { path, version } = req.query()?;
+    let project = req
+        .state()
+        .language_directory
+        .read()
+        .await
+        .language(&language_name)?
+        .project(req.param("project_name")?)?
+        .for_version(version.as_deref())
+        .ok_or(Error::from_str(StatusCode::NotFound, "Version not found"))?
+        .clone();
+    let stat = project
+        .stat(&req, path.trim_start_matches('/'))
+        .await?
+        .ok_or(Error::from_str(StatusCode::NotFound, "File not found"))?;
+    Ok(json!({
+        "path": path,
+        "exists": true,
+        "size": stat.len,
+        "modified": stat.modified.and_then(datetime::utc),
+        "content-type": stat.mime.to_string(),
+    })
+    .into())
+}
+
+/// The build that is running, answered regardless of the Host header.
+async fn build_info(_req: Request<ServerState>) -> Result {
+    let built_at = env!("DAPSD_BUILT_AT")
+        .parse()
+        .ok()
+        .and_then(|seconds| datetime::utc(UNIX_EPOCH + Duration::from_secs(seconds)));
+    Ok(json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "git-commit": option_env!("DAPSD_GIT_COMMIT"),
+        "built-at": built_at,
+    })
+    .into())
+}
+
+/// Liveness and readiness probe, answered regardless of the Host header.
+/// It is 503 while a reload swaps in the new registry.
+async fn healthz(req: Request<ServerState>) -> Result {
+    if req.state().maintenance.is_active() {
+        let mut response = service_unavailable();
+        response.set_body(json!({ "status": "maintenance" }));
+        return Ok(response);
+    }
+    let language_directory = req.state().language_directory.read().await;
+    let projects = language_directory.projects().count();
+    Ok(json!({ "status": "ok", "projects": projects }).into())
+}
+
+async fn metrics(req: Request<ServerState>) -> Result {
+    Ok(Response::builder(StatusCode::Ok)
+        .body(req.state().metrics.render(req.state().page_slots.in_use()))
+        .content_type("text/plain; version=0.0.4")
+        .build())
+}
+
+/// Redirects to the language's configured default project when it is
+/// registered, and otherwise lists the language's projects.
+async fn language_root(req: Request<ServerState>) -> Result {
+    let language_name = match LanguageName::from_request(&req) {
+        Ok(language_name) => language_name,
+        Err(_) if !req.state().options.no_landing_page && names_no_language(&req) => {
+            return Ok(landing_page(&req).await)
+        }
+        Err(e) => return Err(e),
+    };
+    if let Some(challenge) = req.state().auth.challenge(&req, language_name.as_str()) {
+        return Ok(challenge);
+    }
+    let language_directory = req.state().language_directory.read().await;
+    let language = language_directory.language(&language_name)?;
+    let default_project = req
+        .state()
+        .default_projects
+        .get(language_name.as_str())
+        .filter(|project_name| language.projects.contains_key(project_name.as_str()));
+    if let Some(project_name) = default_project {
+        let location = format!(
+            "{}/{}/",
+            mount_prefix(&req, &language_name),
+            utf8_percent_encode(project_name, autoindex::PATH_SEGMENT)
+        );
+        return Ok(redirect(&req, StatusCode::Found, location));
+    }
+
+    let mut project_names: Vec<&str> = language.projects.keys().map(String::as_str).collect();
+    project_names.sort_unstable();
+    Ok(Response::builder(StatusCode::Ok)
+        .body(autoindex::render_projects(
+            language_name.as_str(),
+            &mount_prefix(&req, &language_name),
+            &project_names,
+        ))
+        .content_type(mime::HTML)
+        .build())
+}
+
+/// Whether the request's host is missing or lacks the host suffix, as a
+/// bare IP address does, rather than naming a language badly.
+fn names_no_language(req: &Request<ServerState>) -> bool {
+    !requested_host(req).is_some_and(|host| {
+        strip_port(&host.to_ascii_lowercase()).ends_with(&req.state().options.host_suffix)
+    })
+}
+
+/// Where the placeholder for the list of languages goes in a
+/// `--landing-template`.
+const LANDING_LANGUAGES: &str = "{{languages}}";
+
+/// Explains how languages are named, for the first request anyone makes,
+/// which is usually to the address dapsd is listening on, and links to
+/// each language not behind auth and its projects. A `--landing-template`
+/// replaces everything but the list.
+async fn landing_page(req: &Request<ServerState>) -> Response {
+    let state = req.state();
+    let options = &state.options;
+    let host = requested_host(req).unwrap_or("");
+    let port = &host[strip_port(host).len()..];
+    let language_directory = state.language_directory.read().await;
+    let mut languages: Vec<&Language> = language_directory
+        .languages
+        .values()
+        .filter(|language| !state.auth.is_protected(&language.name))
+        .collect();
+    languages.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+    let links: Vec<autoindex::LanguageLinks> = languages
+        .into_iter()
+        .map(|language| {
+            let language_name = LanguageName(language.name.clone());
+            let origin = match &options.canonical_base {
+                Some(_) => public_origin(req, &language_name).unwrap_or_default(),
+                None if options.language_in_path => String::new(),
+                None => format!("//{}{}{}", language.name, options.host_suffix, port),
+            };
+            let href = format!("{}{}/", origin, mount_prefix(req, &language_name));
+            let mut projects: Vec<&str> = language.projects.keys().map(String::as_str).collect();
+            projects.sort_unstable();
+            let projects = projects
+                .into_iter()
+                .map(|project_name| {
+                    let encoded = utf8_percent_encode(project_name, autoindex::PATH_SEGMENT);
+                    (project_name, format!("{}{}/", href, encoded))
+                })
+                .collect();
+            autoindex::LanguageLinks {
+                name: &language.name,
+                href,
+                projects,
+            }
+        })
+        .collect();
+    let html = match &state.landing_template {
+        Some(template) => {
+            template.replace(LANDING_LANGUAGES, &autoindex::render_language_list(&links))
+        }
+        None => autoindex::render_languages(&landing_explanation(options), &links),
+    };
+    Response::builder(StatusCode::Ok)
+        .body(html)
+        .content_type(mime::HTML)
+        .build()
+}
+
+async fn load_landing_template(path: &Path) -> io::Result<Arc<str>> {
+    let template = fs::read_to_string(path)
+        .await
+        .map_err(|e| io::Error::new(e.kind(), format!("failed to read {:?}: {}", path, e)))?;
+    if !template.contains(LANDING_LANGUAGES) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("landing template {:?} has no {}", path, LANDING_LANGUAGES),
+        ));
+    }
+    Ok(template.into())
+}
+
+fn landing_explanation(options: &Options) -> String {
+    match &options.canonical_base {
+        _ if options.language_in_path => format!(
+            "Documentation is served by language, at paths like {}/rust/ for Rust.",
+            options.base_path
+        ),
+        Some(base) => format!(
+            "Documentation is served by language, on hosts named like rust.{} for Rust.",
+            base.host_str().unwrap_or_default()
+        ),
+        None => format!(
+            "Documentation is served by language, on hosts named like rust{0} for Rust: \
+             the language followed by {0}. Resolve such names to this server, as in \
+             /etc/hosts, to browse them.",
+            options.host_suffix
+        ),
+    }
+}
+
+/// The `favicon.ico` of the `root-files` directory, for every host.
+async fn favicon(req: Request<ServerState>) -> Result {
+    Ok(root_file(&req, "favicon.ico")
+        .await?
+        .unwrap_or_else(|| Response::new(StatusCode::NotFound)))
+}
+
+/// `file` from the `root-files` directory, if it is configured and has one.
+async fn root_file(req: &Request<ServerState>, file: &str) -> Result<Option<Response>> {
+    let root_files = match &req.state().root_files {
+        Some(root_files) => root_files,
+        None => return Ok(None),
+    };
+    let response = root_files.serve_path(req, file).await?;
+    Ok(Some(response).filter(|response| response.status() != StatusCode::NotFound))
+}
+
+/// The `robots.txt` of the `root-files` directory if there is one, and
+/// otherwise crawler rules for the language named by the host. Hosts that
+/// don't name a language get the default of allowing everything.
+async fn robots_txt_for_host(req: Request<ServerState>) -> Result {
+    if let Some(response) = root_file(&req, "robots.txt").await? {
+        return Ok(response);
+    }
+    let state = req.state();
+    let mut paths = Vec::new();
+    if let Ok(language_name) = LanguageName::from_request(&req) {
+        let language = language_name.as_str();
+        if state.auth.is_protected(language) || state.robots.disallows_language(language) {
+            paths.push("/".to_string());
+        } else {
+            let language_directory = state.language_directory.read().await;
+            let aliases = language_directory
+                .language(&language_name)
+                .ok()
+                .map(|language| &language.aliases);
+            for project_name in state.robots.disallowed_projects(language) {
+                paths.push(robots::project_path(project_name));
+                // Aliases serve the same pages.
+                let project_aliases = aliases
+                    .into_iter()
+                    .flatten()
+                    .filter(|(_, target)| *target == project_name);
+                for (alias, _) in project_aliases {
+                    paths.push(robots::project_path(alias));
+                }
+            }
+        }
+    }
+    paths.sort_unstable();
+    paths.dedup();
+    Ok(Response::builder(StatusCode::Ok)
+        .body(robots::render(&state.options.base_path, &paths))
+        .content_type("text/plain; charset=utf-8")
+        .build())
+}
+
+/// The methods routes that only read support.
+const READ_METHODS: &str = "GET, HEAD, OPTIONS";
+
+/// For the methods a route has no handler for: OPTIONS is answered with
+/// the `methods` it supports, and anything else with a 405 naming them.
+/// Tide's own 405 doesn't say which methods are allowed.
+fn allow(methods: &'static str) -> impl Endpoint<ServerState> + Clone {
+    move |req: Request<ServerState>| async move { Result::Ok(other_method(&req, methods)) }
+}
+
+fn other_method(req: &Request<ServerState>, methods: &str) -> Response {
+    let status = if req.method() == Method::Options {
+        StatusCode::NoContent
+    } else {
+        StatusCode::MethodNotAllowed
+    };
+    Response::builder(status).header(ALLOW, methods).build()
+}
+
+/// Routes GET and HEAD to `endpoint`. HEAD is routed explicitly because a
+/// catch-all handler would otherwise take it instead of the GET handler.
+fn read_only(route: &mut Route<'_, ServerState>, endpoint: impl Endpoint<ServerState> + Clone) {
+    route
+        .get(endpoint.clone())
+        .head(endpoint)
+        .all(allow(READ_METHODS));
+}
+
+async fn serve_page(req: Request<ServerState>) -> Result {
+    if !matches!(req.method(), Method::Get | Method::Head) {
+        return Ok(other_method(&req, READ_METHODS));
+    }
+    let start = Instant::now();
+    let slot = req.state().page_slots.try_acquire();
+    let mut served = match slot {
+        Some(_) => match check_path_limits(&req) {
+            Ok(()) => find_page(&req).await,
+            Err(e) => Err(e),
+        },
+        None => Ok(service_unavailable()),
+    };
+    let head = req.method() == Method::Head;
+    if head {
+        served = served.map(without_body);
+    }
+    let (status, len) = match &served {
+        Ok(response) if !head => (response.status(), response.len()),
+        Ok(response) => (response.status(), None),
+        Err(e) => (e.status(), None),
+    };
+    req.state().metrics.record(status, len, start.elapsed());
+    drop(slot);
+    served
+}
+
+/// Turns away paths too long or deep to be real pages before anything
+/// looks at the filesystem.
+fn check_path_limits(req: &Request<ServerState>) -> Result<()> {
+    let options = &req.state().options;
+    let path = req.url().path();
+    if path.len() > options.max_path_length {
+        return Err(Error::from_str(
+            StatusCode::UriTooLong,
+            format!("path is longer than {} bytes", options.max_path_length),
+        ));
+    }
+    // Encoded slashes still separate segments once decoded.
+    let depth =
+        path.matches('/').count() + path.matches("%2f").count() + path.matches("%2F").count();
+    if depth > options.max_path_depth {
+        return Err(Error::from_str(
+            StatusCode::BadRequest,
+            format!("path has more than {} segments", options.max_path_depth),
+        ));
+    }
+    Ok(())
+}
+
+/// The first path segment of a language's shared assets, which no project
+/// can take.
+const ASSETS: &str = "_assets";
+
+/// The name the `root-files` directory is served as.
+const ROOT_FILES: &str = "_root";
+
+/// Seconds a 503 tells clients to wait before asking again.
+const RETRY_AFTER_SECONDS: u64 = 1;
+
+/// For requests turned away while busy, which are worth retrying soon.
+fn service_unavailable() -> Response {
+    Response::builder(StatusCode::ServiceUnavailable)
+        .header(RETRY_AFTER, RETRY_AFTER_SECONDS.to_string())
+        .build()
+}
+
+/// Answers HEAD with the headers GET would send, Content-Length included,
+/// without reading any of the body.
+fn without_body(mut response: Response) -> Response {
+    if response.is_empty() != Some(false) {
+        return response;
+    }
+    let mut empty = Body::from_reader(async_std::io::empty(), response.len());
+    response.swap_body(&mut empty);
+    response
+}
+
+async fn find_page(req: &Request<ServerState>) -> Result {
+    if req.state().maintenance.is_active() {
+        return Ok(service_unavailable());
+    }
+    let language_name = LanguageName::from_request(req)?;
+    let auth = &req.state().auth;
+    // Whether credentials decided the response, which caches then need to
+    // keep apart.
+    let mut gated = auth.is_protected(language_name.as_str());
+    if let Some(mut challenge) = auth.challenge(req, language_name.as_str()) {
+        add_vary(&mut challenge, AUTHORIZATION)?;
+        return Ok(challenge);
+    }
+    let project_name = req.param("project_name")?;
+    let path = req.param("path").unwrap_or("");
+    if project_name == ASSETS {
+        if let Some(assets) = req.state().language_assets.get(language_name.as_str()) {
+            let mut response = assets.serve_path(req, path).await?;
+            if gated {
+                add_vary(&mut response, AUTHORIZATION)?;
+            }
+            return Ok(response);
+        }
+    }
+    let language_directory = req.state().language_directory.read().await;
+    let mut response =
+        serve_in_language(req, &language_directory, &language_name, project_name, path).await;
+    if response.status() != StatusCode::NotFound {
+        if gated {
+            add_vary(&mut response, AUTHORIZATION)?;
+        }
+        return Ok(response);
+    }
+
+    // Whatever is found from here on, or not, depends on Accept-Language.
+    for fallback_language in fallback_languages(req, &language_name) {
+        if fallback_language == language_name {
+            continue;
+        }
+        if auth.is_protected(fallback_language.as_str()) {
+            gated = true;
+            if auth.challenge(req, fallback_language.as_str()).is_some() {
+                continue;
+            }
+        }
+        let fallback = serve_in_language(
+            req,
+            &language_directory,
+            &fallback_language,
+            project_name,
+            path,
+        )
+        .await;
+        if fallback.status() != StatusCode::NotFound {
+            response = fallback;
+            break;
+        }
+    }
+    add_vary(&mut response, ACCEPT_LANGUAGE)?;
+    if gated {
+        add_vary(&mut response, AUTHORIZATION)?;
+    }
+    Ok(response)
+}
+
+/// Errors become responses here so that a 404 from any step of the lookup
+/// can be told apart from a page that was found.
+async fn serve_in_language(
+    req: &Request<ServerState>,
+    language_directory: &LanguageDirectory,
+    language_name: &LanguageName,
+    project_name: &str,
+    path: &str,
+) -> Response {
+    let requested_path = path;
+    let served = async {
+        let versions = language_directory
+            .language(language_name)?
+            .project(project_name)?;
+        let (project, path) = versions
+            .resolve(path)
+            .ok_or(Error::from_str(StatusCode::NotFound, "Version not found"))?;
+        if project.project_name != project_name && req.state().options.redirect_aliases {
+            return Ok(alias_redirect(req, &project.project_name));
+        }
+        if let Some(response) = redirect_rule(req, project, path) {
+            return Ok(response);
+        }
+        let mut response = project.serve_path(req, path).await?;
+        if response.status() == StatusCode::Ok {
+            let link = canonical_link(req, versions, project, requested_path, path, &response);
+            if let Some(link) = link.await {
+                response.insert_header("link", link);
+            }
+            for (name, value) in &project.headers {
+                response.insert_header(name.as_str(), value.as_str());
+            }
+        }
+        if response.status().is_success() {
+            // HEAD responses lose their body before they are sent.
+            let len = if req.method() == Method::Head {
+                None
+            } else {
+                response.len()
+            };
+            req.state()
+                .metrics
+                .record_project(&project.language, &project.project_name, len);
+        }
+        Result::Ok(response)
+    };
+    served.await.unwrap_or_else(Response::from)
+}
+
+/// A `Link` to the canonical URL of an HTML page, which names the project
+/// rather than any alias it was requested by. That is the same page in
+/// `latest` for an older version with `canonical-latest`, if it is there,
+/// and otherwise the page itself, under `--canonical-base` only.
+/// `requested_path` is the path within the project as requested, and
+/// `path` what is left of it after the version.
+async fn canonical_link(
+    req: &Request<ServerState>,
+    versions: &ProjectVersions,
+    project: &Project,
+    requested_path: &str,
+    path: &str,
+    response: &Response,
+) -> Option<String> {
+    let is_html = response
+        .content_type()
+        .is_some_and(|mime| mime.essence() == mime::HTML.essence());
+    if !is_html {
+        return None;
+    }
+    let language_name = LanguageName(project.language.clone());
+    let latest = versions.latest().filter(|latest| {
+        project.canonical_latest && project.version.is_some() && latest.version != project.version
+    });
+    let version = match latest {
+        Some(latest) if matches!(latest.stat(req, path).await, Ok(Some(_))) => "latest/",
+        _ => {
+            req.state().options.canonical_base.as_ref()?;
+            &requested_path[..requested_path.len() - path.len()]
+        }
+    };
+    Some(format!(
+        "<{}{}/{}/{}{}>; rel=\"canonical\"",
+        public_origin(req, &language_name)?,
+        mount_prefix(req, &language_name),
+        utf8_percent_encode(&project.project_name, autoindex::PATH_SEGMENT),
+        version,
+        path
+    ))
+}
+
+/// A redirect for `path` by the first of the project's rules that applies.
+fn redirect_rule(req: &Request<ServerState>, project: &Project, path: &str) -> Option<Response> {
+    if project.redirects.is_empty() {
+        return None;
+    }
+    let url_path = req.url().path();
+    let base = url_path.strip_suffix(path).unwrap_or(url_path);
+    let decoded = percent_decode_str(path).decode_utf8_lossy();
+    let (status, location) = redirects::find(&project.redirects, base, &format!("/{}", decoded))?;
+    Some(redirect(req, status, location))
+}
+
+/// Swaps the alias in the request's path for the project name, keeping
+/// any language segment, the rest of the path and the query as they were
+/// sent.
+fn alias_redirect(req: &Request<ServerState>, project_name: &str) -> Response {
+    let base_path = &req.state().options.base_path;
+    let path = &req.url().path()[base_path.len()..];
+    let mut segments = path.trim_start_matches('/').splitn(3, '/');
+    let prefix = if req.state().options.language_in_path {
+        format!("{}/{}", base_path, segments.next().unwrap_or(""))
+    } else {
+        base_path.clone()
+    };
+    segments.next();
+    let rest = segments.collect::<Vec<_>>().join("/");
+    let path = format!(
+        "{}/{}/{}",
+        prefix,
+        utf8_percent_encode(project_name, autoindex::PATH_SEGMENT),
+        rest
+    );
+    permanent_redirect(req, &path)
+}
+
+/// A 301 to `path` on the same host, carrying over the request's query.
+fn permanent_redirect(req: &Request<ServerState>, path: impl Into<String>) -> Response {
+    redirect(req, StatusCode::MovedPermanently, path.into())
+}
+
+/// Appends the request's query to any `location` already has. Paths on
+/// the same host keep a single leading slash, since a client would take
+/// `//example.com/` to another host, and are made absolute under
+/// `--canonical-base`, or with the scheme a trusted proxy forwarded, so
+/// that a proxy terminating HTTPS doesn't send clients back to `http://`.
+fn redirect(req: &Request<ServerState>, status: StatusCode, mut location: String) -> Response {
+    if location.is_empty() || location.starts_with("//") {
+        location = format!("/{}", location.trim_start_matches('/'));
+    }
+    let options = &req.state().options;
+    let absolute = options.canonical_base.is_some()
+        || proxy::forwarded_proto(req, &options.trusted_proxies).is_some();
+    if location.starts_with('/') && absolute {
+        let origin = LanguageName::from_request(req)
+            .ok()
+            .and_then(|language_name| public_origin(req, &language_name));
+        if let Some(origin) = origin {
+            location.insert_str(0, &origin);
+        }
+    }
+    if let Some(query) = req.url().query() {
+        location.push(if location.contains('?') { '&' } else { '?' });
+        location.push_str(query);
+    }
+    Response::builder(status).header(LOCATION, location).build()
+}
+
+/// The languages to try when a page is missing in `language_name`: those
+/// named in `Accept-Language` by descending preference, then its
+/// `language-fallbacks`, then `--default-language`.
+fn fallback_languages(
+    req: &Request<ServerState>,
+    language_name: &LanguageName,
+) -> Vec<LanguageName> {
+    let mut proposals: Vec<(&str, f32)> = req
+        .header(ACCEPT_LANGUAGE)
+        .into_iter()
+        .flat_map(|values| values.iter())
+        .flat_map(|value| value.as_str().split(','))
+        .filter_map(parse_language_proposal)
+        .collect();
+    // A stable sort keeps the header's order among equal weights.
+    proposals.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
+
+    let mut languages: Vec<LanguageName> = Vec::new();
+    let state = req.state();
+    // With `--language-codes`, tags name languages as `from_request` would,
+    // so `en_US` is `en-us` and a tag that is no language code is no one's.
+    let language_codes = state.options.language_codes;
+    let tags = proposals
+        .into_iter()
+        .filter_map(|(tag, _)| {
+            if language_codes {
+                language_code::normalize(tag)
+            } else {
+                Some(tag.to_ascii_lowercase())
+            }
+        })
+        .flat_map(|tag| {
+            // `en-US` falls back to plain `en` as well.
+            let primary = tag
+                .split('-')
+                .next()
+                .filter(|primary| *primary != tag)
+                .map(str::to_string);
+            std::iter::once(tag).chain(primary)
+        });
+    let chain = state.language_fallbacks.chain(language_name.as_str());
+    let default_language = state.options.default_language.as_deref();
+    let configured = chain.into_iter().chain(default_language);
+    for tag in tags.chain(configured.map(str::to_ascii_lowercase)) {
+        let language = LanguageName(tag);
+        if !languages.contains(&language) {
+            languages.push(language);
+        }
+    }
+    languages
+}
+
+/// Parses one `tag;q=weight` entry, skipping wildcards and tags the client
+/// has explicitly refused with `q=0`.
+fn parse_language_proposal(proposal: &str) -> Option<(&str, f32)> {
+    let mut parts = proposal.split(';').map(str::trim);
+    let tag = parts.next().filter(|tag| !tag.is_empty() && *tag != "*")?;
+    let mut weight = 1.0;
+    for parameter in parts {
+        if let Some(q) = parameter.strip_prefix("q=") {
+            weight = q.parse().ok()?;
+        }
+    }
+    Some((tag, weight)).filter(|(_, weight)| *weight > 0.0)
+}
@@ -0,0 +1,119 @@
+//! Live reload for local doc authoring, enabled with `--dev`. Served HTML
+//! gets a small script that listens for server-sent events on
+//! `/__live_reload` and reloads the page whenever a file in its project
+//! changes.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use async_std::{
+    channel::{self, Receiver, Sender},
+    path::PathBuf,
+    task,
+};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use tide::{log, prelude::*, sse, Request, Result};
+
+use crate::{
+    walk::{self, Fingerprint},
+    LanguageName, ServerState, SharedLanguageDirectory,
+};
+
+pub const PATH: &str = "/__live_reload";
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+type ProjectKey = (String, String);
+
+#[derive(Debug, Default)]
+pub struct LiveReload {
+    clients: Mutex<Vec<(ProjectKey, Sender<()>)>>,
+}
+
+impl LiveReload {
+    fn subscribe(&self, key: ProjectKey) -> Receiver<()> {
+        let (sender, receiver) = channel::bounded(1);
+        self.clients.lock().unwrap().push((key, sender));
+        receiver
+    }
+
+    fn notify(&self, key: &ProjectKey) {
+        let mut clients = self.clients.lock().unwrap();
+        clients.retain(|(_, sender)| !sender.is_closed());
+        for (_, sender) in clients.iter().filter(|(client_key, _)| client_key == key) {
+            // A full channel already has a reload pending.
+            let _ = sender.try_send(());
+        }
+    }
+
+    /// Polls every registered project directory, notifying the clients of
+    /// those that changed since the last pass.
+    pub async fn watch(self: Arc<Self>, language_directory: SharedLanguageDirectory) {
+        let mut fingerprints: HashMap<ProjectKey, Fingerprint> = HashMap::new();
+        loop {
+            task::sleep(POLL_INTERVAL).await;
+            let projects: Vec<(ProjectKey, PathBuf)> = language_directory
+                .read()
+                .await
+                .projects()
+                .map(|project| {
+                    let key = (project.language.clone(), project.project_name.clone());
+                    (key, project.directories.primary().clone().into())
+                })
+                .collect();
+            let mut seen = HashMap::new();
+            for (key, directory) in projects {
+                let fingerprint = walk::fingerprint(directory).await;
+                let changed = fingerprints
+                    .get(&key)
+                    .is_some_and(|previous| *previous != fingerprint);
+                if changed {
+                    log::debug!("Reloading clients of {} in language {}", key.1, key.0);
+                    self.notify(&key);
+                }
+                seen.insert(key, fingerprint);
+            }
+            fingerprints = seen;
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct LiveReloadQuery {
+    project: String,
+}
+
+/// Sends a `reload` event each time the project given by `?project=`
+/// changes, for the language named by the Host header.
+pub fn endpoint() -> impl tide::Endpoint<ServerState> {
+    sse::endpoint(|req: Request<ServerState>, sender| async move {
+        let live_reload = match &req.state().live_reload {
+            Some(live_reload) => live_reload.clone(),
+            None => return Ok(()),
+        };
+        let language_name = LanguageName::from_request(&req)?;
+        let LiveReloadQuery { project } = req.query()?;
+        let reloads = live_reload.subscribe((language_name.as_str().clone(), project));
+        while reloads.recv().await.is_ok() {
+            sender.send("reload", "", None).await?;
+        }
+        Result::Ok(())
+    })
+}
+
+/// Adds the reload script before `</body>`, or at the end when there isn't
+/// one. The events are at `PATH` under `base_path`.
+pub fn inject_client(html: &mut String, base_path: &str, project_name: &str) {
+    let script = format!(
+        "<script>new EventSource(\"{}{}?project={}\").addEventListener(\"reload\", () => location.reload());</script>\n",
+        base_path,
+        PATH,
+        utf8_percent_encode(project_name, NON_ALPHANUMERIC)
+    );
+    match html.rfind("</body>") {
+        Some(index) => html.insert_str(index, &script),
+        None => html.push_str(&script),
+    }
+}
@@ -0,0 +1,30 @@
+//! The brief window in which `/api/reload` swaps in the rebuilt registry.
+//! Pages asked for meanwhile are answered with 503 rather than waiting on
+//! the swap.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Counts the reloads in their window, so that overlapping reloads don't
+/// end each other's.
+#[derive(Debug, Default)]
+pub struct Maintenance(AtomicUsize);
+
+impl Maintenance {
+    pub fn is_active(&self) -> bool {
+        self.0.load(Ordering::SeqCst) > 0
+    }
+
+    /// Lasts until the guard is dropped.
+    pub fn begin(&self) -> MaintenanceGuard<'_> {
+        self.0.fetch_add(1, Ordering::SeqCst);
+        MaintenanceGuard(&self.0)
+    }
+}
+
+pub struct MaintenanceGuard<'a>(&'a AtomicUsize);
+
+impl Drop for MaintenanceGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,180 @@
+//! Counters for served pages, rendered in the Prometheus text format at
+//! `/metrics`.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use tide::StatusCode;
+
+/// Upper bounds, in seconds, of the request duration histogram buckets.
+const DURATION_BUCKETS: [f64; 10] = [0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0];
+
+#[derive(Debug, Default)]
+struct ProjectCounters {
+    requests: u64,
+    bytes: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct Metrics {
+    statuses: Mutex<BTreeMap<u16, u64>>,
+    projects: Mutex<BTreeMap<(String, String), ProjectCounters>>,
+    bytes: AtomicU64,
+    duration_buckets: [AtomicU64; DURATION_BUCKETS.len()],
+    duration_micros: AtomicU64,
+    duration_count: AtomicU64,
+    file_cache_hits: AtomicU64,
+    file_cache_misses: AtomicU64,
+}
+
+impl Metrics {
+    /// `len` is the response body length, when known.
+    pub fn record(&self, status: StatusCode, len: Option<usize>, duration: Duration) {
+        *self
+            .statuses
+            .lock()
+            .unwrap()
+            .entry(status as u16)
+            .or_default() += 1;
+        self.bytes
+            .fetch_add(len.unwrap_or(0) as u64, Ordering::Relaxed);
+
+        let seconds = duration.as_secs_f64();
+        for (bucket, bound) in self.duration_buckets.iter().zip(DURATION_BUCKETS.iter()) {
+            if seconds <= *bound {
+                bucket.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.duration_micros
+            .fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+        self.duration_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts a page a registered project served successfully. Only the
+    /// names of registered projects are passed, rather than whatever the
+    /// client asked for, which keeps the series bounded.
+    pub fn record_project(&self, language: &str, project: &str, len: Option<usize>) {
+        let mut projects = self.projects.lock().unwrap();
+        let counters = projects
+            .entry((language.to_string(), project.to_string()))
+            .or_default();
+        counters.requests += 1;
+        counters.bytes += len.unwrap_or(0) as u64;
+    }
+
+    pub fn record_file_cache(&self, hit: bool) {
+        let counter = if hit {
+            &self.file_cache_hits
+        } else {
+            &self.file_cache_misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// `pages_in_flight` is how many page requests are being handled now.
+    pub fn render(&self, pages_in_flight: usize) -> String {
+        let mut out = String::new();
+
+        out.push_str("# HELP dapsd_pages_in_flight Page requests being handled.\n");
+        out.push_str("# TYPE dapsd_pages_in_flight gauge\n");
+        let _ = writeln!(out, "dapsd_pages_in_flight {}", pages_in_flight);
+
+        out.push_str("# HELP dapsd_requests_total Pages requested, by response status.\n");
+        out.push_str("# TYPE dapsd_requests_total counter\n");
+        for (status, count) in self.statuses.lock().unwrap().iter() {
+            let _ = writeln!(
+                out,
+                "dapsd_requests_total{{status=\"{}\"}} {}",
+                status, count
+            );
+        }
+
+        let projects = self.projects.lock().unwrap();
+        out.push_str(
+            "# HELP dapsd_project_requests_total Pages served successfully, by language and project.\n",
+        );
+        out.push_str("# TYPE dapsd_project_requests_total counter\n");
+        for ((language, project), counters) in projects.iter() {
+            let _ = writeln!(
+                out,
+                "dapsd_project_requests_total{{language=\"{}\",project=\"{}\"}} {}",
+                escape_label(language),
+                escape_label(project),
+                counters.requests
+            );
+        }
+        out.push_str("# HELP dapsd_project_response_bytes_total Body bytes served, by language and project.\n");
+        out.push_str("# TYPE dapsd_project_response_bytes_total counter\n");
+        for ((language, project), counters) in projects.iter() {
+            let _ = writeln!(
+                out,
+                "dapsd_project_response_bytes_total{{language=\"{}\",project=\"{}\"}} {}",
+                escape_label(language),
+                escape_label(project),
+                counters.bytes
+            );
+        }
+        drop(projects);
+
+        out.push_str("# HELP dapsd_response_bytes_total Body bytes served.\n");
+        out.push_str("# TYPE dapsd_response_bytes_total counter\n");
+        let _ = writeln!(
+            out,
+            "dapsd_response_bytes_total {}",
+            self.bytes.load(Ordering::Relaxed)
+        );
+
+        out.push_str("# HELP dapsd_request_duration_seconds Time taken to serve pages.\n");
+        out.push_str("# TYPE dapsd_request_duration_seconds histogram\n");
+        for (bucket, bound) in self.duration_buckets.iter().zip(DURATION_BUCKETS.iter()) {
+            let _ = writeln!(
+                out,
+                "dapsd_request_duration_seconds_bucket{{le=\"{}\"}} {}",
+                bound,
+                bucket.load(Ordering::Relaxed)
+            );
+        }
+        let count = self.duration_count.load(Ordering::Relaxed);
+        let _ = writeln!(
+            out,
+            "dapsd_request_duration_seconds_bucket{{le=\"+Inf\"}} {}",
+            count
+        );
+        let _ = writeln!(
+            out,
+            "dapsd_request_duration_seconds_sum {}",
+            self.duration_micros.load(Ordering::Relaxed) as f64 / 1e6
+        );
+        let _ = writeln!(out, "dapsd_request_duration_seconds_count {}", count);
+
+        out.push_str("# HELP dapsd_file_cache_hits_total Files served from the file cache.\n");
+        out.push_str("# TYPE dapsd_file_cache_hits_total counter\n");
+        let _ = writeln!(
+            out,
+            "dapsd_file_cache_hits_total {}",
+            self.file_cache_hits.load(Ordering::Relaxed)
+        );
+        out.push_str("# HELP dapsd_file_cache_misses_total Cacheable files read from disk.\n");
+        out.push_str("# TYPE dapsd_file_cache_misses_total counter\n");
+        let _ = writeln!(
+            out,
+            "dapsd_file_cache_misses_total {}",
+            self.file_cache_misses.load(Ordering::Relaxed)
+        );
+        out
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -0,0 +1,76 @@
+//! Content types by file extension, consulted before the type guessed by
+//! `Body::from_file`, which is missing or wrong for some web formats.
+
+use std::{collections::HashMap, io, path::Path, str::FromStr};
+
+use tide::http::{mime, Mime};
+
+const BUILTIN: &[(&str, &str)] = &[
+    ("map", "application/json"),
+    ("mjs", "text/javascript"),
+    ("wasm", "application/wasm"),
+    ("woff2", "font/woff2"),
+];
+
+#[derive(Debug)]
+pub struct MimeTypes {
+    by_extension: HashMap<String, Mime>,
+}
+
+impl Default for MimeTypes {
+    fn default() -> Self {
+        let mut mime_types = MimeTypes {
+            by_extension: HashMap::new(),
+        };
+        for (extension, mime) in BUILTIN {
+            mime_types.insert(extension, mime).unwrap();
+        }
+        mime_types
+    }
+}
+
+impl MimeTypes {
+    /// Adds or replaces mappings, as given by the `mime-types` table of the
+    /// config file. Extensions may be written with or without a leading dot.
+    pub fn extend(&mut self, mime_types: &HashMap<String, String>) -> io::Result<()> {
+        for (extension, mime) in mime_types {
+            self.insert(extension, mime).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid MIME type {:?} for {:?}: {}", mime, extension, e),
+                )
+            })?;
+        }
+        Ok(())
+    }
+
+    fn insert(&mut self, extension: &str, mime: &str) -> tide::http::Result<()> {
+        let extension = extension.trim_start_matches('.').to_ascii_lowercase();
+        self.by_extension.insert(extension, Mime::from_str(mime)?);
+        Ok(())
+    }
+
+    pub fn for_path(&self, path: &Path) -> Option<Mime> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        self.by_extension.get(&extension).cloned()
+    }
+
+    /// The type of a file by its extension alone, for when there is no
+    /// contents at hand to sniff.
+    pub fn guess(&self, path: &Path) -> Mime {
+        let mime = self
+            .for_path(path)
+            .or_else(|| path.extension()?.to_str().and_then(Mime::from_extension))
+            .unwrap_or(mime::BYTE_STREAM);
+        with_html_charset(mime)
+    }
+}
+
+/// HTML is always served as UTF-8 unless a charset was given explicitly.
+pub fn with_html_charset(mime: Mime) -> Mime {
+    if mime.essence() == mime::HTML.essence() && mime.param("charset").is_none() {
+        mime::HTML
+    } else {
+        mime
+    }
+}
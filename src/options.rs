@@ -0,0 +1,621 @@
+//! Command line and environment configuration. Flags take precedence over
+//! their `DAPSD_*` environment variable counterparts.
+
+use std::{
+    env, fmt,
+    net::{IpAddr, Ipv4Addr, SocketAddr, ToSocketAddrs},
+    path::PathBuf,
+    time::Duration,
+};
+
+use tide::http::Url;
+
+const DEFAULT_LISTEN_IP: IpAddr = IpAddr::V4(Ipv4Addr::new(127, 0, 10, 1));
+const DEFAULT_LISTEN_PORT: u16 = 8080;
+const DEFAULT_HOST_SUFFIX: &str = ".docs";
+const DEFAULT_WATCH_INTERVAL: Duration = Duration::from_secs(5);
+const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_FILE_CACHE_MAX_FILE: usize = 64 * 1024;
+const DEFAULT_MAX_REGISTER_BODY: usize = 64 * 1024;
+const DEFAULT_MAX_PATH_LENGTH: usize = 4096;
+const DEFAULT_MAX_PATH_DEPTH: usize = 64;
+
+pub const USAGE: &str = "\
+Usage: dapsd [OPTIONS]
+
+Options:
+    --listen <addr>    Address to listen on, as host:port or a bare port
+                       [env: DAPSD_LISTEN] [default: 127.0.10.1:8080]
+    --unix-socket <path>
+                       Unix domain socket to listen on, instead of TCP
+                       unless --listen is given too. A stale socket left
+                       at the path is replaced [env: DAPSD_UNIX_SOCKET]
+    --config <path>    JSON file of projects to register at startup
+                       [env: DAPSD_CONFIG]
+    --state-file <path>
+                       File to save registrations to, restored at startup
+                       [env: DAPSD_STATE_FILE]
+    --host-suffix <suffix>
+                       Domain stripped from the Host header to find the
+                       language, e.g. `rust.docs` serves `rust`
+                       [env: DAPSD_HOST_SUFFIX] [default: .docs]
+    --canonical-base <url>
+                       Public scheme and domain to build absolute URLs in
+                       sitemaps, redirects and canonical links with, such
+                       as `https://docs.example.com`, under which each
+                       language is a subdomain unless --language-in-path
+                       is given; taken from each request when unset
+                       [env: DAPSD_CANONICAL_BASE]
+    --base-path <path> Path everything is served under, such as `/docs`
+                       when dapsd is mounted at a subpath of a larger
+                       site; requests outside it are 404s
+                       [env: DAPSD_BASE_PATH]
+    --default-language <language>
+                       Language to serve a page from when it is missing in
+                       the requested one, in every `Accept-Language` and in
+                       the requested one's `language-fallbacks`
+                       [env: DAPSD_DEFAULT_LANGUAGE]
+    --watch-interval <seconds>
+                       How often to check that registered directories still
+                       exist, evicting those that don't, and whether their
+                       sitemaps are stale; 0 disables this
+                       [env: DAPSD_WATCH_INTERVAL] [default: 5]
+    --request-timeout <seconds>
+                       Answer 504 to page and registration requests not
+                       handled in time; 0 disables this
+                       [env: DAPSD_REQUEST_TIMEOUT] [default: 0]
+    --shutdown-timeout <seconds>
+                       How long to let in-flight requests finish after
+                       SIGINT or SIGTERM before closing their connections
+                       and exiting [env: DAPSD_SHUTDOWN_TIMEOUT]
+                       [default: 30]
+    --max-connections <count>
+                       Most page requests to handle at once, answering 503
+                       to those past it; unlimited when unset or 0
+                       [env: DAPSD_MAX_CONNECTIONS]
+    --register-token <token>
+                       Bearer token required by the registration, unregister
+                       and reload endpoints, which are open without one
+                       [env: DAPSD_REGISTER_TOKEN]
+    --dev              Reload pages in the browser when their project's
+                       files change, for local authoring [env: DAPSD_DEV]
+    --redirect-aliases Answer requests for a project alias with a 301 to
+                       the project's own name instead of serving it
+                       [env: DAPSD_REDIRECT_ALIASES]
+    --language-in-path Take the language from the first path segment,
+                       serving `/<language>/<project>/...` on any host,
+                       instead of from the host name
+                       [env: DAPSD_LANGUAGE_IN_PATH]
+    --clamp-parent-segments
+                       Resolve `..` in page paths by going up a directory,
+                       stopping at the project's root, instead of answering
+                       400 [env: DAPSD_CLAMP_PARENT_SEGMENTS]
+    --file-cache-size <bytes>
+                       Keep up to this many bytes of recently served files
+                       in memory; disabled when unset or 0
+                       [env: DAPSD_FILE_CACHE_SIZE]
+    --file-cache-max-file <bytes>
+                       Largest file to keep in the file cache
+                       [env: DAPSD_FILE_CACHE_MAX_FILE] [default: 65536]
+    --max-register-body <bytes>
+                       Largest request body the registration endpoints
+                       accept, answering 413 to bigger ones
+                       [env: DAPSD_MAX_REGISTER_BODY] [default: 65536]
+    --max-path-length <bytes>
+                       Longest page path, as sent, answering 414 to longer
+                       ones [env: DAPSD_MAX_PATH_LENGTH] [default: 4096]
+    --max-path-depth <segments>
+                       Most segments in a page path, answering 400 to
+                       deeper ones [env: DAPSD_MAX_PATH_DEPTH] [default: 64]
+    --index-files <name>[,<name>...]
+                       Files to serve for a directory, the first that exists
+                       winning, for projects that don't list their own
+                       [env: DAPSD_INDEX_FILES] [default: index.html]
+    --trust-proxy <addr>[,<addr>...]
+                       Reverse proxies to take the client address from
+                       X-Forwarded-For, the language's host from
+                       X-Forwarded-Host and the scheme of redirects and
+                       links from X-Forwarded-Proto for
+                       [env: DAPSD_TRUST_PROXY]
+    --log-clients      Add each request's client address and User-Agent to
+                       the access log [env: DAPSD_LOG_CLIENTS]
+    --forbidden-page <path>
+                       HTML file to send with the 403 for paths outside a
+                       project that has no `forbidden` page of its own
+                       [env: DAPSD_FORBIDDEN_PAGE]
+    --language-codes   Require languages to be BCP 47 codes like `en` or
+                       `pt-br`, in hosts, paths, registrations and the
+                       config file, taking `EN` and `pt_BR` as `en` and
+                       `pt-br` [env: DAPSD_LANGUAGE_CODES]
+    --no-landing-page  Answer 400, rather than a page explaining how hosts
+                       name languages and listing every language and
+                       project, at the root of a host that doesn't name a
+                       language, such as a bare IP address
+                       [env: DAPSD_NO_LANDING_PAGE]
+    --landing-template <path>
+                       HTML file for the landing page, in which
+                       `{{languages}}` is replaced with the list of
+                       languages and projects [env: DAPSD_LANDING_TEMPLATE]
+    --check-config     Validate the --config file, listing every problem
+                       startup would refuse it for, and exit without
+                       serving
+    -h, --help         Print this message
+
+Environment:
+    RUST_LOG           Log level, one of off, error, warn, info, debug or
+                       trace [default: info]
+";
+
+#[derive(Debug)]
+pub enum OptionsError {
+    Help,
+    Invalid(String),
+}
+
+impl fmt::Display for OptionsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OptionsError::Help => f.write_str(USAGE),
+            OptionsError::Invalid(message) => f.write_str(message),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Options {
+    /// None when only listening on a Unix socket.
+    pub listen: Option<SocketAddr>,
+    pub unix_socket: Option<PathBuf>,
+    pub config: Option<PathBuf>,
+    pub state_file: Option<PathBuf>,
+    pub host_suffix: String,
+    /// Only a scheme, host and optional port.
+    pub canonical_base: Option<Url>,
+    /// Empty, or a path with a leading slash and no trailing one.
+    pub base_path: String,
+    pub default_language: Option<String>,
+    pub watch_interval: Option<Duration>,
+    pub request_timeout: Option<Duration>,
+    pub shutdown_timeout: Duration,
+    pub max_connections: Option<usize>,
+    pub register_token: Option<String>,
+    pub dev: bool,
+    pub redirect_aliases: bool,
+    pub clamp_parent_segments: bool,
+    pub language_in_path: bool,
+    pub file_cache_size: Option<usize>,
+    pub file_cache_max_file: usize,
+    pub max_register_body: usize,
+    pub max_path_length: usize,
+    pub max_path_depth: usize,
+    pub index_files: Vec<String>,
+    pub trusted_proxies: Vec<IpAddr>,
+    pub log_clients: bool,
+    pub no_landing_page: bool,
+    pub landing_template: Option<PathBuf>,
+    pub forbidden_page: Option<PathBuf>,
+    pub language_codes: bool,
+    pub check_config: bool,
+}
+
+impl Options {
+    pub fn from_env() -> Result<Self, OptionsError> {
+        Self::parse(env::args().skip(1), |name| env::var(name).ok())
+    }
+
+    /// Parses `args`, without the program name, looking up environment
+    /// variables with `var`.
+    pub fn parse(
+        args: impl IntoIterator<Item = String>,
+        var: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, OptionsError> {
+        let mut listen = None;
+        let mut unix_socket = None;
+        let mut config = None;
+        let mut state_file = None;
+        let mut host_suffix = None;
+        let mut canonical_base = None;
+        let mut base_path = None;
+        let mut default_language = None;
+        let mut watch_interval = None;
+        let mut request_timeout = None;
+        let mut shutdown_timeout = None;
+        let mut max_connections = None;
+        let mut register_token = None;
+        let mut dev = false;
+        let mut redirect_aliases = false;
+        let mut clamp_parent_segments = false;
+        let mut language_in_path = false;
+        let mut file_cache_size = None;
+        let mut file_cache_max_file = None;
+        let mut max_register_body = None;
+        let mut max_path_length = None;
+        let mut max_path_depth = None;
+        let mut index_files = None;
+        let mut trust_proxy = None;
+        let mut log_clients = false;
+        let mut no_landing_page = false;
+        let mut landing_template = None;
+        let mut forbidden_page = None;
+        let mut language_codes = false;
+        let mut check_config = false;
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => {
+                    (flag.to_string(), Some(value.to_string()))
+                }
+                _ => (arg, None),
+            };
+            match flag.as_str() {
+                "--listen" => listen = Some(value_for(&flag, inline_value, &mut args)?),
+                "--unix-socket" => unix_socket = Some(value_for(&flag, inline_value, &mut args)?),
+                "--config" => config = Some(value_for(&flag, inline_value, &mut args)?),
+                "--state-file" => state_file = Some(value_for(&flag, inline_value, &mut args)?),
+                "--host-suffix" => host_suffix = Some(value_for(&flag, inline_value, &mut args)?),
+                "--canonical-base" => {
+                    canonical_base = Some(value_for(&flag, inline_value, &mut args)?)
+                }
+                "--base-path" => base_path = Some(value_for(&flag, inline_value, &mut args)?),
+                "--default-language" => {
+                    default_language = Some(value_for(&flag, inline_value, &mut args)?)
+                }
+                "--watch-interval" => {
+                    watch_interval = Some(value_for(&flag, inline_value, &mut args)?)
+                }
+                "--request-timeout" => {
+                    request_timeout = Some(value_for(&flag, inline_value, &mut args)?)
+                }
+                "--shutdown-timeout" => {
+                    shutdown_timeout = Some(value_for(&flag, inline_value, &mut args)?)
+                }
+                "--max-connections" => {
+                    max_connections = Some(value_for(&flag, inline_value, &mut args)?)
+                }
+                "--register-token" => {
+                    register_token = Some(value_for(&flag, inline_value, &mut args)?)
+                }
+                "--dev" if inline_value.is_none() => dev = true,
+                "--redirect-aliases" if inline_value.is_none() => redirect_aliases = true,
+                "--language-in-path" if inline_value.is_none() => language_in_path = true,
+                "--clamp-parent-segments" if inline_value.is_none() => clamp_parent_segments = true,
+                "--file-cache-size" => {
+                    file_cache_size = Some(value_for(&flag, inline_value, &mut args)?)
+                }
+                "--file-cache-max-file" => {
+                    file_cache_max_file = Some(value_for(&flag, inline_value, &mut args)?)
+                }
+                "--max-register-body" => {
+                    max_register_body = Some(value_for(&flag, inline_value, &mut args)?)
+                }
+                "--max-path-length" => {
+                    max_path_length = Some(value_for(&flag, inline_value, &mut args)?)
+                }
+                "--max-path-depth" => {
+                    max_path_depth = Some(value_for(&flag, inline_value, &mut args)?)
+                }
+                "--index-files" => index_files = Some(value_for(&flag, inline_value, &mut args)?),
+                "--trust-proxy" => trust_proxy = Some(value_for(&flag, inline_value, &mut args)?),
+                "--log-clients" if inline_value.is_none() => log_clients = true,
+                "--no-landing-page" if inline_value.is_none() => no_landing_page = true,
+                "--landing-template" => {
+                    landing_template = Some(value_for(&flag, inline_value, &mut args)?)
+                }
+                "--language-codes" if inline_value.is_none() => language_codes = true,
+                "--forbidden-page" => {
+                    forbidden_page = Some(value_for(&flag, inline_value, &mut args)?)
+                }
+                "--check-config" if inline_value.is_none() => check_config = true,
+                "-h" | "--help" => return Err(OptionsError::Help),
+                _ => {
+                    return Err(OptionsError::Invalid(format!(
+                        "unrecognized option `{}`",
+                        flag
+                    )))
+                }
+            }
+        }
+
+        let unix_socket = unix_socket
+            .or_else(|| var("DAPSD_UNIX_SOCKET"))
+            .map(PathBuf::from);
+        let listen = match listen.or_else(|| var("DAPSD_LISTEN")) {
+            Some(address) => Some(parse_listen_address(&address)?),
+            None if unix_socket.is_some() => None,
+            None => Some(SocketAddr::new(DEFAULT_LISTEN_IP, DEFAULT_LISTEN_PORT)),
+        };
+
+        let config = config.or_else(|| var("DAPSD_CONFIG")).map(PathBuf::from);
+        if check_config && config.is_none() {
+            return Err(OptionsError::Invalid(
+                "`--check-config` requires `--config`".to_string(),
+            ));
+        }
+        let state_file = state_file
+            .or_else(|| var("DAPSD_STATE_FILE"))
+            .map(PathBuf::from);
+
+        let host_suffix = host_suffix
+            .or_else(|| var("DAPSD_HOST_SUFFIX"))
+            .map_or_else(|| DEFAULT_HOST_SUFFIX.to_string(), normalize_host_suffix);
+
+        let canonical_base = canonical_base
+            .or_else(|| var("DAPSD_CANONICAL_BASE"))
+            .map(|base| parse_canonical_base(&base))
+            .transpose()?;
+
+        let base_path = base_path
+            .or_else(|| var("DAPSD_BASE_PATH"))
+            .map(|path| normalize_base_path(&path))
+            .transpose()?
+            .unwrap_or_default();
+
+        let default_language = default_language.or_else(|| var("DAPSD_DEFAULT_LANGUAGE"));
+
+        let watch_interval = match watch_interval.or_else(|| var("DAPSD_WATCH_INTERVAL")) {
+            Some(seconds) => match seconds.parse::<u64>() {
+                Ok(0) => None,
+                Ok(seconds) => Some(Duration::from_secs(seconds)),
+                Err(_) => {
+                    return Err(OptionsError::Invalid(format!(
+                        "invalid watch interval `{}`, expected a number of seconds",
+                        seconds
+                    )))
+                }
+            },
+            None => Some(DEFAULT_WATCH_INTERVAL),
+        };
+        let request_timeout = match request_timeout.or_else(|| var("DAPSD_REQUEST_TIMEOUT")) {
+            Some(seconds) => match seconds.parse::<u64>() {
+                Ok(0) => None,
+                Ok(seconds) => Some(Duration::from_secs(seconds)),
+                Err(_) => {
+                    return Err(OptionsError::Invalid(format!(
+                        "invalid request timeout `{}`, expected a number of seconds",
+                        seconds
+                    )))
+                }
+            },
+            None => None,
+        };
+        let shutdown_timeout = match shutdown_timeout.or_else(|| var("DAPSD_SHUTDOWN_TIMEOUT")) {
+            Some(seconds) => match seconds.parse::<u64>() {
+                Ok(seconds) => Duration::from_secs(seconds),
+                Err(_) => {
+                    return Err(OptionsError::Invalid(format!(
+                        "invalid shutdown timeout `{}`, expected a number of seconds",
+                        seconds
+                    )))
+                }
+            },
+            None => DEFAULT_SHUTDOWN_TIMEOUT,
+        };
+
+        let max_connections = match max_connections.or_else(|| var("DAPSD_MAX_CONNECTIONS")) {
+            Some(count) => match count.parse::<usize>() {
+                Ok(0) => None,
+                Ok(count) => Some(count),
+                Err(_) => {
+                    return Err(OptionsError::Invalid(format!(
+                        "invalid connection limit `{}`, expected a number",
+                        count
+                    )))
+                }
+            },
+            None => None,
+        };
+
+        let register_token = register_token
+            .or_else(|| var("DAPSD_REGISTER_TOKEN"))
+            .filter(|token| !token.is_empty());
+
+        let dev = dev || is_enabled(var("DAPSD_DEV"));
+        let redirect_aliases = redirect_aliases || is_enabled(var("DAPSD_REDIRECT_ALIASES"));
+        let clamp_parent_segments =
+            clamp_parent_segments || is_enabled(var("DAPSD_CLAMP_PARENT_SEGMENTS"));
+        let language_in_path = language_in_path || is_enabled(var("DAPSD_LANGUAGE_IN_PATH"));
+
+        let file_cache_size = match file_cache_size.or_else(|| var("DAPSD_FILE_CACHE_SIZE")) {
+            Some(bytes) => Some(parse_bytes("file cache size", &bytes)?).filter(|bytes| *bytes > 0),
+            None => None,
+        };
+        let file_cache_max_file =
+            match file_cache_max_file.or_else(|| var("DAPSD_FILE_CACHE_MAX_FILE")) {
+                Some(bytes) => parse_bytes("file cache file size", &bytes)?,
+                None => DEFAULT_FILE_CACHE_MAX_FILE,
+            };
+        let max_register_body = match max_register_body.or_else(|| var("DAPSD_MAX_REGISTER_BODY")) {
+            Some(bytes) => parse_bytes("registration body size", &bytes)?,
+            None => DEFAULT_MAX_REGISTER_BODY,
+        };
+        let max_path_length = match max_path_length.or_else(|| var("DAPSD_MAX_PATH_LENGTH")) {
+            Some(bytes) => parse_bytes("path length", &bytes)?,
+            None => DEFAULT_MAX_PATH_LENGTH,
+        };
+        let max_path_depth = match max_path_depth.or_else(|| var("DAPSD_MAX_PATH_DEPTH")) {
+            Some(count) => count.parse::<usize>().map_err(|_| {
+                OptionsError::Invalid(format!(
+                    "invalid path depth `{}`, expected a number of segments",
+                    count
+                ))
+            })?,
+            None => DEFAULT_MAX_PATH_DEPTH,
+        };
+
+        let index_files: Vec<String> = match index_files.or_else(|| var("DAPSD_INDEX_FILES")) {
+            Some(names) => names
+                .split(',')
+                .map(|name| name.trim().to_string())
+                .collect(),
+            None => vec!["index.html".to_string()],
+        };
+        if let Some(name) = index_files
+            .iter()
+            .find(|name| name.is_empty() || name.contains('/') || name.starts_with('.'))
+        {
+            return Err(OptionsError::Invalid(format!(
+                "invalid index file `{}`, expected a file name",
+                name
+            )));
+        }
+
+        let trusted_proxies = match trust_proxy.or_else(|| var("DAPSD_TRUST_PROXY")) {
+            Some(addresses) => addresses
+                .split(',')
+                .map(|address| {
+                    address.trim().parse::<IpAddr>().map_err(|_| {
+                        OptionsError::Invalid(format!(
+                            "invalid proxy address `{}`, expected an IP address",
+                            address
+                        ))
+                    })
+                })
+                .collect::<Result<_, _>>()?,
+            None => Vec::new(),
+        };
+
+        let log_clients = log_clients || is_enabled(var("DAPSD_LOG_CLIENTS"));
+        let no_landing_page = no_landing_page || is_enabled(var("DAPSD_NO_LANDING_PAGE"));
+        let landing_template = landing_template
+            .or_else(|| var("DAPSD_LANDING_TEMPLATE"))
+            .map(PathBuf::from);
+        let forbidden_page = forbidden_page
+            .or_else(|| var("DAPSD_FORBIDDEN_PAGE"))
+            .map(PathBuf::from);
+        let language_codes = language_codes || is_enabled(var("DAPSD_LANGUAGE_CODES"));
+
+        Ok(Options {
+            listen,
+            unix_socket,
+            config,
+            state_file,
+            host_suffix,
+            canonical_base,
+            base_path,
+            default_language,
+            watch_interval,
+            request_timeout,
+            shutdown_timeout,
+            max_connections,
+            register_token,
+            dev,
+            redirect_aliases,
+            clamp_parent_segments,
+            language_in_path,
+            file_cache_size,
+            file_cache_max_file,
+            max_register_body,
+            max_path_length,
+            max_path_depth,
+            index_files,
+            trusted_proxies,
+            log_clients,
+            no_landing_page,
+            landing_template,
+            forbidden_page,
+            language_codes,
+            check_config,
+        })
+    }
+}
+
+fn parse_bytes(what: &str, bytes: &str) -> Result<usize, OptionsError> {
+    bytes.parse().map_err(|_| {
+        OptionsError::Invalid(format!(
+            "invalid {} `{}`, expected a number of bytes",
+            what, bytes
+        ))
+    })
+}
+
+/// Switches set in the environment are on unless empty or `0`.
+fn is_enabled(value: Option<String>) -> bool {
+    value.is_some_and(|value| !value.is_empty() && value != "0")
+}
+
+fn value_for(
+    flag: &str,
+    inline_value: Option<String>,
+    args: &mut impl Iterator<Item = String>,
+) -> Result<String, OptionsError> {
+    inline_value
+        .or_else(|| args.next())
+        .ok_or_else(|| OptionsError::Invalid(format!("`{}` requires a value", flag)))
+}
+
+/// The suffix always begins with a dot so that `docs` can't match the end
+/// of `rustdocs`.
+fn normalize_host_suffix(host_suffix: String) -> String {
+    if host_suffix.starts_with('.') {
+        host_suffix
+    } else {
+        format!(".{}", host_suffix)
+    }
+}
+
+/// An `http` or `https` URL with nothing after the host and port, though
+/// a trailing slash is allowed.
+fn parse_canonical_base(base: &str) -> Result<Url, OptionsError> {
+    let invalid = || {
+        OptionsError::Invalid(format!(
+            "invalid canonical base `{}`, expected a URL like https://docs.example.com",
+            base
+        ))
+    };
+    let url = Url::parse(base).map_err(|_| invalid())?;
+    let bare = matches!(url.scheme(), "http" | "https")
+        && url.host_str().is_some()
+        && url.username().is_empty()
+        && url.password().is_none()
+        && url.path() == "/"
+        && url.query().is_none()
+        && url.fragment().is_none();
+    if bare {
+        Ok(url)
+    } else {
+        Err(invalid())
+    }
+}
+
+/// Adds any missing leading slash and drops trailing ones, so `/` and an
+/// empty path both mean the root.
+fn normalize_base_path(path: &str) -> Result<String, OptionsError> {
+    let trimmed = path.trim_matches('/');
+    let valid = !trimmed.contains(|c: char| matches!(c, '?' | '#' | '%') || c.is_whitespace())
+        && (trimmed.is_empty()
+            || trimmed
+                .split('/')
+                .all(|segment| !matches!(segment, "" | "." | "..")));
+    if valid && trimmed.is_empty() {
+        Ok(String::new())
+    } else if valid {
+        Ok(format!("/{}", trimmed))
+    } else {
+        Err(OptionsError::Invalid(format!(
+            "invalid base path `{}`, expected a path like /docs",
+            path
+        )))
+    }
+}
+
+/// Accepts a socket address, a `host:port` pair to resolve, or a bare port
+/// to bind on the default address.
+fn parse_listen_address(address: &str) -> Result<SocketAddr, OptionsError> {
+    if let Ok(port) = address.parse::<u16>() {
+        return Ok(SocketAddr::new(DEFAULT_LISTEN_IP, port));
+    }
+    if let Ok(socket_address) = address.parse::<SocketAddr>() {
+        return Ok(socket_address);
+    }
+    address
+        .to_socket_addrs()
+        .ok()
+        .and_then(|mut addresses| addresses.next())
+        .ok_or_else(|| {
+            OptionsError::Invalid(format!(
+                "invalid listen address `{}`, expected host:port or a port",
+                address
+            ))
+        })
+}
@@ -0,0 +1,76 @@
+//! Headers set by the reverse proxies named with `--trust-proxy`. They are
+//! ignored on requests from anywhere else, since any client can send them.
+
+use std::net::{IpAddr, SocketAddr};
+
+use tide::Request;
+
+/// The host the client asked for, when the request came through a trusted
+/// proxy. Proxies in a chain append to `X-Forwarded-Host`, and anything
+/// before the entry of the nearest one came from further out, the client
+/// included, so it is the last entry.
+pub fn forwarded_host<'a, State>(
+    req: &'a Request<State>,
+    trusted_proxies: &[IpAddr],
+) -> Option<&'a str> {
+    if !trusted_proxies.contains(&peer_ip(req)?) {
+        return None;
+    }
+    last_entry(req, "x-forwarded-host")
+}
+
+/// The scheme the client used, when the request came through a trusted
+/// proxy, which may have terminated HTTPS. Like the host, it is the last
+/// entry of `X-Forwarded-Proto`.
+pub fn forwarded_proto<'a, State>(
+    req: &'a Request<State>,
+    trusted_proxies: &[IpAddr],
+) -> Option<&'a str> {
+    if !trusted_proxies.contains(&peer_ip(req)?) {
+        return None;
+    }
+    match last_entry(req, "x-forwarded-proto") {
+        Some("http") => Some("http"),
+        Some("https") => Some("https"),
+        _ => None,
+    }
+}
+
+/// The address of the client a request came from. Each trusted proxy
+/// appends the address it received the request from to `X-Forwarded-For`,
+/// so the client is the last entry that no trusted proxy could have added.
+pub fn client_ip<State>(req: &Request<State>, trusted_proxies: &[IpAddr]) -> Option<IpAddr> {
+    let peer = peer_ip(req)?;
+    if !trusted_proxies.contains(&peer) {
+        return Some(peer);
+    }
+    let forwarded = req
+        .header("x-forwarded-for")
+        .into_iter()
+        .flat_map(|values| values.iter())
+        .flat_map(|value| value.as_str().split(','))
+        .map(str::trim)
+        .collect::<Vec<_>>();
+    for address in forwarded.into_iter().rev() {
+        match address.parse::<IpAddr>() {
+            Ok(address) if trusted_proxies.contains(&address) => continue,
+            Ok(address) => return Some(address),
+            Err(_) => break,
+        }
+    }
+    Some(peer)
+}
+
+/// The right-most entry of the comma-separated values of `name`.
+fn last_entry<'a, State>(req: &'a Request<State>, name: &str) -> Option<&'a str> {
+    req.header(name)?
+        .iter()
+        .flat_map(|value| value.as_str().split(','))
+        .map(str::trim)
+        .last()
+        .filter(|entry| !entry.is_empty())
+}
+
+fn peer_ip<State>(req: &Request<State>) -> Option<IpAddr> {
+    Some(req.peer_addr()?.parse::<SocketAddr>().ok()?.ip())
+}
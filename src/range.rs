@@ -0,0 +1,72 @@
+//! Single `Range: bytes=` requests, answered with 206 Partial Content.
+
+use tide::{http::headers::HeaderValues, Request};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ByteRange {
+    /// No usable range was requested, so the whole file is served. This
+    /// includes multi-part and malformed ranges, which may be ignored.
+    Full,
+    /// Inclusive first and last byte positions.
+    Partial(u64, u64),
+    Unsatisfiable,
+}
+
+impl ByteRange {
+    /// `If-Range` only accepts the range when the file hasn't changed. Our
+    /// entity tags are weak, which `If-Range` never matches, so only the
+    /// `Last-Modified` date is compared.
+    pub fn from_request<State>(
+        req: &Request<State>,
+        len: u64,
+        last_modified: Option<&str>,
+    ) -> ByteRange {
+        if let Some(if_range) = req.header("if-range") {
+            if Some(if_range.last().as_str()) != last_modified {
+                return ByteRange::Full;
+            }
+        }
+        req.header("range")
+            .map(HeaderValues::last)
+            .map_or(ByteRange::Full, |range| Self::parse(range.as_str(), len))
+    }
+
+    fn parse(range: &str, len: u64) -> ByteRange {
+        let spec = match range.trim().strip_prefix("bytes=") {
+            Some(spec) if !spec.contains(',') => spec.trim(),
+            _ => return ByteRange::Full,
+        };
+        let (first, last) = match spec.split_once('-') {
+            Some(bounds) => bounds,
+            None => return ByteRange::Full,
+        };
+        // `bytes=-n` asks for the final n bytes.
+        if first.is_empty() {
+            return match last.parse::<u64>() {
+                Ok(0) => ByteRange::Unsatisfiable,
+                Ok(_) if len == 0 => ByteRange::Unsatisfiable,
+                Ok(suffix) => ByteRange::Partial(len.saturating_sub(suffix), len - 1),
+                Err(_) => ByteRange::Full,
+            };
+        }
+        let first = match first.parse::<u64>() {
+            Ok(first) => first,
+            Err(_) => return ByteRange::Full,
+        };
+        if last.is_empty() {
+            return ByteRange::bounded(first, u64::MAX, len);
+        }
+        match last.parse::<u64>() {
+            Ok(last) if first <= last => ByteRange::bounded(first, last, len),
+            _ => ByteRange::Full,
+        }
+    }
+
+    fn bounded(first: u64, last: u64, len: u64) -> ByteRange {
+        if first >= len {
+            ByteRange::Unsatisfiable
+        } else {
+            ByteRange::Partial(first, last.min(len - 1))
+        }
+    }
+}
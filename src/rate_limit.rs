@@ -0,0 +1,153 @@
+//! Per-client token bucket rate limits, configured by the `rate-limits`
+//! table of the config file. Serving pages and the API are limited
+//! separately, and neither is limited unless configured.
+
+use std::{
+    collections::HashMap,
+    io,
+    net::IpAddr,
+    result::Result as StdResult,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use tide::{
+    http::headers::RETRY_AFTER, log, prelude::*, utils::async_trait, Middleware, Next, Request,
+    Response, Result, StatusCode,
+};
+
+use crate::{proxy, ServerState};
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct RateLimits {
+    #[serde(default)]
+    pub serve: Option<Limit>,
+    #[serde(default)]
+    pub api: Option<Limit>,
+    /// Clients tracked by each limit at once. Past this, clients whose
+    /// buckets have refilled are forgotten first, then the least recently
+    /// seen until half as many are left.
+    #[serde(default = "default_max_clients")]
+    pub max_clients: usize,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        RateLimits {
+            serve: None,
+            api: None,
+            max_clients: default_max_clients(),
+        }
+    }
+}
+
+fn default_max_clients() -> usize {
+    10_000
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Limit {
+    /// Sustained requests per second.
+    pub per_second: f64,
+    /// Requests a client may make at once after being idle.
+    pub burst: u32,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+#[derive(Clone, Debug)]
+pub struct RateLimit {
+    limit: Limit,
+    max_clients: usize,
+    buckets: Arc<Mutex<HashMap<IpAddr, Bucket>>>,
+}
+
+impl RateLimit {
+    pub fn new(name: &str, limit: Limit, max_clients: usize) -> io::Result<Self> {
+        if !limit.per_second.is_finite()
+            || limit.per_second <= 0.0
+            || limit.burst == 0
+            || max_clients == 0
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{} rate limit must allow a positive rate, burst and number of clients",
+                    name
+                ),
+            ));
+        }
+        Ok(RateLimit {
+            limit,
+            max_clients,
+            buckets: Arc::default(),
+        })
+    }
+
+    /// Takes a token from `client`'s bucket, or returns how long until one
+    /// will be available.
+    fn take(&self, client: IpAddr, now: Instant) -> StdResult<(), Duration> {
+        let burst = self.limit.burst as f64;
+        let refilled = |bucket: &Bucket| {
+            let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+            (bucket.tokens + elapsed * self.limit.per_second).min(burst)
+        };
+        let mut buckets = self.buckets.lock().unwrap();
+        if !buckets.contains_key(&client) && buckets.len() >= self.max_clients {
+            // Freeing half the clients at once keeps the scans this takes
+            // to one per that many new clients, however many there are.
+            buckets.retain(|_, bucket| refilled(bucket) < burst);
+            let keep = self.max_clients / 2;
+            if buckets.len() > keep {
+                let mut by_age: Vec<(Instant, IpAddr)> = buckets
+                    .iter()
+                    .map(|(client, bucket)| (bucket.updated, *client))
+                    .collect();
+                let forget = by_age.len() - keep;
+                by_age.select_nth_unstable(forget - 1);
+                for (_, client) in &by_age[..forget] {
+                    buckets.remove(client);
+                }
+            }
+        }
+        let bucket = buckets.entry(client).or_insert(Bucket {
+            tokens: burst,
+            updated: now,
+        });
+        bucket.tokens = refilled(bucket);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / self.limit.per_second,
+            ))
+        }
+    }
+}
+
+#[async_trait]
+impl Middleware<ServerState> for RateLimit {
+    async fn handle(&self, req: Request<ServerState>, next: Next<'_, ServerState>) -> Result {
+        let client = match proxy::client_ip(&req, &req.state().options.trusted_proxies) {
+            Some(client) => client,
+            None => return Ok(next.run(req).await),
+        };
+        match self.take(client, Instant::now()) {
+            Ok(()) => Ok(next.run(req).await),
+            Err(wait) => {
+                log::debug!("Rate limited {}", client);
+                Ok(Response::builder(StatusCode::TooManyRequests)
+                    .header(RETRY_AFTER, wait.as_secs_f64().ceil().max(1.0).to_string())
+                    .build())
+            }
+        }
+    }
+}
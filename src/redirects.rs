@@ -0,0 +1,110 @@
+//! Per-project redirect rules, so that links into docs that have been
+//! reorganized keep working.
+
+use std::convert::TryFrom;
+
+use percent_encoding::utf8_percent_encode;
+use tide::{http::Url, prelude::*, Error, Result, StatusCode};
+
+use crate::autoindex::PATH_SEGMENT;
+
+const STATUSES: &[u16] = &[301, 302, 307, 308];
+
+/// Paths are relative to the project root, as in `/old/page.html`, and
+/// written without percent-encoding.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct RedirectRule {
+    pub from: String,
+    /// A path in the project, or an `http` or `https` URL.
+    pub to: String,
+    /// Also redirect paths under `from`, appending what follows it to `to`.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub prefix: bool,
+    #[serde(default = "default_status")]
+    pub status: u16,
+}
+
+fn default_status() -> u16 {
+    301
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+impl RedirectRule {
+    pub fn check(&self) -> Result<()> {
+        if !self.from.starts_with('/') {
+            return Err(Error::from_str(
+                StatusCode::BadRequest,
+                format!("redirect from {:?} must start with /", self.from),
+            ));
+        }
+        if !self.to.starts_with('/') && !is_web_url(&self.to) {
+            return Err(Error::from_str(
+                StatusCode::BadRequest,
+                format!(
+                    "redirect to {:?} must start with / or be an http or https URL",
+                    self.to
+                ),
+            ));
+        }
+        if !STATUSES.contains(&self.status) {
+            return Err(Error::from_str(
+                StatusCode::BadRequest,
+                format!("redirect status must be one of {:?}", STATUSES),
+            ));
+        }
+        Ok(())
+    }
+
+    /// What follows `from` in `path`, when this rule applies to it.
+    fn remainder<'a>(&self, path: &'a str) -> Option<&'a str> {
+        if path == self.from {
+            return Some("");
+        }
+        if !self.prefix {
+            return None;
+        }
+        let rest = path.strip_prefix(self.from.trim_end_matches('/'))?;
+        Some(rest).filter(|rest| rest.starts_with('/'))
+    }
+
+    /// The `Location` for a redirected `path`. Targets in the project are
+    /// put under `base`, the URL path the project is served at.
+    fn location(&self, base: &str, remainder: &str) -> String {
+        let remainder = encode_path(remainder);
+        let to = if self.to.starts_with('/') {
+            format!("{}{}", base.trim_end_matches('/'), encode_path(&self.to))
+        } else {
+            Url::parse(&self.to).map_or_else(|_| self.to.clone(), |url| url.to_string())
+        };
+        if remainder.is_empty() {
+            to
+        } else {
+            format!("{}{}", to.trim_end_matches('/'), remainder)
+        }
+    }
+}
+
+/// The status and `Location` of the first of `rules` that applies to
+/// `path`, a decoded path relative to the project root.
+pub fn find(rules: &[RedirectRule], base: &str, path: &str) -> Option<(StatusCode, String)> {
+    rules.iter().find_map(|rule| {
+        let remainder = rule.remainder(path)?;
+        let status = StatusCode::try_from(rule.status).ok()?;
+        Some((status, rule.location(base, remainder)))
+    })
+}
+
+fn is_web_url(url: &str) -> bool {
+    Url::parse(url).is_ok_and(|url| url.scheme() == "http" || url.scheme() == "https")
+}
+
+fn encode_path(path: &str) -> String {
+    path.split('/')
+        .map(|segment| utf8_percent_encode(segment, PATH_SEGMENT).to_string())
+        .collect::<Vec<_>>()
+        .join("/")
+}
@@ -0,0 +1,57 @@
+//! `/robots.txt`, configured by the `robots` table of the config file.
+//! Crawlers are allowed everywhere unless asked to stay out of a language
+//! or project, and always asked to stay out of languages behind auth.
+
+use std::collections::{HashMap, HashSet};
+
+use percent_encoding::utf8_percent_encode;
+use tide::prelude::*;
+
+use crate::autoindex;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Robots {
+    /// Languages crawlers are asked to stay out of entirely.
+    #[serde(default)]
+    pub disallow_languages: HashSet<String>,
+    /// Projects crawlers are asked to stay out of, by language name.
+    #[serde(default)]
+    pub disallow_projects: HashMap<String, Vec<String>>,
+}
+
+impl Robots {
+    pub fn disallows_language(&self, language: &str) -> bool {
+        self.disallow_languages.contains(language)
+    }
+
+    pub fn disallowed_projects(&self, language: &str) -> &[String] {
+        self.disallow_projects
+            .get(language)
+            .map_or(&[], Vec::as_slice)
+    }
+}
+
+/// A policy for all user agents that disallows each of `paths` under
+/// `base_path`, or nothing when there are none.
+pub fn render(base_path: &str, paths: &[String]) -> String {
+    let mut robots = String::from("User-agent: *\n");
+    if paths.is_empty() {
+        robots.push_str("Disallow:\n");
+    }
+    for path in paths {
+        robots.push_str("Disallow: ");
+        robots.push_str(base_path);
+        robots.push_str(path);
+        robots.push('\n');
+    }
+    robots
+}
+
+/// The path prefix covering every page of a project.
+pub fn project_path(project_name: &str) -> String {
+    format!(
+        "/{}/",
+        utf8_percent_encode(project_name, autoindex::PATH_SEGMENT)
+    )
+}
@@ -0,0 +1,295 @@
+//! Full-text search over a project's HTML pages. The inverted index is
+//! built on first use, or on `POST /api/index/:project_name`, and kept with
+//! the project until it is re-registered or the config is reloaded.
+
+use std::{collections::HashMap, fs, io, path::Path, sync::Arc};
+
+use async_std::sync::Mutex;
+use tide::log;
+
+use crate::{archive::Archive, walk};
+
+/// Indexing stops after this many pages, or this many postings in all, so
+/// that a huge doc set can't exhaust memory. Searches then only cover the
+/// pages indexed so far.
+const MAX_PAGES: usize = 20_000;
+const MAX_POSTINGS: usize = 2_000_000;
+/// Bigger pages are skipped, as they are rarely prose worth searching.
+const MAX_PAGE_BYTES: u64 = 2 * 1024 * 1024;
+const MAX_TERM_LEN: usize = 40;
+/// A term in the title counts as much as this many in the body.
+const TITLE_WEIGHT: u32 = 5;
+
+#[derive(Debug)]
+struct Page {
+    /// Relative to the project root, with `/` separators.
+    path: String,
+    title: String,
+}
+
+#[derive(Debug, Default)]
+pub struct SearchIndex {
+    pages: Vec<Page>,
+    /// Pages containing each term, with the term's weight in that page.
+    terms: HashMap<String, Vec<(u32, u32)>>,
+    postings: usize,
+    pub truncated: bool,
+}
+
+#[derive(Debug)]
+pub struct Hit<'a> {
+    pub path: &'a str,
+    pub title: &'a str,
+    pub score: f64,
+}
+
+impl SearchIndex {
+    pub fn pages(&self) -> usize {
+        self.pages.len()
+    }
+
+    pub fn terms(&self) -> usize {
+        self.terms.len()
+    }
+
+    /// Pages containing every term of `query`, best first, scored by the
+    /// sum of each term's weight in the page times its inverse document
+    /// frequency.
+    pub fn search(&self, query: &str, limit: usize) -> Vec<Hit<'_>> {
+        let mut terms: Vec<String> = tokens(query).collect();
+        terms.sort_unstable();
+        terms.dedup();
+        if terms.is_empty() {
+            return Vec::new();
+        }
+        let page_count = self.pages.len() as f64;
+        let mut scores: HashMap<u32, (usize, f64)> = HashMap::new();
+        for term in &terms {
+            let postings = match self.terms.get(term) {
+                Some(postings) => postings,
+                None => return Vec::new(),
+            };
+            let idf = (1.0 + page_count / postings.len() as f64).ln();
+            for &(page, weight) in postings {
+                let score = scores.entry(page).or_default();
+                score.0 += 1;
+                score.1 += weight as f64 * idf;
+            }
+        }
+        let mut hits: Vec<(u32, f64)> = scores
+            .into_iter()
+            .filter(|(_, (matched, _))| *matched == terms.len())
+            .map(|(page, (_, score))| (page, score))
+            .collect();
+        hits.sort_by(|a, b| {
+            b.1.partial_cmp(&a.1)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| {
+                    self.pages[a.0 as usize]
+                        .path
+                        .cmp(&self.pages[b.0 as usize].path)
+                })
+        });
+        hits.into_iter()
+            .take(limit)
+            .map(|(page, score)| {
+                let page = &self.pages[page as usize];
+                Hit {
+                    path: &page.path,
+                    title: &page.title,
+                    score,
+                }
+            })
+            .collect()
+    }
+
+    /// Returns false once the index is full.
+    fn add(&mut self, path: String, html: &str) -> bool {
+        if self.pages.len() >= MAX_PAGES || self.postings >= MAX_POSTINGS {
+            self.truncated = true;
+            return false;
+        }
+        let (title, text) = extract_text(html);
+        let mut weights: HashMap<String, u32> = HashMap::new();
+        for term in tokens(&text) {
+            *weights.entry(term).or_default() += 1;
+        }
+        for term in tokens(&title) {
+            *weights.entry(term).or_default() += TITLE_WEIGHT;
+        }
+        let page = self.pages.len() as u32;
+        self.postings += weights.len();
+        for (term, weight) in weights {
+            self.terms.entry(term).or_default().push((page, weight));
+        }
+        let title = if title.trim().is_empty() {
+            path.clone()
+        } else {
+            title.trim().to_string()
+        };
+        self.pages.push(Page { path, title });
+        true
+    }
+
+    /// Symlinks aren't followed, so nothing outside `root` is indexed.
+    pub fn build_from_directory(root: &Path) -> io::Result<Self> {
+        let mut index = SearchIndex::default();
+        for file in walk::html_files(root)? {
+            if file.metadata.len() > MAX_PAGE_BYTES {
+                continue;
+            }
+            let html = match fs::read(&file.full_path) {
+                Ok(html) => String::from_utf8_lossy(&html).into_owned(),
+                Err(e) => {
+                    log::warn!("Failed to index {:?}: {}", file.full_path, e);
+                    continue;
+                }
+            };
+            if !index.add(file.path, &html) {
+                break;
+            }
+        }
+        Ok(index)
+    }
+
+    pub fn build_from_archive(archive: &Archive) -> io::Result<Self> {
+        let mut index = SearchIndex::default();
+        let mut names: Vec<&str> = archive
+            .names()
+            .filter(|name| walk::is_html(name) && !walk::is_hidden(Path::new(name)))
+            .collect();
+        names.sort_unstable();
+        for name in names {
+            let entry = match archive.entry(name) {
+                Some(entry) if entry.len <= MAX_PAGE_BYTES => entry,
+                _ => continue,
+            };
+            let html = String::from_utf8_lossy(&archive.read(entry)?).into_owned();
+            if !index.add(name.to_string(), &html) {
+                break;
+            }
+        }
+        Ok(index)
+    }
+}
+
+/// Lowercased runs of letters, digits and underscores, so that identifiers
+/// like `from_str` are kept whole.
+fn tokens(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|token| !token.is_empty() && token.chars().count() <= MAX_TERM_LEN)
+        .map(str::to_lowercase)
+}
+
+/// Splits an HTML page into its `<title>` and the rest of its text, with
+/// tags, scripts and styles removed and common entities decoded.
+fn extract_text(html: &str) -> (String, String) {
+    let mut title = String::new();
+    let mut text = String::new();
+    let mut in_title = false;
+    let mut rest = html;
+    while let Some(start) = rest.find(['<', '&']) {
+        let target = if in_title { &mut title } else { &mut text };
+        target.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if rest.starts_with('&') {
+            let end = rest
+                .char_indices()
+                .take(12)
+                .find(|(_, c)| *c == ';')
+                .map(|(end, _)| end);
+            match end.map(|end| (&rest[1..end], end)) {
+                Some((entity, end)) => {
+                    target.push(decode_entity(entity));
+                    rest = &rest[end + 1..];
+                }
+                None => {
+                    target.push('&');
+                    rest = &rest[1..];
+                }
+            }
+            continue;
+        }
+        let end = match rest.find('>') {
+            Some(end) => end,
+            None => break,
+        };
+        let tag = rest[1..end].trim().to_ascii_lowercase();
+        rest = &rest[end + 1..];
+        let name = tag.split(|c: char| c.is_whitespace() || c == '/').next();
+        match name {
+            Some("script") | Some("style") if !tag.starts_with('/') => {
+                let closing = format!("</{}", name.unwrap_or_default());
+                match rest.to_ascii_lowercase().find(&closing) {
+                    Some(close) => rest = &rest[close..],
+                    None => break,
+                }
+            }
+            Some("title") => in_title = true,
+            _ if tag == "/title" => in_title = false,
+            _ => text.push(' '),
+        }
+    }
+    if in_title {
+        title.push_str(rest);
+    } else {
+        text.push_str(rest);
+    }
+    (title, text)
+}
+
+fn decode_entity(entity: &str) -> char {
+    match entity {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        _ => entity
+            .strip_prefix("#x")
+            .or_else(|| entity.strip_prefix("#X"))
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            .or_else(|| entity.strip_prefix('#')?.parse().ok())
+            .and_then(char::from_u32)
+            .unwrap_or(' '),
+    }
+}
+
+/// The index of one project. Concurrent searches of an unindexed project
+/// wait for a single build.
+#[derive(Debug, Default)]
+pub struct SearchCache {
+    index: Mutex<Option<Arc<SearchIndex>>>,
+}
+
+impl SearchCache {
+    /// The current index, building it with `build` if there is none.
+    pub async fn get<F>(&self, build: F) -> io::Result<Arc<SearchIndex>>
+    where
+        F: std::future::Future<Output = io::Result<SearchIndex>>,
+    {
+        let mut index = self.index.lock().await;
+        if let Some(index) = &*index {
+            return Ok(index.clone());
+        }
+        let built = Arc::new(build.await?);
+        *index = Some(built.clone());
+        Ok(built)
+    }
+
+    /// Replaces the index with a freshly built one.
+    pub async fn rebuild<F>(&self, build: F) -> io::Result<Arc<SearchIndex>>
+    where
+        F: std::future::Future<Output = io::Result<SearchIndex>>,
+    {
+        let mut index = self.index.lock().await;
+        let built = Arc::new(build.await?);
+        *index = Some(built.clone());
+        Ok(built)
+    }
+
+    /// Drops the index, so the next search builds a new one.
+    pub async fn clear(&self) {
+        *self.index.lock().await = None;
+    }
+}
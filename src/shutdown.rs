@@ -0,0 +1,116 @@
+//! Graceful shutdown: stop accepting connections on SIGINT or SIGTERM and
+//! give in-flight requests up to `--shutdown-timeout` to finish.
+
+use std::{
+    future::Future,
+    io,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    thread,
+    time::{Duration, Instant},
+};
+
+use async_std::{
+    channel,
+    io::{BufReader, Read},
+    task,
+};
+use signal_hook::{
+    consts::{SIGINT, SIGTERM},
+    iterator::Signals,
+};
+use tide::{utils::async_trait, Body, Middleware, Next, Request, Result};
+
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Counts the requests currently being handled, each until its body has
+/// been sent.
+#[derive(Clone, Debug, Default)]
+pub struct InFlight(Arc<AtomicUsize>);
+
+impl InFlight {
+    pub fn count(&self) -> usize {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// Waits for in-flight requests to finish, giving up after `timeout`.
+    /// Returns the number of requests still running.
+    pub async fn drain(&self, timeout: Duration) -> usize {
+        let deadline = Instant::now() + timeout;
+        while self.count() > 0 && Instant::now() < deadline {
+            task::sleep(DRAIN_POLL_INTERVAL).await;
+        }
+        self.count()
+    }
+}
+
+/// Decrements on drop so that cancelled or panicking requests, and bodies
+/// whose client went away, are counted as finished too.
+struct InFlightGuard(Arc<AtomicUsize>);
+
+impl InFlightGuard {
+    fn new(count: Arc<AtomicUsize>) -> Self {
+        count.fetch_add(1, Ordering::SeqCst);
+        InFlightGuard(count)
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// A response body that keeps its request counted while it is read.
+struct CountedBody {
+    body: Body,
+    _guard: InFlightGuard,
+}
+
+impl Read for CountedBody {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.body).poll_read(cx, buf)
+    }
+}
+
+#[async_trait]
+impl<State: Clone + Send + Sync + 'static> Middleware<State> for InFlight {
+    async fn handle(&self, req: Request<State>, next: Next<'_, State>) -> Result {
+        let guard = InFlightGuard::new(self.0.clone());
+        let mut response = next.run(req).await;
+        if response.is_empty() == Some(true) {
+            return Ok(response);
+        }
+        let body = response.take_body();
+        let (len, mime) = (body.len(), body.mime().clone());
+        let counted = CountedBody {
+            body,
+            _guard: guard,
+        };
+        let mut body = Body::from_reader(BufReader::new(counted), len);
+        body.set_mime(mime);
+        response.set_body(body);
+        Ok(response)
+    }
+}
+
+/// Installs handlers for SIGINT and SIGTERM immediately, returning a future
+/// that resolves with the first signal received.
+pub fn signal() -> io::Result<impl Future<Output = i32>> {
+    let mut signals = Signals::new([SIGINT, SIGTERM])?;
+    let (sender, receiver) = channel::bounded(1);
+    thread::spawn(move || {
+        if let Some(signal) = signals.forever().next() {
+            let _ = sender.try_send(signal);
+        }
+    });
+    Ok(async move { receiver.recv().await.unwrap_or(SIGTERM) })
+}
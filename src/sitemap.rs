@@ -0,0 +1,144 @@
+//! `sitemap.xml` for each project, listing its HTML pages. The pages are
+//! found on first request and kept until the directory watcher sees the
+//! project change, or it is re-registered or the config is reloaded.
+
+use std::{io, path::Path, sync::Arc, time::SystemTime};
+
+use async_std::sync::Mutex;
+use percent_encoding::utf8_percent_encode;
+use tide::log;
+
+use crate::{
+    archive::Archive,
+    autoindex, datetime,
+    walk::{self, Fingerprint},
+};
+
+/// The most URLs the sitemap protocol allows in one file.
+const MAX_URLS: usize = 50_000;
+
+#[derive(Debug)]
+struct Page {
+    /// Relative to the project root, with `/` separators.
+    path: String,
+    modified: Option<SystemTime>,
+}
+
+#[derive(Debug)]
+pub struct Pages {
+    pages: Vec<Page>,
+    /// Of the project when its pages were listed.
+    fingerprint: Fingerprint,
+}
+
+impl Pages {
+    /// Symlinks aren't followed, so nothing outside `root` is listed.
+    pub fn from_directory(root: &Path, fingerprint: Fingerprint) -> io::Result<Self> {
+        let pages = walk::html_files(root)?
+            .into_iter()
+            .map(|file| Page {
+                path: file.path,
+                modified: file.metadata.modified().ok(),
+            })
+            .collect();
+        Ok(Pages::new(pages, fingerprint))
+    }
+
+    pub fn from_archive(archive: &Archive, fingerprint: Fingerprint) -> Self {
+        let pages = archive
+            .names()
+            .filter(|name| walk::is_html(name) && !walk::is_hidden(Path::new(name)))
+            .map(|name| Page {
+                path: name.to_string(),
+                modified: archive.entry(name).and_then(|entry| entry.modified),
+            })
+            .collect();
+        Pages::new(pages, fingerprint)
+    }
+
+    fn new(mut pages: Vec<Page>, fingerprint: Fingerprint) -> Self {
+        pages.sort_by(|a, b| a.path.cmp(&b.path));
+        if pages.len() > MAX_URLS {
+            log::warn!(
+                "Sitemap lists only the first {} of {} pages",
+                MAX_URLS,
+                pages.len()
+            );
+            pages.truncate(MAX_URLS);
+        }
+        Pages { pages, fingerprint }
+    }
+
+    /// The sitemap with each page's URL under `base`, which ends in `/`.
+    /// An `index.html` is listed as its directory, which is where it is
+    /// served.
+    pub fn render(&self, base: &str) -> String {
+        let mut xml = String::from(concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n"
+        ));
+        for page in &self.pages {
+            let path = match page.path.strip_suffix("index.html") {
+                Some(directory) if directory.is_empty() || directory.ends_with('/') => directory,
+                _ => &page.path,
+            };
+            let path: Vec<_> = path
+                .split('/')
+                .map(|segment| utf8_percent_encode(segment, autoindex::PATH_SEGMENT).to_string())
+                .collect();
+            xml.push_str("  <url><loc>");
+            xml.push_str(&escape(&format!("{}{}", base, path.join("/"))));
+            xml.push_str("</loc>");
+            if let Some(modified) = page.modified.and_then(datetime::utc) {
+                xml.push_str("<lastmod>");
+                xml.push_str(&modified);
+                xml.push_str("</lastmod>");
+            }
+            xml.push_str("</url>\n");
+        }
+        xml.push_str("</urlset>\n");
+        xml
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// The pages of one project. Concurrent requests for an unlisted project
+/// wait for a single walk.
+#[derive(Debug, Default)]
+pub struct SitemapCache {
+    pages: Mutex<Option<Arc<Pages>>>,
+}
+
+impl SitemapCache {
+    /// The current pages, listing them with `list` if there are none.
+    pub async fn get<F>(&self, list: F) -> io::Result<Arc<Pages>>
+    where
+        F: std::future::Future<Output = io::Result<Pages>>,
+    {
+        let mut pages = self.pages.lock().await;
+        if let Some(pages) = &*pages {
+            return Ok(pages.clone());
+        }
+        let listed = Arc::new(list.await?);
+        *pages = Some(listed.clone());
+        Ok(listed)
+    }
+
+    /// The fingerprint the cached pages were listed at, if any are cached.
+    pub async fn fingerprint(&self) -> Option<Fingerprint> {
+        let pages = self.pages.lock().await;
+        pages.as_ref().map(|pages| pages.fingerprint.clone())
+    }
+
+    /// Drops the pages, so the next request lists them again.
+    pub async fn clear(&self) {
+        *self.pages.lock().await = None;
+    }
+}
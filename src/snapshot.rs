@@ -0,0 +1,115 @@
+//! A file opened once, whose headers and body both come from the open
+//! handle. A rebuild that replaces the file after it was opened doesn't
+//! affect the response, and one that rewrites it in place fails the read
+//! rather than sending fewer bytes than the `Content-Length` promised.
+
+use std::{
+    fs::Metadata,
+    io::{self, SeekFrom},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use async_std::{
+    fs::File,
+    io::{BufReader, Read},
+    path::Path,
+    prelude::*,
+};
+use tide::{http::Mime, Body};
+
+/// As many bytes as `Body::from_file` sniffs for a MIME type.
+pub const SNIFF_LEN: usize = 300;
+
+#[derive(Debug)]
+pub struct Snapshot {
+    file: File,
+    metadata: Metadata,
+}
+
+impl Snapshot {
+    pub async fn open(path: &Path) -> io::Result<Self> {
+        let file = File::open(path).await?;
+        let metadata = file.metadata().await?;
+        Ok(Snapshot { file, metadata })
+    }
+
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    /// The type sniffed from the first bytes, as `Body::from_file` does.
+    pub async fn sniff(&mut self) -> io::Result<Option<Mime>> {
+        let mut prefix = vec![0; SNIFF_LEN.min(self.metadata.len() as usize)];
+        let mut filled = 0;
+        while filled < prefix.len() {
+            match self.file.read(&mut prefix[filled..]).await? {
+                0 => break,
+                n => filled += n,
+            }
+        }
+        self.file.seek(SeekFrom::Start(0)).await?;
+        Ok(Mime::sniff(&prefix[..filled]).ok())
+    }
+
+    pub async fn read_to_end(self) -> io::Result<Vec<u8>> {
+        let len = self.metadata.len();
+        let mut contents = Vec::with_capacity(len as usize);
+        Exact::new(self.file, len)
+            .read_to_end(&mut contents)
+            .await?;
+        Ok(contents)
+    }
+
+    /// Streams `len` bytes starting at `first`.
+    pub async fn body(mut self, first: u64, len: u64) -> io::Result<Body> {
+        self.file.seek(SeekFrom::Start(first)).await?;
+        let reader = BufReader::new(Exact::new(self.file, len));
+        Ok(Body::from_reader(reader, Some(len as usize)))
+    }
+}
+
+/// The error for a file that was shorter than its snapshot when read.
+pub fn is_changed(e: &io::Error) -> bool {
+    e.kind() == io::ErrorKind::UnexpectedEof
+}
+
+/// Reads exactly `remaining` bytes: any more are left unread, and running
+/// out early is an error.
+struct Exact<R> {
+    inner: R,
+    remaining: u64,
+}
+
+impl<R> Exact<R> {
+    fn new(inner: R, remaining: u64) -> Self {
+        Exact { inner, remaining }
+    }
+}
+
+impl<R: Read + Unpin> Read for Exact<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        if self.remaining == 0 || buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        let max = buf
+            .len()
+            .min(self.remaining.min(usize::MAX as u64) as usize);
+        let n = match Pin::new(&mut self.inner).poll_read(cx, &mut buf[..max]) {
+            Poll::Ready(Ok(n)) => n,
+            other => return other,
+        };
+        if n == 0 {
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "file changed while it was being read",
+            )));
+        }
+        self.remaining -= n as u64;
+        Poll::Ready(Ok(n))
+    }
+}
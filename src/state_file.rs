@@ -0,0 +1,49 @@
+//! Persists the registered projects across restarts with `--state-file`.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use async_std::{fs, prelude::*};
+
+use crate::LanguageDirectory;
+
+#[derive(Debug)]
+pub struct StateFile {
+    path: PathBuf,
+}
+
+impl StateFile {
+    pub fn new(path: PathBuf) -> Self {
+        StateFile { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns `None` when no state has been saved yet.
+    pub async fn load(&self) -> io::Result<Option<LanguageDirectory>> {
+        let contents = match fs::read_to_string(&self.path).await {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        serde_json::from_str(&contents)
+            .map(Some)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Writes to a sibling temporary file and renames it into place, so a
+    /// crash mid-write leaves the previous state intact.
+    pub async fn save(&self, language_directory: &LanguageDirectory) -> io::Result<()> {
+        let contents = serde_json::to_vec_pretty(language_directory)?;
+        let mut temporary_path = self.path.clone().into_os_string();
+        temporary_path.push(".tmp");
+        let mut file = fs::File::create(&temporary_path).await?;
+        file.write_all(&contents).await?;
+        file.sync_all().await?;
+        fs::rename(&temporary_path, &self.path).await
+    }
+}
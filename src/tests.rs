@@ -0,0 +1,174 @@
+//! Unit tests for items private to the crate; the HTTP behaviour is tested
+//! through the public API under `tests/`.
+
+use std::io::Read;
+
+use tide::http::{self, Method, Url};
+
+use super::*;
+
+const HOST: &str = "rust.docs";
+
+/// A request for `path_and_query` on the `rust` language's host.
+fn request(method: Method, path_and_query: &str) -> http::Request {
+    let url = Url::parse(&format!("http://{}{}", HOST, path_and_query)).unwrap();
+    let mut req = http::Request::new(method, url);
+    req.insert_header("host", HOST);
+    req
+}
+
+fn header(response: &http::Response, name: &str) -> Option<String> {
+    response
+        .header(name)
+        .map(|values| values.last().as_str().to_string())
+}
+
+#[test]
+fn strips_ports_but_not_ipv6_addresses() {
+    for (host, stripped) in &[
+        ("rust.docs", "rust.docs"),
+        ("rust.docs:8080", "rust.docs"),
+        ("127.0.0.1:8080", "127.0.0.1"),
+        ("[::1]:8080", "[::1]"),
+        ("[::1]", "[::1]"),
+        ("::1", "::1"),
+    ] {
+        assert_eq!(strip_port(host), *stripped, "{}", host);
+    }
+}
+
+#[async_std::test]
+async fn redirects_stay_on_the_host() {
+    let options = Options::parse(Vec::new(), |_| None).unwrap();
+    let mut app = build_app(ServerState::from_options(options).await.unwrap());
+    app.at("/redirect-to/*location")
+        .get(|req: Request<ServerState>| async move {
+            let location = format!("/{}", req.param("location")?);
+            Ok(redirect(&req, StatusCode::Found, location))
+        });
+
+    for (path, location) in &[
+        ("/redirect-to//evil.example/?q=1", "/evil.example/?q=1"),
+        ("/redirect-to///evil.example/", "/evil.example/"),
+        ("/redirect-to/proj/", "/proj/"),
+    ] {
+        let response: http::Response = app.respond(request(Method::Get, path)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::Found, "{}", path);
+        assert_eq!(
+            header(&response, "location").unwrap(),
+            *location,
+            "{}",
+            path
+        );
+    }
+}
+
+/// `TEXT` as `gzip -9n` compresses it.
+const TEXT: &str = "Docs and pony show, docs and pony show.\n";
+const GZIPPED: &[u8] = &[
+    0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0x73, 0xc9, 0x4f, 0x2e, 0x56, 0x48,
+    0xcc, 0x4b, 0x51, 0x28, 0xc8, 0xcf, 0xab, 0x54, 0x28, 0xce, 0xc8, 0x2f, 0xd7, 0x51, 0x48, 0xc1,
+    0x10, 0xd3, 0xe3, 0x02, 0x00, 0x52, 0x73, 0x1f, 0x86, 0x28, 0x00, 0x00, 0x00,
+];
+
+fn gunzip(mut gzipped: &[u8]) -> String {
+    inflate::skip_gzip_header(&mut gzipped).unwrap();
+    let mut inflated = String::new();
+    inflate::Inflater::new(gzipped)
+        .read_to_string(&mut inflated)
+        .unwrap();
+    inflated
+}
+
+#[test]
+fn inflates_what_system_gzip_wrote() {
+    assert_eq!(gunzip(GZIPPED), TEXT);
+}
+
+#[test]
+fn gzips_with_the_header_and_trailer_system_gzip_writes() {
+    let mut encoder = gzip::GzipEncoder::new();
+    let mut gzipped = encoder.write(TEXT.as_bytes());
+    gzipped.extend(encoder.finish());
+    assert_eq!(&gzipped[..3], &GZIPPED[..3]);
+    // The CRC-32 and length.
+    assert_eq!(&gzipped[gzipped.len() - 8..], &GZIPPED[GZIPPED.len() - 8..]);
+}
+
+#[test]
+fn gzip_round_trips_through_inflate() {
+    let page = "<p>Docs and pony show</p>\n".repeat(4000);
+    let mut encoder = gzip::GzipEncoder::new();
+    let mut gzipped = Vec::new();
+    for chunk in page.as_bytes().chunks(10_000) {
+        gzipped.extend(encoder.write(chunk));
+    }
+    gzipped.extend(encoder.finish());
+    assert!(gzipped.len() < page.len() / 10, "{} bytes", gzipped.len());
+    assert_eq!(gunzip(&gzipped), page);
+}
+
+#[async_std::test]
+async fn answers_504_when_handling_runs_past_the_timeout() {
+    let mut app = tide::new();
+    app.with(RequestTimeout::new(Duration::from_millis(50)));
+    app.at("/slow").get(|_| async {
+        async_std::task::sleep(Duration::from_secs(5)).await;
+        Ok("late")
+    });
+    app.at("/fast").get(|_| async { Ok("on time") });
+
+    let response: http::Response = app.respond(request(Method::Get, "/slow")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::GatewayTimeout);
+    let mut response: http::Response = app.respond(request(Method::Get, "/fast")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::Ok);
+    assert_eq!(response.body_string().await.unwrap(), "on time");
+}
+
+/// A zip of one stored entry, `name`, holding `data` but claiming to be
+/// `claimed_len` bytes once extracted.
+fn stored_zip(name: &str, data: &[u8], claimed_len: u32) -> Vec<u8> {
+    let data_len = data.len() as u32;
+    let mut zip = Vec::new();
+    zip.extend(&0x0403_4b50u32.to_le_bytes());
+    zip.extend(&[0; 14]);
+    zip.extend(&data_len.to_le_bytes());
+    zip.extend(&claimed_len.to_le_bytes());
+    zip.extend(&(name.len() as u16).to_le_bytes());
+    zip.extend(&[0; 2]);
+    zip.extend(name.as_bytes());
+    zip.extend(data);
+
+    let directory_offset = zip.len() as u32;
+    zip.extend(&0x0201_4b50u32.to_le_bytes());
+    zip.extend(&[0; 16]);
+    zip.extend(&data_len.to_le_bytes());
+    zip.extend(&claimed_len.to_le_bytes());
+    zip.extend(&(name.len() as u16).to_le_bytes());
+    zip.extend(&[0; 16]);
+    zip.extend(name.as_bytes());
+    let directory_len = zip.len() as u32 - directory_offset;
+
+    zip.extend(&0x0605_4b50u32.to_le_bytes());
+    zip.extend(&[0; 4]);
+    zip.extend(&[1, 0, 1, 0]);
+    zip.extend(&directory_len.to_le_bytes());
+    zip.extend(&directory_offset.to_le_bytes());
+    zip.extend(&[0; 2]);
+    zip
+}
+
+#[test]
+fn reads_archive_entries_no_longer_than_their_data() {
+    let path = std::env::temp_dir().join(format!("dapsd-unit-{}.zip", std::process::id()));
+    let read = |claimed_len| {
+        std::fs::write(&path, stored_zip("index.html", b"docs", claimed_len)).unwrap();
+        let archive = archive::Archive::open(&path).unwrap();
+        archive.read(archive.entry("index.html").unwrap())
+    };
+
+    assert_eq!(read(4).unwrap(), b"docs");
+    let error = read(u32::MAX).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    let _ = std::fs::remove_file(&path);
+}
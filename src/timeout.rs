@@ -0,0 +1,34 @@
+//! `--request-timeout`, which gives up on serving or registration requests
+//! that take too long, say on a stalled disk, and answers 504 instead.
+//! Only producing the response is timed; a body already being streamed to
+//! a slow client isn't cut off.
+
+use std::time::Duration;
+
+use async_std::future;
+use tide::{log, utils::async_trait, Middleware, Next, Request, Response, Result, StatusCode};
+
+#[derive(Clone, Copy, Debug)]
+pub struct RequestTimeout {
+    duration: Duration,
+}
+
+impl RequestTimeout {
+    pub fn new(duration: Duration) -> Self {
+        RequestTimeout { duration }
+    }
+}
+
+#[async_trait]
+impl<State: Clone + Send + Sync + 'static> Middleware<State> for RequestTimeout {
+    async fn handle(&self, req: Request<State>, next: Next<'_, State>) -> Result {
+        let path = req.url().path().to_string();
+        match future::timeout(self.duration, next.run(req)).await {
+            Ok(response) => Ok(response),
+            Err(_) => {
+                log::warn!("Timed out after {:?} handling {}", self.duration, path);
+                Ok(Response::new(StatusCode::GatewayTimeout))
+            }
+        }
+    }
+}
@@ -0,0 +1,40 @@
+//! The socket file behind `--unix-socket`, which outlives a server that
+//! didn't get to remove it.
+
+use std::{
+    fs, io,
+    os::unix::{fs::FileTypeExt, net::UnixStream},
+    path::Path,
+};
+
+/// Removes a socket left at `path` by a server that is no longer running.
+/// Anything else there, including a socket something still listens on, is
+/// left alone and reported.
+pub fn remove_stale(path: &Path) -> io::Result<()> {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    if !metadata.file_type().is_socket() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "something other than a socket is there",
+        ));
+    }
+    if UnixStream::connect(path).is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            "another server is listening on it",
+        ));
+    }
+    fs::remove_file(path)
+}
+
+pub fn remove(path: &Path) {
+    if let Err(e) = fs::remove_file(path) {
+        if e.kind() != io::ErrorKind::NotFound {
+            tide::log::warn!("Failed to remove {:?}: {}", path, e);
+        }
+    }
+}
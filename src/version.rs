@@ -0,0 +1,115 @@
+//! Ordering of version labels. Semantic versions sort by precedence, and
+//! any other label, such as `nightly`, sorts lexically after them.
+
+use std::cmp::Ordering;
+
+#[derive(Debug, PartialEq, Eq)]
+struct Semver<'a> {
+    major: u64,
+    minor: u64,
+    patch: u64,
+    /// Empty for a release.
+    pre: Vec<&'a str>,
+}
+
+impl<'a> Semver<'a> {
+    /// `MAJOR.MINOR.PATCH`, with an optional `-pre-release` and `+build`.
+    fn parse(label: &'a str) -> Option<Self> {
+        let label = label.split_once('+').map_or(label, |(version, _)| version);
+        let (core, pre) = match label.split_once('-') {
+            Some((core, pre)) => (core, Some(pre)),
+            None => (label, None),
+        };
+        let mut numbers = core.split('.').map(number);
+        let semver = Semver {
+            major: numbers.next()??,
+            minor: numbers.next()??,
+            patch: numbers.next()??,
+            pre: match pre {
+                Some(pre) => pre.split('.').collect(),
+                None => Vec::new(),
+            },
+        };
+        let valid_pre = semver.pre.iter().all(|identifier| {
+            !identifier.is_empty()
+                && identifier
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b == b'-')
+        });
+        Some(semver).filter(|_| numbers.next().is_none() && valid_pre)
+    }
+
+    fn is_release(&self) -> bool {
+        self.pre.is_empty()
+    }
+}
+
+/// Leading zeros aren't allowed, as in semver.
+fn number(digits: &str) -> Option<u64> {
+    let valid = !digits.is_empty()
+        && digits.bytes().all(|b| b.is_ascii_digit())
+        && (digits == "0" || !digits.starts_with('0'));
+    digits.parse().ok().filter(|_| valid)
+}
+
+impl Ord for Semver<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.major, self.minor, self.patch)
+            .cmp(&(other.major, other.minor, other.patch))
+            .then_with(|| match (self.is_release(), other.is_release()) {
+                (true, true) => Ordering::Equal,
+                (true, false) => Ordering::Greater,
+                (false, true) => Ordering::Less,
+                (false, false) => compare_pre(&self.pre, &other.pre),
+            })
+    }
+}
+
+impl PartialOrd for Semver<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Numeric identifiers compare numerically and below alphanumeric ones,
+/// and a shorter list of otherwise equal identifiers is lower.
+fn compare_pre(a: &[&str], b: &[&str]) -> Ordering {
+    for (a, b) in a.iter().zip(b) {
+        let ordering = match (a.parse::<u64>(), b.parse::<u64>()) {
+            (Ok(a), Ok(b)) => a.cmp(&b),
+            (Ok(_), Err(_)) => Ordering::Less,
+            (Err(_), Ok(_)) => Ordering::Greater,
+            (Err(_), Err(_)) => a.cmp(b),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+/// Labels the same but for build metadata are told apart lexically, so
+/// that this is a total order.
+pub fn compare(a: &str, b: &str) -> Ordering {
+    match (Semver::parse(a), Semver::parse(b)) {
+        (Some(a_semver), Some(b_semver)) => a_semver.cmp(&b_semver).then_with(|| a.cmp(b)),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => a.cmp(b),
+    }
+}
+
+/// The highest release among `labels`, or the highest pre-release when
+/// there are no releases. Labels that aren't semantic versions are never
+/// picked.
+pub fn latest<'a>(labels: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    labels
+        .into_iter()
+        .filter_map(|label| Some((Semver::parse(label)?, label)))
+        .max_by(|(a_semver, a), (b_semver, b)| {
+            (a_semver.is_release(), a_semver)
+                .cmp(&(b_semver.is_release(), b_semver))
+                .then_with(|| a.cmp(b))
+        })
+        .map(|(_, label)| label)
+}
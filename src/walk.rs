@@ -0,0 +1,122 @@
+//! Walks of project directories. Symlinks aren't followed, so a link cycle
+//! can't hang a walk and nothing outside the project's root is reached.
+
+use std::{
+    fs, io,
+    path::{Component, Path, PathBuf},
+    time::SystemTime,
+};
+
+use async_std::{fs as async_fs, path::PathBuf as AsyncPathBuf, prelude::*};
+
+/// Summarizes a directory tree cheaply enough to poll: any added, removed,
+/// resized or rewritten file changes it. An archive project's fingerprint
+/// is that of the archive file.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Fingerprint {
+    files: u64,
+    bytes: u64,
+    newest: Option<SystemTime>,
+}
+
+impl Fingerprint {
+    fn add(&mut self, metadata: &async_fs::Metadata) {
+        self.files += 1;
+        self.bytes += metadata.len();
+        if let Ok(modified) = metadata.modified() {
+            self.newest = self.newest.max(Some(modified));
+        }
+    }
+}
+
+pub async fn fingerprint(root: AsyncPathBuf) -> Fingerprint {
+    let mut fingerprint = Fingerprint::default();
+    if let Ok(metadata) = async_fs::metadata(&root).await {
+        if metadata.is_file() {
+            fingerprint.add(&metadata);
+            return fingerprint;
+        }
+    }
+    let mut directories = vec![root];
+    while let Some(directory) = directories.pop() {
+        let mut entries = match async_fs::read_dir(&directory).await {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        while let Some(Ok(entry)) = entries.next().await {
+            let metadata = match async_fs::symlink_metadata(entry.path()).await {
+                Ok(metadata) => metadata,
+                Err(_) => continue,
+            };
+            if metadata.is_dir() {
+                directories.push(entry.path());
+                continue;
+            }
+            fingerprint.add(&metadata);
+        }
+    }
+    fingerprint
+}
+
+#[derive(Debug)]
+pub struct HtmlFile {
+    /// Relative to the walked root, with `/` separators.
+    pub path: String,
+    pub full_path: PathBuf,
+    pub metadata: fs::Metadata,
+}
+
+/// Every regular `.html` or `.htm` file under `root`, sorted by path.
+/// Hidden files and directories are skipped.
+pub fn html_files(root: &Path) -> io::Result<Vec<HtmlFile>> {
+    let mut files = Vec::new();
+    let mut directories = vec![PathBuf::new()];
+    while let Some(relative) = directories.pop() {
+        for entry in fs::read_dir(root.join(&relative))?.filter_map(|entry| entry.ok()) {
+            let name = match entry.file_name().into_string() {
+                Ok(name) => name,
+                Err(_) => continue,
+            };
+            let metadata = match fs::symlink_metadata(entry.path()) {
+                Ok(metadata) => metadata,
+                Err(_) => continue,
+            };
+            let path = relative.join(&name);
+            if is_hidden(&path) {
+                continue;
+            }
+            if metadata.is_dir() {
+                directories.push(path);
+            } else if metadata.is_file() && is_html(&name) {
+                files.push(HtmlFile {
+                    path: path.to_string_lossy().replace('\\', "/"),
+                    full_path: entry.path(),
+                    metadata,
+                });
+            }
+        }
+    }
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(files)
+}
+
+/// Whether a path relative to a project's root has a segment starting with
+/// a dot, like `.git/config`. A leading `.well-known` doesn't count, as
+/// ACME challenges and the like must be reachable.
+pub fn is_hidden(relative: &Path) -> bool {
+    relative
+        .components()
+        .enumerate()
+        .any(|(index, component)| match component {
+            Component::Normal(name) => {
+                let name = name.to_string_lossy();
+                name.starts_with('.') && !(index == 0 && name == ".well-known")
+            }
+            _ => false,
+        })
+}
+
+pub fn is_html(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    name.ends_with(".html") || name.ends_with(".htm")
+}
@@ -0,0 +1,120 @@
+//! Reading a project's files into the file cache as it's registered, so
+//! that the first requests for them don't wait on the disk.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use async_std::{fs, prelude::*};
+use tide::{log, prelude::*};
+
+use crate::{file_cache::FileCache, snapshot::Snapshot, walk};
+
+/// Files to read, relative to the project directory, and optionally every
+/// file of at most `max-size` bytes. Either way only as much as the cache
+/// holds is read.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Warmup {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub paths: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<u64>,
+}
+
+/// Reads the files `warmup` names under `directory`, which must be
+/// canonical. Failures are logged and skipped: the project is served from
+/// disk all the same.
+pub async fn run(
+    warmup: Warmup,
+    directory: PathBuf,
+    show_hidden: bool,
+    file_cache: Arc<FileCache>,
+) {
+    let mut candidates: Vec<PathBuf> = warmup
+        .paths
+        .iter()
+        .map(|path| directory.join(path))
+        .collect();
+    if let Some(max_size) = warmup.max_size {
+        match small_files(&directory, max_size, show_hidden).await {
+            Ok(files) => candidates.extend(files),
+            Err(e) => log::warn!("Failed to list {:?} for warmup: {}", directory, e),
+        }
+    }
+
+    let mut files = 0;
+    let mut bytes = 0;
+    for candidate in candidates {
+        let path = match fs::canonicalize(&candidate).await {
+            Ok(path) if path.starts_with(&directory) => PathBuf::from(path.into_os_string()),
+            Ok(_) => {
+                log::warn!("Not warming {:?}, it is outside the project", candidate);
+                continue;
+            }
+            Err(e) => {
+                log::warn!("Failed to warm {:?}: {}", candidate, e);
+                continue;
+            }
+        };
+        let snapshot = match Snapshot::open(path.as_path().into()).await {
+            Ok(snapshot) if snapshot.metadata().is_file() => snapshot,
+            Ok(_) => continue,
+            Err(e) => {
+                log::warn!("Failed to warm {:?}: {}", path, e);
+                continue;
+            }
+        };
+        let len = snapshot.metadata().len();
+        if !file_cache.accepts(len) {
+            log::debug!("Not warming {:?}, it is too large to cache", path);
+            continue;
+        }
+        // Reading past capacity would only evict what was just read.
+        if bytes + len > file_cache.max_bytes() as u64 {
+            break;
+        }
+        match file_cache.read(&path, snapshot).await {
+            Ok(_) => {
+                files += 1;
+                bytes += len;
+            }
+            Err(e) => log::warn!("Failed to warm {:?}: {}", path, e),
+        }
+    }
+    log::info!(
+        "Warmed the file cache with {} files ({} bytes) from {:?}",
+        files,
+        bytes,
+        directory
+    );
+}
+
+/// Files under `root` of at most `max_size` bytes, smallest first so that
+/// as many as possible fit. Symlinks aren't followed.
+async fn small_files(root: &Path, max_size: u64, show_hidden: bool) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut directories = vec![PathBuf::new()];
+    while let Some(relative) = directories.pop() {
+        let mut entries = fs::read_dir(root.join(&relative)).await?;
+        while let Some(Ok(entry)) = entries.next().await {
+            let path = relative.join(entry.file_name());
+            if !show_hidden && walk::is_hidden(&path) {
+                continue;
+            }
+            let metadata = match fs::symlink_metadata(entry.path()).await {
+                Ok(metadata) => metadata,
+                Err(_) => continue,
+            };
+            if metadata.is_dir() {
+                directories.push(path);
+            } else if metadata.is_file() && metadata.len() <= max_size {
+                files.push((metadata.len(), root.join(path)));
+            }
+        }
+    }
+    files.sort();
+    Ok(files.into_iter().map(|(_, path)| path).collect())
+}
@@ -0,0 +1,41 @@
+mod common;
+
+use common::{get, post_json, TempDir};
+use serde_json::json;
+
+#[async_std::test]
+async fn serves_language_assets_ahead_of_projects() {
+    let assets = TempDir::new();
+    assets.write("logo.svg", "<svg/>");
+    assets.write("themes/dark.css", "body{}");
+    let config = TempDir::new();
+    let config_path = config.write(
+        "config.json",
+        &json!({ "language-assets": { "rust": assets.path() } }).to_string(),
+    );
+    let app = common::app(&["--config", config_path.to_str().unwrap()]).await;
+
+    let mut response = get(&app, "/_assets/logo.svg").await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.content_type().unwrap().essence(), "image/svg+xml");
+    assert_eq!(response.body_string().await.unwrap(), "<svg/>");
+    let mut response = get(&app, "/_assets/themes/dark.css").await;
+    assert_eq!(response.body_string().await.unwrap(), "body{}");
+    assert_eq!(get(&app, "/_assets/missing.png").await.status(), 404);
+    assert_eq!(get(&app, "/_assets/..%2fconfig.json").await.status(), 400);
+}
+
+#[async_std::test]
+async fn keeps_the_assets_name_from_projects() {
+    let project = TempDir::new();
+    project.write("logo.svg", "the project's own");
+    let app = common::app(&[]).await;
+    let body = json!({
+        "language": "rust",
+        "project-name": "_assets",
+        "directory": project.path(),
+    });
+    let response = post_json(&app, "/api/register/dir", body).await;
+    assert_eq!(response.status(), 400);
+    assert_eq!(get(&app, "/_assets/logo.svg").await.status(), 404);
+}
@@ -0,0 +1,48 @@
+mod common;
+
+use common::{get, register, TempDir};
+use serde_json::json;
+
+#[async_std::test]
+async fn escapes_file_names_in_listings() {
+    let docs = TempDir::new();
+    docs.write("<script>&\".html", "sneaky");
+    docs.write("<i>/page.html", "page");
+    let app = common::app(&[]).await;
+    register(&app, "proj", docs.path(), json!({ "autoindex": true })).await;
+
+    let mut response = get(&app, "/proj/").await;
+    assert_eq!(response.status(), 200);
+    let body = response.body_string().await.unwrap();
+    assert!(
+        body.contains(
+            "<a href=\"/proj/%3Cscript%3E%26%22.html\">&lt;script&gt;&amp;&quot;.html</a>"
+        ),
+        "{}",
+        body
+    );
+    assert!(
+        body.contains("<a href=\"/proj/%3Ci%3E/\">&lt;i&gt;/</a>"),
+        "{}",
+        body
+    );
+    assert!(
+        !body.contains("<script>") && !body.contains("<i>"),
+        "{}",
+        body
+    );
+
+    let mut response = get(&app, "/proj/%3Ci%3E/").await;
+    assert_eq!(response.status(), 200);
+    let body = response.body_string().await.unwrap();
+    assert!(
+        body.contains("<title>Index of /proj/&lt;i&gt;/</title>"),
+        "{}",
+        body
+    );
+    assert!(
+        body.contains("<a href=\"/proj/%3Ci%3E/page.html\">"),
+        "{}",
+        body
+    );
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use common::{get, header, post_json, TempDir};
+use serde_json::json;
+use tide::Server;
+
+use doc_and_pony_show::ServerState;
+
+async fn register_under_docs(app: &Server<ServerState>, directory: &TempDir) {
+    let response = post_json(
+        app,
+        "/docs/api/register/dir",
+        json!({
+            "language": "rust",
+            "project-name": "proj",
+            "directory": directory.path(),
+            "aliases": ["old"],
+        }),
+    )
+    .await;
+    assert!(response.status().is_success());
+}
+
+#[async_std::test]
+async fn serves_everything_under_the_base_path() {
+    let docs = TempDir::new();
+    docs.write("index.html", "home");
+    docs.write("guide/index.html", "guide");
+    let app = common::app(&["--base-path", "docs/", "--redirect-aliases"]).await;
+    register_under_docs(&app, &docs).await;
+
+    let mut response = get(&app, "/docs/proj/guide/").await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.body_string().await.unwrap(), "guide");
+    assert_eq!(get(&app, "/proj/guide/").await.status(), 404);
+    assert_eq!(get(&app, "/api/projects").await.status(), 404);
+    assert_eq!(get(&app, "/docs/healthz").await.status(), 200);
+
+    let response = get(&app, "/docs/proj/guide").await;
+    assert_eq!(header(&response, "location").unwrap(), "/docs/proj/guide/");
+    let response = get(&app, "/docs/old/guide/").await;
+    assert_eq!(header(&response, "location").unwrap(), "/docs/proj/guide/");
+
+    let mut listing = get(&app, "/docs").await;
+    let listing = listing.body_string().await.unwrap();
+    assert!(listing.contains("href=\"/docs/proj/\""));
+
+    let mut sitemap = get(&app, "/docs/proj/sitemap.xml").await;
+    let sitemap = sitemap.body_string().await.unwrap();
+    assert!(sitemap.contains("<loc>http://rust.docs/docs/proj/guide/</loc>"));
+}
+
+#[async_std::test]
+async fn adds_the_base_path_to_canonical_urls() {
+    let docs = TempDir::new();
+    docs.write("guide/index.html", "guide");
+    let app = common::app(&[
+        "--base-path",
+        "/docs",
+        "--canonical-base",
+        "https://example.com",
+        "--language-in-path",
+        "--redirect-aliases",
+    ])
+    .await;
+    register_under_docs(&app, &docs).await;
+
+    let response = get(&app, "/docs/rust/proj/guide/").await;
+    assert_eq!(
+        header(&response, "link").unwrap(),
+        "<https://example.com/docs/rust/proj/guide/>; rel=\"canonical\""
+    );
+    let response = get(&app, "/docs/rust/old/guide/").await;
+    assert_eq!(
+        header(&response, "location").unwrap(),
+        "https://example.com/docs/rust/proj/guide/"
+    );
+    assert_eq!(get(&app, "/rust/proj/guide/").await.status(), 404);
+}
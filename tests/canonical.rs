@@ -0,0 +1,156 @@
+mod common;
+
+use common::{get, header, register, request, send, TempDir};
+use serde_json::json;
+use tide::http::Method;
+
+#[async_std::test]
+async fn builds_absolute_urls_on_the_canonical_base() {
+    let docs = TempDir::new();
+    docs.write("index.html", "home");
+    docs.write("guide/index.html", "guide");
+    docs.write("style.css", "body{}");
+    let app = common::app(&["--canonical-base", "https://docs.example.com:8443/"]).await;
+    register(&app, "proj", docs.path(), json!({ "aliases": ["old"] })).await;
+
+    let response = get(&app, "/proj/guide").await;
+    assert_eq!(
+        header(&response, "location").unwrap(),
+        "https://rust.docs.example.com:8443/proj/guide/"
+    );
+
+    let response = get(&app, "/old/guide/").await;
+    assert_eq!(
+        header(&response, "link").unwrap(),
+        "<https://rust.docs.example.com:8443/proj/guide/>; rel=\"canonical\""
+    );
+    assert_eq!(header(&get(&app, "/proj/style.css").await, "link"), None);
+
+    let mut sitemap = get(&app, "/proj/sitemap.xml").await;
+    let sitemap = sitemap.body_string().await.unwrap();
+    assert!(sitemap.contains("<loc>https://rust.docs.example.com:8443/proj/guide/</loc>"));
+}
+
+#[async_std::test]
+async fn keeps_the_language_in_the_path_on_the_canonical_base() {
+    let docs = TempDir::new();
+    docs.write("index.html", "home");
+    let app = common::app(&[
+        "--canonical-base",
+        "https://docs.example.com",
+        "--language-in-path",
+    ])
+    .await;
+    register(&app, "proj", docs.path(), json!({ "version": "1.0" })).await;
+
+    let response = get(&app, "/rust/proj/1.0/").await;
+    assert_eq!(
+        header(&response, "link").unwrap(),
+        "<https://docs.example.com/rust/proj/1.0/>; rel=\"canonical\""
+    );
+    let response = get(&app, "/rust/proj/1.0").await;
+    assert_eq!(
+        header(&response, "location").unwrap(),
+        "https://docs.example.com/rust/proj/1.0/"
+    );
+}
+
+#[async_std::test]
+async fn uses_the_request_without_a_canonical_base() {
+    let docs = TempDir::new();
+    docs.write("guide/index.html", "guide");
+    let app = common::app(&[]).await;
+    register(&app, "proj", docs.path(), json!({})).await;
+
+    let response = get(&app, "/proj/guide").await;
+    assert_eq!(header(&response, "location").unwrap(), "/proj/guide/");
+    assert_eq!(header(&get(&app, "/proj/guide/").await, "link"), None);
+    let mut sitemap = get(&app, "/proj/sitemap.xml").await;
+    let sitemap = sitemap.body_string().await.unwrap();
+    assert!(sitemap.contains("<loc>http://rust.docs/proj/guide/</loc>"));
+}
+
+#[async_std::test]
+async fn points_older_versions_at_latest() {
+    let old = TempDir::new();
+    old.write("index.html", "old home");
+    old.write("removed.html", "removed since");
+    old.write("style.css", "body{}");
+    let new = TempDir::new();
+    new.write("index.html", "new home");
+    let app = common::app(&[]).await;
+    let extra = json!({ "version": "1.0.0", "canonical-latest": true });
+    register(&app, "proj", old.path(), extra).await;
+    register(&app, "proj", new.path(), json!({ "version": "2.0.0" })).await;
+
+    let response = get(&app, "/proj/1.0.0/").await;
+    assert_eq!(
+        header(&response, "link").unwrap(),
+        "<http://rust.docs/proj/latest/>; rel=\"canonical\""
+    );
+    assert_eq!(
+        header(&get(&app, "/proj/1.0.0/removed.html").await, "link"),
+        None
+    );
+    assert_eq!(
+        header(&get(&app, "/proj/1.0.0/style.css").await, "link"),
+        None
+    );
+    assert_eq!(header(&get(&app, "/proj/latest/").await, "link"), None);
+
+    let app = common::app(&["--canonical-base", "https://docs.example.com"]).await;
+    let extra = json!({ "version": "1.0.0", "canonical-latest": true });
+    register(&app, "proj", old.path(), extra).await;
+    register(&app, "proj", new.path(), json!({ "version": "2.0.0" })).await;
+    let response = get(&app, "/proj/1.0.0/").await;
+    assert_eq!(
+        header(&response, "link").unwrap(),
+        "<https://rust.docs.example.com/proj/latest/>; rel=\"canonical\""
+    );
+    let response = get(&app, "/proj/1.0.0/removed.html").await;
+    assert_eq!(
+        header(&response, "link").unwrap(),
+        "<https://rust.docs.example.com/proj/1.0.0/removed.html>; rel=\"canonical\""
+    );
+}
+
+#[async_std::test]
+async fn takes_the_scheme_from_trusted_proxies() {
+    let docs = TempDir::new();
+    docs.write("guide/index.html", "guide");
+    let app = common::app(&[
+        "--trust-proxy",
+        "10.0.0.1",
+        "--host-suffix",
+        ".docs.example.com",
+    ])
+    .await;
+    register(&app, "proj", docs.path(), json!({})).await;
+
+    let proxied = |peer: &str| {
+        let mut req = request(Method::Get, "/proj/guide");
+        req.set_peer_addr(Some(peer));
+        req.insert_header("x-forwarded-proto", "https");
+        req.insert_header("host", "rust.docs.example.com");
+        req
+    };
+    let response = send(&app, proxied("10.0.0.1:4000")).await;
+    assert_eq!(
+        header(&response, "location").unwrap(),
+        "https://rust.docs.example.com/proj/guide/"
+    );
+    let response = send(&app, proxied("10.0.0.2:4000")).await;
+    assert_eq!(header(&response, "location").unwrap(), "/proj/guide/");
+
+    for (proto, scheme) in &[("http, https", "https"), ("https, http", "http")] {
+        let mut req = proxied("10.0.0.1:4000");
+        req.insert_header("x-forwarded-proto", *proto);
+        let response = send(&app, req).await;
+        assert_eq!(
+            header(&response, "location").unwrap(),
+            format!("{}://rust.docs.example.com/proj/guide/", scheme),
+            "{}",
+            proto
+        );
+    }
+}
@@ -0,0 +1,114 @@
+mod common;
+
+use common::TempDir;
+use doc_and_pony_show::check_config;
+use serde_json::json;
+
+#[async_std::test]
+async fn lists_every_problem_startup_would_refuse() {
+    let docs = TempDir::new();
+    let config = TempDir::new();
+    let config_path = config.write(
+        "config.json",
+        &json!({
+            "missing-directory": "abort",
+            "projects": [
+                { "language": "rust", "project-name": "Bad", "directory": docs.path() },
+                { "language": "rust", "project-name": "gone", "directory": docs.path().join("gone") },
+                {
+                    "language": "rust",
+                    "project-name": "moved",
+                    "directory": docs.path(),
+                    "redirects": [{ "from": "old.html", "to": "/new.html" }],
+                },
+                { "language": "rust", "project-name": "fine", "directory": docs.path() },
+            ],
+        })
+        .to_string(),
+    );
+
+    let check = check_config(&config_path, false).await.unwrap();
+    assert_eq!(check.errors.len(), 3, "{:?}", check.errors);
+    assert!(check.errors[0].starts_with("Bad in language rust"));
+    assert!(check.errors[1].starts_with("gone in language rust has invalid directory"));
+    assert!(check.errors[2].starts_with("moved in language rust"));
+    assert_eq!(check.projects, 1);
+}
+
+#[async_std::test]
+async fn reports_directories_it_would_skip_without_failing() {
+    let docs = TempDir::new();
+    let config = TempDir::new();
+    let config_path = config.write(
+        "config.json",
+        &json!({
+            "projects": [
+                { "language": "rust", "project-name": "gone", "directory": docs.path().join("gone") },
+                { "language": "rust", "project-name": "fine", "directory": docs.path() },
+            ],
+        })
+        .to_string(),
+    );
+
+    let check = check_config(&config_path, false).await.unwrap();
+    assert!(check.errors.is_empty(), "{:?}", check.errors);
+    assert_eq!(check.skipped.len(), 1);
+    assert_eq!(check.projects, 1);
+
+    let unparsable = config.write("broken.json", "{ \"projects\": ");
+    assert!(check_config(&unparsable, false).await.is_err());
+}
+
+#[async_std::test]
+async fn normalizes_configured_languages_with_language_codes() {
+    let docs = TempDir::new();
+    let config = TempDir::new();
+    let config_path = config.write(
+        "config.json",
+        &json!({
+            "projects": [{ "language": "EN", "project-name": "fine", "directory": docs.path() }],
+        })
+        .to_string(),
+    );
+    assert_eq!(
+        check_config(&config_path, false)
+            .await
+            .unwrap()
+            .errors
+            .len(),
+        1
+    );
+    let check = check_config(&config_path, true).await.unwrap();
+    assert!(check.errors.is_empty(), "{:?}", check.errors);
+    assert_eq!(check.projects, 1);
+
+    let invalid = config.write(
+        "invalid.json",
+        &json!({ "default-projects": { "english": "fine" } }).to_string(),
+    );
+    let check = check_config(&invalid, true).await.unwrap();
+    assert_eq!(check.errors.len(), 1, "{:?}", check.errors);
+    assert!(check.errors[0].starts_with("invalid language code `english`"));
+}
+
+#[async_std::test]
+async fn rejects_languages_that_normalize_alike() {
+    let config = TempDir::new();
+    for (name, config_json, message) in &[
+        (
+            "fallbacks.json",
+            json!({ "language-fallbacks": { "EN": ["fr"], "en": ["de"] } }),
+            "languages `EN` and `en` are both the language code `en`",
+        ),
+        (
+            "defaults.json",
+            json!({ "default-projects": { "pt-BR": "a", "pt_br": "b" } }),
+            "languages `pt-BR` and `pt_br` are both the language code `pt-br`",
+        ),
+    ] {
+        let config_path = config.write(name, &config_json.to_string());
+        let check = check_config(&config_path, true).await.unwrap();
+        assert_eq!(check.errors.len(), 1, "{:?}", check.errors);
+        assert!(check.errors[0].contains(message), "{:?}", check.errors);
+    }
+}
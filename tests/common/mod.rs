@@ -0,0 +1,111 @@
+//! Boots dapsd in-process against temporary directories, without binding a
+//! port, and sends it requests through `Server::respond`.
+
+#![allow(dead_code)]
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use doc_and_pony_show::{build_app, Options, ServerState};
+use tide::{
+    http::{headers::HeaderName, Method, Request, Response, Url},
+    Server,
+};
+
+pub const HOST: &str = "rust.docs";
+
+/// A directory under the system's temporary directory, removed on drop.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new() -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let path = env::temp_dir().join(format!(
+            "dapsd-test-{}-{}",
+            process::id(),
+            NEXT.fetch_add(1, Ordering::SeqCst)
+        ));
+        fs::create_dir_all(&path).unwrap();
+        TempDir(fs::canonicalize(path).unwrap())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// Writes `contents` to `relative`, creating directories as needed.
+    pub fn write(&self, relative: &str, contents: &str) -> PathBuf {
+        let path = self.0.join(relative);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, contents).unwrap();
+        path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// The app as dapsd would run it with `args`, ignoring the environment.
+pub async fn app(args: &[&str]) -> Server<ServerState> {
+    let options = Options::parse(args.iter().map(|arg| arg.to_string()), |_| None).unwrap();
+    build_app(ServerState::from_options(options).await.unwrap())
+}
+
+/// A request for `path_and_query` on the `rust` language's host.
+pub fn request(method: Method, path_and_query: &str) -> Request {
+    let url = Url::parse(&format!("http://{}{}", HOST, path_and_query)).unwrap();
+    let mut req = Request::new(method, url);
+    req.insert_header("host", HOST);
+    req
+}
+
+pub async fn send(app: &Server<ServerState>, req: Request) -> Response {
+    app.respond(req).await.unwrap()
+}
+
+pub async fn get(app: &Server<ServerState>, path_and_query: &str) -> Response {
+    send(app, request(Method::Get, path_and_query)).await
+}
+
+pub async fn post_json(app: &Server<ServerState>, path: &str, json: serde_json::Value) -> Response {
+    let mut req = request(Method::Post, path);
+    req.set_body(json);
+    send(app, req).await
+}
+
+/// Registers `directory` as `project_name` in the `rust` language, with any
+/// further registration fields in `extra`, and checks that it worked.
+pub async fn register(
+    app: &Server<ServerState>,
+    project_name: &str,
+    directory: &Path,
+    extra: serde_json::Value,
+) {
+    let mut body = serde_json::json!({
+        "language": "rust",
+        "project-name": project_name,
+        "directory": directory,
+    });
+    if let serde_json::Value::Object(extra) = extra {
+        body.as_object_mut().unwrap().extend(extra);
+    }
+    let mut response = post_json(app, "/api/register/dir", body).await;
+    assert!(
+        response.status().is_success(),
+        "registration failed: {}",
+        response.body_string().await.unwrap()
+    );
+}
+
+pub fn header(response: &Response, name: &str) -> Option<String> {
+    response
+        .header(HeaderName::from(name))
+        .map(|values| values.last().as_str().to_string())
+}
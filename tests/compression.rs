@@ -0,0 +1,108 @@
+mod common;
+
+use common::{header, register, request, send, TempDir};
+use serde_json::json;
+use tide::http::{Method, Response};
+use tide::Server;
+
+use doc_and_pony_show::ServerState;
+
+async fn get_gzip(app: &Server<ServerState>, path: &str) -> Response {
+    send_gzip(app, Method::Get, path).await
+}
+
+async fn send_gzip(app: &Server<ServerState>, method: Method, path: &str) -> Response {
+    let mut req = request(method, path);
+    req.insert_header("accept-encoding", "gzip");
+    send(app, req).await
+}
+
+#[async_std::test]
+async fn streams_large_text_compressed() {
+    let docs = TempDir::new();
+    let page = "<p>Docs and pony show</p>\n".repeat(4000);
+    docs.write("page.html", &page);
+    docs.write("module.mjs", &page);
+    docs.write("bundle.js.map", &page);
+    let app = common::app(&[]).await;
+    register(&app, "proj", docs.path(), json!({})).await;
+
+    for path in &["/proj/page.html", "/proj/module.mjs", "/proj/bundle.js.map"] {
+        let mut response = get_gzip(&app, path).await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            header(&response, "content-encoding").as_deref(),
+            Some("gzip")
+        );
+        assert_eq!(header(&response, "content-length"), None);
+        let body = response.body_bytes().await.unwrap();
+        assert_eq!(&body[..2], &[0x1f, 0x8b]);
+        assert!(body.len() < page.len() / 10, "{} bytes", body.len());
+    }
+}
+
+#[async_std::test]
+async fn head_is_compressed_like_get() {
+    let docs = TempDir::new();
+    docs.write("page.html", &"<p>Docs and pony show</p>\n".repeat(100));
+    let app = common::app(&[]).await;
+    register(&app, "proj", docs.path(), json!({})).await;
+
+    let got = get_gzip(&app, "/proj/page.html").await;
+    let mut head = send_gzip(&app, Method::Head, "/proj/page.html").await;
+    assert_eq!(head.status(), 200);
+    for name in &["content-encoding", "content-length", "content-type", "vary"] {
+        assert_eq!(header(&head, name), header(&got, name), "{}", name);
+    }
+    assert_eq!(header(&head, "content-encoding").as_deref(), Some("gzip"));
+    // Without a length the encoder sends no Content-Length either.
+    assert_eq!(head.len(), None);
+    assert_eq!(head.body_bytes().await.unwrap(), b"");
+}
+
+#[async_std::test]
+async fn leaves_small_files_and_images_alone() {
+    let docs = TempDir::new();
+    docs.write("small.html", &"x".repeat(50));
+    docs.write("image.png", &"\u{1}PNG".repeat(2000));
+    let app = common::app(&[]).await;
+    register(&app, "proj", docs.path(), json!({})).await;
+
+    for (path, len) in &[("/proj/small.html", 50), ("/proj/image.png", 8000)] {
+        let mut response = get_gzip(&app, path).await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(header(&response, "content-encoding"), None, "{}", path);
+        assert_eq!(response.body_bytes().await.unwrap().len(), *len, "{}", path);
+    }
+}
+
+#[async_std::test]
+async fn serves_ranges_uncompressed_and_compressed_bodies_without_ranges() {
+    let docs = TempDir::new();
+    let page = "<p>Docs and pony show</p>\n".repeat(4000);
+    docs.write("page.html", &page);
+    let app = common::app(&[]).await;
+    register(&app, "proj", docs.path(), json!({})).await;
+
+    let mut req = request(Method::Get, "/proj/page.html");
+    req.insert_header("accept-encoding", "gzip");
+    req.insert_header("range", "bytes=3-9");
+    let mut response = send(&app, req).await;
+    assert_eq!(response.status(), 206);
+    assert_eq!(header(&response, "content-encoding"), None);
+    assert_eq!(
+        header(&response, "content-range").as_deref(),
+        Some(format!("bytes 3-9/{}", page.len()).as_str())
+    );
+    assert_eq!(response.body_string().await.unwrap(), &page[3..10]);
+
+    let response = get_gzip(&app, "/proj/page.html").await;
+    assert_eq!(
+        header(&response, "content-encoding").as_deref(),
+        Some("gzip")
+    );
+    assert_eq!(header(&response, "accept-ranges"), None);
+
+    let response = send(&app, request(Method::Get, "/proj/page.html")).await;
+    assert_eq!(header(&response, "accept-ranges").as_deref(), Some("bytes"));
+}
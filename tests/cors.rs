@@ -0,0 +1,60 @@
+mod common;
+
+use common::{header, register, request, send, TempDir};
+use serde_json::json;
+use tide::http::{Method, Response};
+
+#[async_std::test]
+async fn shares_responses_only_with_allowed_origins() {
+    let docs = TempDir::new();
+    docs.write("index.html", "docs");
+    let config = TempDir::new();
+    let config_path = config.write(
+        "config.json",
+        &json!({ "cors": { "allowed-origins": ["https://good.example"] } }).to_string(),
+    );
+    let app = common::app(&["--config", config_path.to_str().unwrap()]).await;
+    register(&app, "proj", docs.path(), json!({})).await;
+
+    let from = |origin: &str, method: Method| {
+        let mut req = request(method, "/proj/");
+        req.insert_header("origin", origin);
+        if method == Method::Options {
+            req.insert_header("access-control-request-method", "GET");
+        }
+        req
+    };
+    let allowed_origin = |response: &Response| header(response, "access-control-allow-origin");
+
+    let response = send(&app, from("https://good.example", Method::Get)).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        allowed_origin(&response).as_deref(),
+        Some("https://good.example")
+    );
+    assert!(header(&response, "vary").unwrap().contains("origin"));
+
+    let response = send(&app, from("https://evil.example", Method::Get)).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(allowed_origin(&response), None);
+    assert!(header(&response, "vary").unwrap().contains("origin"));
+
+    let preflight = send(&app, from("https://good.example", Method::Options)).await;
+    assert_eq!(preflight.status(), 204);
+    assert_eq!(
+        allowed_origin(&preflight).as_deref(),
+        Some("https://good.example")
+    );
+    assert_eq!(
+        header(&preflight, "access-control-allow-methods").as_deref(),
+        Some("GET, HEAD")
+    );
+    assert_eq!(
+        header(&preflight, "access-control-max-age").as_deref(),
+        Some("86400")
+    );
+
+    let preflight = send(&app, from("https://evil.example", Method::Options)).await;
+    assert_eq!(allowed_origin(&preflight), None);
+    assert_eq!(header(&preflight, "access-control-allow-methods"), None);
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use std::path::Path;
+
+use common::{header, post_json, request, send, TempDir};
+use serde_json::json;
+use tide::{http::Method, Server};
+
+use doc_and_pony_show::{check_config, Options, ServerState};
+
+async fn register_in(app: &Server<ServerState>, language: &str, directory: &Path) {
+    let body = json!({
+        "language": language,
+        "project-name": "proj",
+        "directory": directory,
+    });
+    let response = post_json(app, "/api/register/dir", body).await;
+    assert!(response.status().is_success());
+}
+
+async fn get_in(app: &Server<ServerState>, language: &str, path: &str) -> (u16, String) {
+    let mut req = request(Method::Get, path);
+    req.insert_header("host", format!("{}.docs", language));
+    let mut response = send(app, req).await;
+    assert_eq!(
+        header(&response, "vary").as_deref(),
+        Some("accept-language"),
+        "{}",
+        path
+    );
+    let status = response.status() as u16;
+    (status, response.body_string().await.unwrap())
+}
+
+#[async_std::test]
+async fn follows_each_languages_fallbacks_in_turn() {
+    let (pt_br, pt, en) = (TempDir::new(), TempDir::new(), TempDir::new());
+    pt.write("guia.html", "pt");
+    en.write("guia.html", "en");
+    en.write("api.html", "en api");
+    let config = TempDir::new();
+    let config_path = config.write(
+        "config.json",
+        &json!({ "language-fallbacks": { "pt-br": ["pt"], "pt": ["en"] } }).to_string(),
+    );
+    let app = common::app(&["--config", config_path.to_str().unwrap()]).await;
+    for (language, directory) in &[("pt-br", &pt_br), ("pt", &pt), ("en", &en)] {
+        register_in(&app, language, directory.path()).await;
+    }
+
+    assert_eq!(
+        get_in(&app, "pt-br", "/proj/guia.html").await,
+        (200, "pt".into())
+    );
+    assert_eq!(
+        get_in(&app, "pt-br", "/proj/api.html").await,
+        (200, "en api".into())
+    );
+    assert_eq!(
+        get_in(&app, "pt", "/proj/api.html").await,
+        (200, "en api".into())
+    );
+    assert_eq!(get_in(&app, "pt-br", "/proj/missing.html").await.0, 404);
+}
+
+#[async_std::test]
+async fn rejects_fallback_cycles() {
+    let config = TempDir::new();
+    let config_path = config.write(
+        "config.json",
+        &json!({ "language-fallbacks": { "pt-br": ["pt"], "pt": ["en"], "en": ["pt-br"] } })
+            .to_string(),
+    );
+    let check = check_config(&config_path, false).await.unwrap();
+    assert_eq!(
+        check.errors,
+        ["language-fallbacks: languages fall back to themselves: en -> pt-br -> pt -> en"]
+    );
+    let options = Options::parse(
+        vec![
+            "--config".to_string(),
+            config_path.to_str().unwrap().to_string(),
+        ],
+        |_| None,
+    )
+    .unwrap();
+    assert!(ServerState::from_options(options).await.is_err());
+}
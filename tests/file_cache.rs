@@ -0,0 +1,47 @@
+mod common;
+
+use common::{get, post_json, register, TempDir};
+use serde_json::{json, Value};
+use tide::Server;
+
+use doc_and_pony_show::ServerState;
+
+async fn flush(app: &Server<ServerState>, query: &str) -> (u16, Value) {
+    let mut response = post_json(app, &format!("/api/cache/flush{}", query), json!({})).await;
+    let status = response.status() as u16;
+    (status, response.body_json().await.unwrap())
+}
+
+#[async_std::test]
+async fn flushes_all_files_or_a_projects() {
+    let (one, two) = (TempDir::new(), TempDir::new());
+    for docs in &[&one, &two] {
+        docs.write("a.html", "a");
+        docs.write("b.html", "b");
+    }
+    let app = common::app(&["--file-cache-size", "65536"]).await;
+    register(&app, "one", one.path(), json!({})).await;
+    register(&app, "two", two.path(), json!({})).await;
+    for path in &["/one/a.html", "/one/b.html", "/two/a.html"] {
+        assert_eq!(get(&app, path).await.status(), 200);
+    }
+
+    assert_eq!(
+        flush(&app, "?project=two").await,
+        (200, json!({ "evicted": 1 }))
+    );
+    assert_eq!(
+        flush(&app, "?project=two").await,
+        (200, json!({ "evicted": 0 }))
+    );
+    assert_eq!(flush(&app, "?project=one&language=go").await.0, 404);
+    assert_eq!(flush(&app, "?project=missing").await.0, 404);
+    assert_eq!(flush(&app, "").await, (200, json!({ "evicted": 2 })));
+}
+
+#[async_std::test]
+async fn needs_the_register_token() {
+    let app = common::app(&["--register-token", "secret"]).await;
+    let response = post_json(&app, "/api/cache/flush", json!({})).await;
+    assert_eq!(response.status(), 401);
+}
@@ -0,0 +1,98 @@
+mod common;
+
+use common::{get, header, register, request, send, TempDir};
+use serde_json::json;
+use tide::http::Method;
+
+async fn get_accepting(
+    app: &tide::Server<doc_and_pony_show::ServerState>,
+    path: &str,
+    accept: &str,
+) -> tide::http::Response {
+    let mut req = request(Method::Get, path);
+    req.insert_header("accept", accept);
+    send(app, req).await
+}
+
+#[async_std::test]
+async fn serves_json_to_clients_that_prefer_it() {
+    let docs = TempDir::new();
+    docs.write("struct.Foo.html", "<h1>Foo</h1>");
+    docs.write("struct.Foo.json", "{\"name\":\"Foo\"}");
+    docs.write("struct.Bar.html", "<h1>Bar</h1>");
+    let app = common::app(&[]).await;
+    register(&app, "proj", docs.path(), json!({ "json-variants": {} })).await;
+
+    let mut response = get_accepting(&app, "/proj/struct.Foo.html", "application/json").await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        response.content_type().unwrap().essence(),
+        "application/json"
+    );
+    assert_eq!(header(&response, "vary").as_deref(), Some("accept"));
+    assert_eq!(response.body_string().await.unwrap(), "{\"name\":\"Foo\"}");
+
+    for accept in &["text/html,application/json", "application/json;q=0.5, */*"] {
+        let mut response = get_accepting(&app, "/proj/struct.Foo.html", accept).await;
+        assert_eq!(
+            response.body_string().await.unwrap(),
+            "<h1>Foo</h1>",
+            "{}",
+            accept
+        );
+        assert_eq!(header(&response, "vary").as_deref(), Some("accept"));
+    }
+
+    let mut response = get_accepting(&app, "/proj/struct.Bar.html", "application/json").await;
+    assert_eq!(response.body_string().await.unwrap(), "<h1>Bar</h1>");
+    assert_eq!(header(&response, "vary"), None);
+}
+
+#[async_std::test]
+async fn looks_for_json_in_the_configured_directory() {
+    let docs = TempDir::new();
+    docs.write("proj/index.html", "<h1>proj</h1>");
+    docs.write("json/proj/index.js", "{}");
+    let app = common::app(&[]).await;
+    register(
+        &app,
+        "proj",
+        docs.path(),
+        json!({ "json-variants": { "directory": "json", "suffix": ".js" } }),
+    )
+    .await;
+
+    let mut response = get_accepting(&app, "/proj/proj/", "application/json").await;
+    assert_eq!(response.body_string().await.unwrap(), "{}");
+}
+
+#[async_std::test]
+async fn ignores_json_variants_unless_configured() {
+    let docs = TempDir::new();
+    docs.write("struct.Foo.html", "<h1>Foo</h1>");
+    docs.write("struct.Foo.json", "{}");
+    let app = common::app(&[]).await;
+    register(&app, "proj", docs.path(), json!({})).await;
+
+    let mut response = get_accepting(&app, "/proj/struct.Foo.html", "application/json").await;
+    assert_eq!(response.body_string().await.unwrap(), "<h1>Foo</h1>");
+    assert_eq!(get(&app, "/proj/struct.Foo.json").await.status(), 200);
+}
+
+#[async_std::test]
+async fn rejects_json_variant_directories_outside_the_project() {
+    let docs = TempDir::new();
+    let app = common::app(&[]).await;
+    let response = common::post_json(
+        &app,
+        "/api/register/dir",
+        json!({
+            "language": "rust",
+            "project-name": "proj",
+            "directory": docs.path(),
+            "json-variants": { "directory": "../json" },
+        }),
+    )
+    .await;
+    assert_eq!(response.status(), 400);
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use common::{get, post_json, request, send, TempDir};
+use serde_json::{json, Value};
+use tide::{http::Method, Server};
+
+use doc_and_pony_show::ServerState;
+
+async fn register_in(app: &Server<ServerState>, language: &str, directory: &TempDir) -> u16 {
+    let body = json!({
+        "language": language,
+        "project-name": "proj",
+        "directory": directory.path(),
+    });
+    post_json(app, "/api/register/dir", body).await.status() as u16
+}
+
+async fn status_on(app: &Server<ServerState>, host: &str) -> u16 {
+    let mut req = request(Method::Get, "/proj/");
+    req.insert_header("host", host);
+    send(app, req).await.status() as u16
+}
+
+#[async_std::test]
+async fn folds_the_case_of_hosts() {
+    let docs = TempDir::new();
+    docs.write("index.html", "docs");
+    let app = common::app(&[]).await;
+    assert_eq!(register_in(&app, "en", &docs).await, 201);
+    assert_eq!(register_in(&app, "EN", &docs).await, 400);
+
+    assert_eq!(status_on(&app, "EN.docs").await, 200);
+    assert_eq!(status_on(&app, "en.DOCS:8080").await, 200);
+}
+
+#[async_std::test]
+async fn normalizes_language_codes_when_asked() {
+    let docs = TempDir::new();
+    docs.write("index.html", "docs");
+    let app = common::app(&["--language-codes"]).await;
+    assert_eq!(register_in(&app, "EN", &docs).await, 201);
+    assert_eq!(register_in(&app, "pt_BR", &docs).await, 201);
+    assert_eq!(register_in(&app, "english", &docs).await, 400);
+    assert_eq!(register_in(&app, "en-thisistoolong", &docs).await, 400);
+
+    assert_eq!(status_on(&app, "en.docs").await, 200);
+    assert_eq!(status_on(&app, "pt-br.docs").await, 200);
+    assert_eq!(status_on(&app, "english.docs").await, 400);
+}
+
+#[async_std::test]
+async fn unregisters_by_language_code() {
+    let docs = TempDir::new();
+    docs.write("index.html", "docs");
+    let app = common::app(&["--language-codes"]).await;
+    assert_eq!(register_in(&app, "en", &docs).await, 201);
+
+    let mut req = request(Method::Delete, "/api/register/dir");
+    req.set_body(json!({ "language": "EN", "project-name": "proj" }));
+    assert_eq!(send(&app, req).await.status(), 200);
+    assert_eq!(status_on(&app, "en.docs").await, 404);
+}
+
+#[async_std::test]
+async fn takes_listed_and_accepted_languages_as_language_codes() {
+    let (en, pt_br) = (TempDir::new(), TempDir::new());
+    en.write("index.html", "en");
+    pt_br.write("guia.html", "pt");
+    let app = common::app(&["--language-codes"]).await;
+    assert_eq!(register_in(&app, "en", &en).await, 201);
+    assert_eq!(register_in(&app, "pt-br", &pt_br).await, 201);
+
+    let mut response = get(&app, "/api/projects?language=PT_br").await;
+    assert_eq!(response.status(), 200);
+    let listing: Value = response.body_json().await.unwrap();
+    assert_eq!(listing[0]["language"], "pt-br");
+    assert_eq!(
+        get(&app, "/api/projects?language=english").await.status(),
+        400
+    );
+
+    let mut req = request(Method::Get, "/proj/guia.html");
+    req.insert_header("host", "en.docs");
+    req.insert_header("accept-language", "PT_BR, english;q=0.5");
+    let mut response = send(&app, req).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.body_string().await.unwrap(), "pt");
+}
@@ -0,0 +1,57 @@
+mod common;
+
+use common::{header, register, request, send, TempDir};
+use serde_json::json;
+use tide::http::{Method, Response};
+use tide::Server;
+
+use doc_and_pony_show::ServerState;
+
+async fn get_on_any_host(app: &Server<ServerState>, path: &str) -> Response {
+    let mut req = request(Method::Get, path);
+    req.insert_header("host", "localhost:8080");
+    send(app, req).await
+}
+
+#[async_std::test]
+async fn takes_the_language_from_the_path() {
+    let docs = TempDir::new();
+    docs.write("index.html", "docs");
+    docs.write("guide/index.html", "guide");
+    let app = common::app(&["--language-in-path", "--redirect-aliases"]).await;
+    register(&app, "proj", docs.path(), json!({ "aliases": ["old"] })).await;
+
+    let mut response = get_on_any_host(&app, "/rust/proj/").await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.body_string().await.unwrap(), "docs");
+    assert_eq!(get_on_any_host(&app, "/go/proj/").await.status(), 404);
+    assert_eq!(get_on_any_host(&app, "/Rust/proj/").await.status(), 400);
+    assert_eq!(get_on_any_host(&app, "/healthz").await.status(), 200);
+
+    let response = get_on_any_host(&app, "/rust/proj/guide?q=1").await;
+    assert_eq!(
+        header(&response, "location").unwrap(),
+        "/rust/proj/guide/?q=1"
+    );
+    let response = get_on_any_host(&app, "/rust/old/guide/").await;
+    assert_eq!(header(&response, "location").unwrap(), "/rust/proj/guide/");
+
+    let mut listing = get_on_any_host(&app, "/rust/").await;
+    assert_eq!(listing.status(), 200);
+    assert!(listing
+        .body_string()
+        .await
+        .unwrap()
+        .contains("href=\"/rust/proj/\""));
+}
+
+#[async_std::test]
+async fn hosts_are_the_default() {
+    let docs = TempDir::new();
+    docs.write("index.html", "docs");
+    let app = common::app(&[]).await;
+    register(&app, "proj", docs.path(), json!({})).await;
+
+    assert_eq!(common::get(&app, "/proj/").await.status(), 200);
+    assert_eq!(common::get(&app, "/rust/proj/").await.status(), 404);
+}
@@ -0,0 +1,28 @@
+mod common;
+
+use common::{header, register, request, send, TempDir};
+use serde_json::json;
+use tide::http::Method;
+
+#[async_std::test]
+async fn answers_other_methods_with_what_is_allowed() {
+    let docs = TempDir::new();
+    docs.write("index.html", "docs");
+    let app = common::app(&[]).await;
+    register(&app, "proj", docs.path(), json!({})).await;
+
+    for method in &[Method::Delete, Method::Put, Method::Post, Method::Patch] {
+        let response = send(&app, request(*method, "/proj/index.html")).await;
+        assert_eq!(response.status(), 405, "{}", method);
+        assert_eq!(header(&response, "allow").unwrap(), "GET, HEAD, OPTIONS");
+    }
+    let response = send(&app, request(Method::Options, "/proj/index.html")).await;
+    assert_eq!(response.status(), 204);
+    assert_eq!(header(&response, "allow").unwrap(), "GET, HEAD, OPTIONS");
+
+    for method in &[Method::Get, Method::Put] {
+        let response = send(&app, request(*method, "/api/register/dir")).await;
+        assert_eq!(response.status(), 405, "{}", method);
+        assert_eq!(header(&response, "allow").unwrap(), "POST, DELETE, OPTIONS");
+    }
+}
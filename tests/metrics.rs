@@ -0,0 +1,27 @@
+mod common;
+
+use common::{get, register, request, send, TempDir};
+use serde_json::json;
+use tide::http::Method;
+
+#[async_std::test]
+async fn counts_pages_by_registered_project() {
+    let docs = TempDir::new();
+    docs.write("page.html", "page");
+    let app = common::app(&[]).await;
+    register(&app, "proj", docs.path(), json!({ "aliases": ["old"] })).await;
+
+    assert_eq!(get(&app, "/proj/page.html").await.status(), 200);
+    assert_eq!(get(&app, "/old/page.html").await.status(), 200);
+    send(&app, request(Method::Head, "/proj/page.html")).await;
+    assert_eq!(get(&app, "/proj/missing.html").await.status(), 404);
+    assert_eq!(get(&app, "/unregistered/page.html").await.status(), 404);
+
+    let mut response = get(&app, "/metrics").await;
+    let metrics = response.body_string().await.unwrap();
+    let series = "{language=\"rust\",project=\"proj\"}";
+    assert!(metrics.contains(&format!("dapsd_project_requests_total{} 3\n", series)));
+    assert!(metrics.contains(&format!("dapsd_project_response_bytes_total{} 8\n", series)));
+    assert!(!metrics.contains("project=\"old\""));
+    assert!(!metrics.contains("unregistered"));
+}
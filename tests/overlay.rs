@@ -0,0 +1,78 @@
+mod common;
+
+use std::os::unix::fs::symlink;
+
+use common::{get, post_json, register, TempDir};
+use serde_json::{json, Value};
+
+#[async_std::test]
+async fn earlier_directories_shadow_later_ones() {
+    let guide = TempDir::new();
+    let api = TempDir::new();
+    guide.write("index.html", "guide home");
+    guide.write("intro/index.html", "intro");
+    api.write("index.html", "api home");
+    api.write("api/fn.render.html", "render");
+    let app = common::app(&[]).await;
+    let body = json!({
+        "language": "rust",
+        "project-name": "proj",
+        "directory": [guide.path(), api.path()],
+    });
+    assert_eq!(
+        post_json(&app, "/api/register/dir", body).await.status(),
+        201
+    );
+
+    for (path, expected) in &[
+        ("/proj/", "guide home"),
+        ("/proj/intro/", "intro"),
+        ("/proj/api/fn.render.html", "render"),
+    ] {
+        let mut response = get(&app, path).await;
+        assert_eq!(response.status(), 200, "{}", path);
+        assert_eq!(response.body_string().await.unwrap(), *expected, "{}", path);
+    }
+    assert_eq!(get(&app, "/proj/missing.html").await.status(), 404);
+
+    let mut response = get(&app, "/api/projects").await;
+    let projects: Value = response.body_json().await.unwrap();
+    assert_eq!(projects[0]["directory"], json!([guide.path(), api.path()]));
+}
+
+#[async_std::test]
+async fn guards_each_directory() {
+    let root = TempDir::new();
+    root.write("secret.txt", "secret");
+    let guide = root.write("guide/index.html", "guide");
+    let guide = guide.parent().unwrap();
+    let api = root.write("api/index.html", "api");
+    let api = api.parent().unwrap();
+    symlink(root.path().join("secret.txt"), api.join("leak.txt")).unwrap();
+    let app = common::app(&[]).await;
+    register(&app, "proj", guide, json!({ "directory": [guide, api] })).await;
+
+    assert_eq!(get(&app, "/proj/leak.txt").await.status(), 403);
+    assert_eq!(get(&app, "/proj/..%2fsecret.txt").await.status(), 400);
+}
+
+#[async_std::test]
+async fn a_single_directory_is_kept_as_a_path() {
+    let docs = TempDir::new();
+    docs.write("index.html", "docs");
+    let app = common::app(&[]).await;
+    register(&app, "proj", docs.path(), json!({})).await;
+    let body = json!({
+        "language": "rust",
+        "project-name": "empty",
+        "directory": [],
+    });
+    assert_eq!(
+        post_json(&app, "/api/register/dir", body).await.status(),
+        422
+    );
+
+    let mut response = get(&app, "/api/projects").await;
+    let projects: Value = response.body_json().await.unwrap();
+    assert_eq!(projects[0]["directory"], docs.path().to_str().unwrap());
+}
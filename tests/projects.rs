@@ -0,0 +1,87 @@
+mod common;
+
+use common::{get, post_json, register, request, send, TempDir};
+use serde_json::{json, Value};
+use tide::http::Method;
+
+async fn listing(app: &tide::Server<doc_and_pony_show::ServerState>, query: &str) -> Value {
+    let mut response = get(app, &format!("/api/projects{}", query)).await;
+    assert_eq!(response.status(), 200);
+    response.body_json().await.unwrap()
+}
+
+fn names(projects: &Value) -> Vec<&str> {
+    projects
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|project| project["project-name"].as_str().unwrap())
+        .collect()
+}
+
+#[async_std::test]
+async fn pages_through_projects_in_a_stable_order() {
+    let docs = TempDir::new();
+    let app = common::app(&[]).await;
+    for name in &["serde", "dodrio", "bumpalo", "dodrio-js"] {
+        register(&app, name, docs.path(), json!({})).await;
+    }
+
+    assert_eq!(
+        names(&listing(&app, "").await),
+        ["bumpalo", "dodrio", "dodrio-js", "serde"]
+    );
+
+    let page = listing(&app, "?limit=2&offset=1").await;
+    assert_eq!(names(&page["projects"]), ["dodrio", "dodrio-js"]);
+    assert_eq!(page["total"], 4);
+    assert_eq!(page["offset"], 1);
+    assert_eq!(page["limit"], 2);
+
+    let page = listing(&app, "?offset=3").await;
+    assert_eq!(names(&page["projects"]), ["serde"]);
+    assert_eq!(page["limit"], Value::Null);
+
+    let page = listing(&app, "?name_contains=DOD&language=rust").await;
+    assert_eq!(names(&page["projects"]), ["dodrio", "dodrio-js"]);
+    assert_eq!(page["total"], 2);
+}
+
+#[async_std::test]
+async fn leaves_out_languages_behind_auth() {
+    let docs = TempDir::new();
+    let config = TempDir::new();
+    let config_path = config.write(
+        "config.json",
+        &json!({
+            "auth": { "rust": [{ "username": "geoff", "password": "secret" }] },
+        })
+        .to_string(),
+    );
+    let app = common::app(&["--config", config_path.to_str().unwrap()]).await;
+    register(&app, "gated", docs.path(), json!({})).await;
+    let body = json!({ "language": "python", "project-name": "open", "directory": docs.path() });
+    assert!(post_json(&app, "/api/register/dir", body)
+        .await
+        .status()
+        .is_success());
+
+    let projects = listing(&app, "").await;
+    assert_eq!(names(&projects), ["open"]);
+    assert!(!projects.to_string().contains("gated"));
+    assert_eq!(get(&app, "/api/projects?language=rust").await.status(), 401);
+
+    // `geoff:secret`
+    let authorized = |path: &str| {
+        let mut req = request(Method::Get, path);
+        req.insert_header("authorization", "Basic Z2VvZmY6c2VjcmV0");
+        req
+    };
+    let mut response = send(&app, authorized("/api/projects")).await;
+    let projects: Value = response.body_json().await.unwrap();
+    assert_eq!(names(&projects), ["open", "gated"]);
+    let mut response = send(&app, authorized("/api/projects?language=rust")).await;
+    assert_eq!(response.status(), 200);
+    let projects: Value = response.body_json().await.unwrap();
+    assert_eq!(names(&projects), ["gated"]);
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use common::{header, register, request, send, TempDir};
+use serde_json::json;
+use tide::{http::Method, Server};
+
+use doc_and_pony_show::ServerState;
+
+async fn get_from(app: &Server<ServerState>, client: u8) -> (u16, Option<String>) {
+    let mut req = request(Method::Get, "/proj/");
+    req.set_peer_addr(Some(format!("10.0.0.{}:4000", client)));
+    let response = send(app, req).await;
+    (response.status() as u16, header(&response, "retry-after"))
+}
+
+#[async_std::test]
+async fn limits_each_client_and_forgets_the_stalest() {
+    let docs = TempDir::new();
+    docs.write("index.html", "docs");
+    let config = TempDir::new();
+    let config_path = config.write(
+        "config.json",
+        &json!({
+            "rate-limits": {
+                "serve": { "per-second": 0.001, "burst": 2 },
+                "max-clients": 4,
+            },
+        })
+        .to_string(),
+    );
+    let app = common::app(&["--config", config_path.to_str().unwrap()]).await;
+    register(&app, "proj", docs.path(), json!({})).await;
+
+    assert_eq!(get_from(&app, 1).await, (200, None));
+    assert_eq!(get_from(&app, 1).await, (200, None));
+    let (status, retry_after) = get_from(&app, 1).await;
+    assert_eq!(status, 429);
+    let retry_after: u64 = retry_after.unwrap().parse().unwrap();
+    assert!(retry_after >= 999, "{}", retry_after);
+
+    for client in 2..=4 {
+        assert_eq!(get_from(&app, client).await.0, 200);
+    }
+    // Client 1 was seen last, so it is kept, still limited, when making
+    // room for client 5 forgets clients 2 and 3.
+    assert_eq!(get_from(&app, 1).await.0, 429);
+    assert_eq!(get_from(&app, 5).await.0, 200);
+    assert_eq!(get_from(&app, 1).await.0, 429);
+}
@@ -0,0 +1,71 @@
+mod common;
+
+use common::{get, header, register, TempDir};
+use serde_json::json;
+
+#[async_std::test]
+async fn redirects_keep_the_query() {
+    let docs = TempDir::new();
+    docs.write("guide/index.html", "guide");
+    docs.write("with space/index.html", "spaced");
+    docs.write("page.html", "page");
+    let app = common::app(&["--redirect-aliases"]).await;
+    register(&app, "proj", docs.path(), json!({ "aliases": ["old"] })).await;
+
+    for (path, location) in &[
+        ("/proj/guide?lang=en", "/proj/guide/?lang=en"),
+        ("/proj/page.html/?lang=en", "/proj/page.html?lang=en"),
+        ("/proj/with%20space?q=a%20b", "/proj/with%20space/?q=a%20b"),
+        ("/old?q=1", "/proj/?q=1"),
+        ("/old/page.html?q=1", "/proj/page.html?q=1"),
+        (
+            "/old/guide/?q=1&highlight=x",
+            "/proj/guide/?q=1&highlight=x",
+        ),
+    ] {
+        let response = get(&app, path).await;
+        assert_eq!(response.status(), 301, "{}", path);
+        assert_eq!(
+            header(&response, "location").unwrap(),
+            *location,
+            "{}",
+            path
+        );
+    }
+}
+
+#[async_std::test]
+async fn rule_locations_are_percent_encoded() {
+    let docs = TempDir::new();
+    let app = common::app(&[]).await;
+    let rules = json!({
+        "redirects": [{ "from": "/old page", "to": "/new page#top" }],
+    });
+    register(&app, "proj", docs.path(), rules).await;
+
+    let response = get(&app, "/proj/old%20page?q=1").await;
+    assert_eq!(response.status(), 301);
+    let location = header(&response, "location").unwrap();
+    assert!(!location.contains(' '), "{}", location);
+    assert!(location.starts_with("/proj/new%20page"), "{}", location);
+    assert!(location.contains("q=1"), "{}", location);
+}
+
+#[async_std::test]
+async fn never_redirects_off_the_host() {
+    let docs = TempDir::new();
+    docs.write("guide/index.html", "guide");
+    let app = common::app(&[]).await;
+    register(&app, "proj", docs.path(), json!({})).await;
+
+    for path in &[
+        "//evil.example/",
+        "//evil.example/guide",
+        "/proj//evil.example/",
+    ] {
+        let response = get(&app, path).await;
+        if let Some(location) = header(&response, "location") {
+            assert!(!location.starts_with("//"), "{} went to {}", path, location);
+        }
+    }
+}
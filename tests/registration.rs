@@ -0,0 +1,213 @@
+mod common;
+
+use common::{get, post_json, request, send, TempDir};
+use serde_json::{json, Value};
+use tide::{http::Method, Body, Server};
+
+use doc_and_pony_show::{check_config, ServerState};
+
+#[async_std::test]
+async fn registers_by_mode() {
+    let docs = TempDir::new();
+    let app = common::app(&[]).await;
+    let body = json!({
+        "language": "rust",
+        "project-name": "proj",
+        "directory": docs.path(),
+    });
+    let register = |query: &str| format!("/api/register/dir{}", query);
+
+    let response = post_json(&app, &register("?mode=update"), body.clone()).await;
+    assert_eq!(response.status(), 404);
+    let mut response = post_json(&app, &register("?mode=create"), body.clone()).await;
+    assert_eq!(response.status(), 201);
+    assert!(response
+        .body_string()
+        .await
+        .unwrap()
+        .starts_with("Registered proj"));
+    let response = post_json(&app, &register("?mode=create"), body.clone()).await;
+    assert_eq!(response.status(), 409);
+    let mut response = post_json(&app, &register("?mode=update"), body.clone()).await;
+    assert_eq!(response.status(), 200);
+    assert!(response
+        .body_string()
+        .await
+        .unwrap()
+        .starts_with("Updated proj"));
+    for query in &["", "?mode=upsert"] {
+        let response = post_json(&app, &register(query), body.clone()).await;
+        assert_eq!(response.status(), 200, "{}", query);
+    }
+    let response = post_json(&app, &register("?mode=replace"), body).await;
+    assert_eq!(response.status(), 400);
+}
+
+async fn register_dirs(app: &Server<ServerState>, query: &str, projects: Value) -> (u16, Value) {
+    let mut response = post_json(app, &format!("/api/register/dirs{}", query), projects).await;
+    let status = response.status() as u16;
+    (status, response.body_json().await.unwrap())
+}
+
+fn statuses(results: &Value) -> Vec<u64> {
+    results
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|result| result["status"].as_u64().unwrap())
+        .collect()
+}
+
+#[async_std::test]
+async fn registers_in_bulk() {
+    let docs = TempDir::new();
+    docs.write("index.html", "home");
+    let app = common::app(&[]).await;
+    let project = |name: &str| {
+        json!({
+            "language": "rust",
+            "project-name": name,
+            "directory": docs.path(),
+        })
+    };
+    let projects = json!([
+        project("one"),
+        project("bad name"),
+        { "language": "rust" },
+        project("two"),
+        {
+            "language": "rust",
+            "project-name": "missing",
+            "directory": "/does/not/exist",
+        },
+    ]);
+
+    let (status, results) = register_dirs(&app, "?atomic=true", projects.clone()).await;
+    assert_eq!(status, 400);
+    assert_eq!(statuses(&results), [424, 400, 422, 424, 400]);
+    assert_eq!(get(&app, "/one/").await.status(), 404);
+
+    let (status, results) = register_dirs(&app, "", projects).await;
+    assert_eq!(status, 200);
+    assert_eq!(statuses(&results), [201, 400, 422, 201, 400]);
+    assert_eq!(results[0]["project-name"], "one");
+    assert_eq!(results[2].get("project-name"), None);
+    assert_eq!(get(&app, "/one/").await.status(), 200);
+    assert_eq!(get(&app, "/two/").await.status(), 200);
+
+    let batch = json!([project("one"), project("three"), project("three")]);
+    let (status, results) = register_dirs(&app, "?mode=create", batch).await;
+    assert_eq!(status, 200);
+    assert_eq!(statuses(&results), [409, 201, 409]);
+}
+
+#[async_std::test]
+async fn rejects_names_that_fixed_routes_shadow() {
+    let docs = TempDir::new();
+    let config = TempDir::new();
+    let app = common::app(&[]).await;
+    for name in &[
+        "api",
+        "healthz",
+        "metrics",
+        "robots.txt",
+        "favicon.ico",
+        "sitemap.xml",
+        "_assets",
+        "_root",
+    ] {
+        let project = json!({ "language": "rust", "project-name": name, "directory": docs.path() });
+        let response = post_json(&app, "/api/register/dir", project.clone()).await;
+        assert_eq!(response.status(), 400, "{}", name);
+        let aliased = json!({
+            "language": "rust",
+            "project-name": "proj",
+            "directory": docs.path(),
+            "aliases": [name],
+        });
+        let response = post_json(&app, "/api/register/dir", aliased).await;
+        assert_eq!(response.status(), 400, "alias {}", name);
+
+        let config_path =
+            config.write("config.json", &json!({ "projects": [project] }).to_string());
+        let check = check_config(&config_path, false).await.unwrap();
+        assert_eq!(check.errors.len(), 1, "{}", name);
+        assert!(
+            check.errors[0].contains("is reserved"),
+            "{:?}",
+            check.errors
+        );
+    }
+    assert_eq!(get(&app, "/api/projects").await.status(), 200);
+}
+
+#[async_std::test]
+async fn refuses_bodies_over_the_limit() {
+    let docs = TempDir::new();
+    let app = common::app(&["--max-register-body", "200"]).await;
+    let body = |padding: usize| {
+        json!({
+            "language": "rust",
+            "project-name": "proj",
+            "directory": docs.path(),
+            "title": "x".repeat(padding),
+        })
+    };
+
+    let response = post_json(&app, "/api/register/dir", body(300)).await;
+    assert_eq!(response.status(), 413);
+    // Without a Content-Length the limit applies while reading.
+    let mut req = request(Method::Post, "/api/register/dir");
+    let bytes = body(300).to_string().into_bytes();
+    req.set_body(Body::from_reader(async_std::io::Cursor::new(bytes), None));
+    assert_eq!(send(&app, req).await.status(), 413);
+
+    let response = post_json(&app, "/api/register/dir", body(1)).await;
+    assert_eq!(response.status(), 201);
+}
+
+#[async_std::test]
+async fn registers_only_existing_directories() {
+    let docs = TempDir::new();
+    let file = docs.write("file.txt", "not a directory");
+    let app = common::app(&[]).await;
+
+    for (directory, status) in &[
+        (docs.path().join("missing"), 400),
+        (file, 400),
+        (docs.path().to_path_buf(), 201),
+    ] {
+        let body = json!({
+            "language": "rust",
+            "project-name": "proj",
+            "directory": directory,
+        });
+        let response = post_json(&app, "/api/register/dir", body).await;
+        assert_eq!(response.status(), *status, "{:?}", directory);
+    }
+}
+
+#[async_std::test]
+async fn rejects_names_that_would_not_route() {
+    let docs = TempDir::new();
+    let app = common::app(&[]).await;
+
+    for name in &["", ".", "..", "...", "a/b", "../proj", "Proj", "pr oj"] {
+        for (language, project_name) in &[("rust", *name), (*name, "proj")] {
+            let body = json!({
+                "language": language,
+                "project-name": project_name,
+                "directory": docs.path(),
+            });
+            let response = post_json(&app, "/api/register/dir", body).await;
+            assert_eq!(response.status(), 400, "{:?}/{:?}", language, project_name);
+        }
+    }
+    let body = json!({
+        "language": "rust",
+        "project-name": "my_proj-2.0",
+        "directory": docs.path(),
+    });
+    let response = post_json(&app, "/api/register/dir", body).await;
+    assert_eq!(response.status(), 201);
+}
@@ -0,0 +1,44 @@
+mod common;
+
+use common::{get, request, send, TempDir};
+use serde_json::json;
+use tide::http::Method;
+
+#[async_std::test]
+async fn serves_root_files_on_every_host() {
+    let root = TempDir::new();
+    root.write("favicon.ico", "icon");
+    root.write("robots.txt", "User-agent: *\nDisallow: /private/\n");
+    let config = TempDir::new();
+    let config_path = config.write(
+        "config.json",
+        &json!({ "root-files": root.path() }).to_string(),
+    );
+    let app = common::app(&["--config", config_path.to_str().unwrap()]).await;
+
+    for host in &["rust.docs", "127.0.10.1:8080"] {
+        let mut req = request(Method::Get, "/favicon.ico");
+        req.insert_header("host", *host);
+        let mut response = send(&app, req).await;
+        assert_eq!(response.status(), 200, "{}", host);
+        assert_eq!(response.body_string().await.unwrap(), "icon");
+    }
+    let mut response = get(&app, "/robots.txt").await;
+    assert_eq!(
+        response.body_string().await.unwrap(),
+        "User-agent: *\nDisallow: /private/\n"
+    );
+}
+
+#[async_std::test]
+async fn answers_a_plain_404_without_root_files() {
+    let app = common::app(&[]).await;
+    let mut req = request(Method::Get, "/favicon.ico");
+    req.remove_header("host");
+    assert_eq!(send(&app, req).await.status(), 404);
+    let mut response = get(&app, "/robots.txt").await;
+    assert_eq!(
+        response.body_string().await.unwrap(),
+        "User-agent: *\nDisallow:\n"
+    );
+}
@@ -0,0 +1,374 @@
+mod common;
+
+use std::os::unix::fs::symlink;
+
+use common::{get, header, post_json, register, request, send, TempDir};
+use serde_json::json;
+use tide::http::Method;
+
+use doc_and_pony_show::{Options, ServerState};
+
+#[async_std::test]
+async fn serves_registered_files() {
+    let docs = TempDir::new();
+    docs.write("index.html", "<h1>proj</h1>");
+    let app = common::app(&[]).await;
+    register(&app, "proj", docs.path(), json!({})).await;
+
+    let mut response = get(&app, "/proj/").await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.body_string().await.unwrap(), "<h1>proj</h1>");
+    assert_eq!(get(&app, "/proj/missing.html").await.status(), 404);
+    assert_eq!(get(&app, "/other/").await.status(), 404);
+}
+
+#[async_std::test]
+async fn refuses_paths_outside_the_project() {
+    let root = TempDir::new();
+    root.write("secret.txt", "secret");
+    let docs = root.write("docs/index.html", "docs");
+    let docs = docs.parent().unwrap();
+    let app = common::app(&[]).await;
+    register(&app, "proj", docs, json!({})).await;
+
+    for path in &["/proj/../secret.txt", "/proj/sub%2F..%2F..%2Fsecret.txt"] {
+        let mut response = get(&app, path).await;
+        let body = response.body_string().await.unwrap();
+        assert_ne!(response.status(), 200, "{} was served", path);
+        assert!(!body.contains("secret"), "{}", path);
+    }
+}
+
+#[async_std::test]
+async fn explains_hosts_at_the_root_of_one_without_a_language() {
+    let docs = TempDir::new();
+    docs.write("index.html", "docs");
+    let app = common::app(&[]).await;
+    register(&app, "proj", docs.path(), json!({})).await;
+
+    let mut req = request(Method::Get, "/");
+    req.insert_header("host", "127.0.10.1:8080");
+    let mut response = send(&app, req).await;
+    assert_eq!(response.status(), 200);
+    let body = response.body_string().await.unwrap();
+    assert!(body.contains("rust.docs"));
+    assert!(body.contains("href=\"//rust.docs:8080/\""));
+
+    let mut req = request(Method::Get, "/proj/");
+    req.remove_header("host");
+    assert_eq!(send(&app, req).await.status(), 400);
+
+    let app = common::app(&["--no-landing-page"]).await;
+    let mut req = request(Method::Get, "/");
+    req.insert_header("host", "127.0.10.1:8080");
+    assert_eq!(send(&app, req).await.status(), 400);
+}
+
+#[async_std::test]
+async fn takes_the_host_the_nearest_trusted_proxy_forwarded() {
+    let docs = TempDir::new();
+    docs.write("index.html", "rust docs");
+    let evil = TempDir::new();
+    evil.write("index.html", "evil docs");
+    let app = common::app(&["--trust-proxy", "10.0.0.1"]).await;
+    register(&app, "proj", docs.path(), json!({})).await;
+    let body = json!({ "language": "evil", "project-name": "proj", "directory": evil.path() });
+    assert!(post_json(&app, "/api/register/dir", body)
+        .await
+        .status()
+        .is_success());
+
+    let mut req = request(Method::Get, "/proj/");
+    req.set_peer_addr(Some("10.0.0.1:4000"));
+    req.insert_header("host", "127.0.0.1:8080");
+    req.insert_header("x-forwarded-host", "evil.docs, rust.docs");
+    let mut response = send(&app, req).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.body_string().await.unwrap(), "rust docs");
+}
+
+#[async_std::test]
+async fn lists_languages_and_projects_on_the_landing_page() {
+    let docs = TempDir::new();
+    docs.write("index.html", "docs");
+    let config = TempDir::new();
+    let template = config.write(
+        "landing.html",
+        "<h1>Our docs</h1>\n{{languages}}<footer>hi</footer>",
+    );
+    let app = common::app(&[
+        "--canonical-base",
+        "https://docs.example.com",
+        "--landing-template",
+        template.to_str().unwrap(),
+    ])
+    .await;
+    register(&app, "proj", docs.path(), json!({})).await;
+    register(&app, "other", docs.path(), json!({})).await;
+
+    let mut req = request(Method::Get, "/");
+    req.insert_header("host", "docs.example.com");
+    let mut response = send(&app, req).await;
+    assert_eq!(response.status(), 200);
+    let body = response.body_string().await.unwrap();
+    assert!(body.starts_with("<h1>Our docs</h1>\n<ul>\n"), "{}", body);
+    assert!(body.ends_with("</ul>\n<footer>hi</footer>"), "{}", body);
+    let rust = body
+        .find("href=\"https://rust.docs.example.com/\"")
+        .unwrap();
+    let other = body
+        .find("href=\"https://rust.docs.example.com/other/\"")
+        .unwrap();
+    let proj = body
+        .find("href=\"https://rust.docs.example.com/proj/\"")
+        .unwrap();
+    assert!(rust < other && other < proj);
+
+    let missing = config.write("plain.html", "no placeholder");
+    let options = Options::parse(
+        vec![
+            "--landing-template".to_string(),
+            missing.to_str().unwrap().to_string(),
+        ],
+        |_| None,
+    )
+    .unwrap();
+    assert!(ServerState::from_options(options).await.is_err());
+}
+
+#[async_std::test]
+async fn rejects_parent_segments() {
+    let root = TempDir::new();
+    root.write("secret.txt", "secret");
+    let docs = root.write("docs/index.html", "docs");
+    let docs = docs.parent().unwrap();
+    let app = common::app(&[]).await;
+    register(&app, "proj", docs, json!({})).await;
+
+    // Plain and `%2e%2e` segments are resolved while parsing the URL, so
+    // only those hidden behind an encoded slash reach the project.
+    assert_eq!(get(&app, "/proj/../secret.txt").await.status(), 404);
+    for path in &["/proj/..%2fsecret.txt", "/proj/%2e%2e%2Fsecret.txt"] {
+        assert_eq!(get(&app, path).await.status(), 400, "{}", path);
+    }
+
+    let app = common::app(&["--clamp-parent-segments"]).await;
+    register(&app, "proj", docs, json!({})).await;
+    assert_eq!(get(&app, "/proj/a%2f..%2findex.html").await.status(), 200);
+    // A `..` at the root stays there rather than reaching `secret.txt`.
+    assert_eq!(get(&app, "/proj/..%2fsecret.txt").await.status(), 404);
+    let mut response = get(&app, "/proj/a%2f..%2f..%2f..%2findex.html").await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.body_string().await.unwrap(), "docs");
+}
+
+#[async_std::test]
+async fn sends_configured_pages_with_the_403() {
+    let outside = TempDir::new();
+    outside.write("secret.txt", "secret");
+    let docs = TempDir::new();
+    docs.write("403.html", "<h1>Not yours</h1>");
+    symlink(
+        outside.path().join("secret.txt"),
+        docs.path().join("link.txt"),
+    )
+    .unwrap();
+    let config = TempDir::new();
+    let global = config.write("forbidden.html", "<h1>Forbidden</h1>");
+
+    let app = common::app(&[]).await;
+    register(&app, "proj", docs.path(), json!({})).await;
+    let mut response = get(&app, "/proj/link.txt").await;
+    assert_eq!(response.status(), 403);
+    assert_eq!(response.body_string().await.unwrap(), "");
+
+    let app = common::app(&["--forbidden-page", global.to_str().unwrap()]).await;
+    register(&app, "proj", docs.path(), json!({})).await;
+    register(&app, "own", docs.path(), json!({ "forbidden": "403.html" })).await;
+    let mut response = get(&app, "/proj/link.txt").await;
+    assert_eq!(response.status(), 403);
+    assert_eq!(response.content_type().unwrap().essence(), "text/html");
+    assert_eq!(response.body_string().await.unwrap(), "<h1>Forbidden</h1>");
+    let mut response = get(&app, "/own/link.txt").await;
+    assert_eq!(response.status(), 403);
+    assert_eq!(response.body_string().await.unwrap(), "<h1>Not yours</h1>");
+
+    let response = post_json(
+        &app,
+        "/api/register/dir",
+        json!({
+            "language": "rust",
+            "project-name": "bad",
+            "directory": docs.path(),
+            "forbidden": "../403.html",
+        }),
+    )
+    .await;
+    assert_eq!(response.status(), 400);
+}
+
+#[async_std::test]
+async fn rejects_paths_too_long_or_deep() {
+    let docs = TempDir::new();
+    docs.write("a/b/page.html", "page");
+    let app = common::app(&["--max-path-length", "64", "--max-path-depth", "4"]).await;
+    register(&app, "proj", docs.path(), json!({})).await;
+
+    assert_eq!(get(&app, "/proj/a/b/page.html").await.status(), 200);
+    assert_eq!(get(&app, "/proj/a/b/c/page.html").await.status(), 400);
+    assert_eq!(get(&app, "/proj/a%2fb%2Fc/page.html").await.status(), 400);
+    let long = format!("/proj/{}.html", "x".repeat(60));
+    assert_eq!(get(&app, &long).await.status(), 414);
+}
+
+#[async_std::test]
+async fn serves_the_file_as_opened_when_it_is_replaced() {
+    let docs = TempDir::new();
+    docs.write("page.html", "the old page");
+    let app = common::app(&[]).await;
+    register(&app, "proj", docs.path(), json!({})).await;
+
+    let mut response = get(&app, "/proj/page.html").await;
+    // A rebuild writing a new file over the old one, before the body is read.
+    let replacement = docs.write("page.html.new", "the new page, which is longer");
+    std::fs::rename(replacement, docs.path().join("page.html")).unwrap();
+    assert_eq!(response.len(), Some(12));
+    assert_eq!(response.body_string().await.unwrap(), "the old page");
+}
+
+#[async_std::test]
+async fn fails_the_body_of_a_file_truncated_while_it_is_read() {
+    let docs = TempDir::new();
+    docs.write("page.html", "the whole page");
+    let app = common::app(&[]).await;
+    register(&app, "proj", docs.path(), json!({})).await;
+
+    let mut response = get(&app, "/proj/page.html").await;
+    docs.write("page.html", "half");
+    assert_eq!(response.len(), Some(14));
+    assert!(response.body_string().await.is_err());
+}
+
+#[async_std::test]
+async fn refuses_symlinks_out_of_the_project() {
+    let docs = TempDir::new();
+    docs.write("index.html", "docs");
+    symlink("/etc/passwd", docs.path().join("passwd")).unwrap();
+    let app = common::app(&[]).await;
+    register(&app, "proj", docs.path(), json!({})).await;
+
+    let mut response = get(&app, "/proj/passwd").await;
+    assert_eq!(response.status(), 403);
+    assert!(!response.body_string().await.unwrap().contains("root:"));
+    assert_eq!(get(&app, "/proj/").await.status(), 200);
+    assert_eq!(get(&app, "/proj/missing.html").await.status(), 404);
+}
+
+#[async_std::test]
+async fn decodes_segments_before_resolving() {
+    let root = TempDir::new();
+    root.write("secret.txt", "secret");
+    let docs = root.write("docs/with space.html", "spaced");
+    let app = common::app(&[]).await;
+    register(&app, "proj", docs.parent().unwrap(), json!({})).await;
+
+    let mut response = get(&app, "/proj/with%20space.html").await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.body_string().await.unwrap(), "spaced");
+    for path in &[
+        "/proj/%2e%2e%2fsecret.txt",
+        "/proj/sub%2F%2E%2E%2F%2E%2E%2Fsecret.txt",
+    ] {
+        let status = get(&app, path).await.status();
+        assert_eq!(status, 400, "{}", path);
+    }
+    let mut response = get(&app, "/proj/%2e%2e/%2e%2e/etc/passwd").await;
+    assert_ne!(response.status(), 200);
+    assert!(!response.body_string().await.unwrap().contains("root:"));
+}
+
+#[async_std::test]
+async fn answers_matching_etags_with_not_modified() {
+    let docs = TempDir::new();
+    docs.write("page.html", "page");
+    let app = common::app(&[]).await;
+    register(&app, "proj", docs.path(), json!({})).await;
+
+    let response = get(&app, "/proj/page.html").await;
+    let etag = header(&response, "etag").expect("an ETag");
+    assert!(etag.starts_with("W/"), "{}", etag);
+    let mut req = request(Method::Get, "/proj/page.html");
+    req.insert_header("if-none-match", etag.as_str());
+    let mut response = send(&app, req).await;
+    assert_eq!(response.status(), 304);
+    assert_eq!(response.body_string().await.unwrap(), "");
+
+    let mut req = request(Method::Get, "/proj/page.html");
+    req.insert_header("if-none-match", "W/\"stale\"");
+    assert_eq!(send(&app, req).await.status(), 200);
+}
+
+#[async_std::test]
+async fn takes_the_language_from_the_host_without_its_port() {
+    let docs = TempDir::new();
+    docs.write("index.html", "docs");
+    let app = common::app(&[]).await;
+    register(&app, "proj", docs.path(), json!({})).await;
+
+    for (host, status) in &[
+        ("rust.docs", 200),
+        ("rust.docs:8080", 200),
+        ("127.0.0.1", 400),
+        ("127.0.0.1:8080", 400),
+        ("[::1]:8080", 400),
+    ] {
+        let mut req = request(Method::Get, "/proj/");
+        req.insert_header("host", *host);
+        assert_eq!(send(&app, req).await.status(), *status, "{}", host);
+    }
+}
+
+#[async_std::test]
+async fn head_matches_get_without_the_body() {
+    let docs = TempDir::new();
+    docs.write("page.html", "<p>page</p>");
+    let app = common::app(&[]).await;
+    register(&app, "proj", docs.path(), json!({})).await;
+
+    for (path, status) in &[("/proj/page.html", 200), ("/proj/missing.html", 404)] {
+        let got = get(&app, path).await;
+        let mut head = send(&app, request(Method::Head, path)).await;
+        assert_eq!(got.status(), *status, "{}", path);
+        assert_eq!(head.status(), *status, "{}", path);
+        for name in &["content-type", "etag", "last-modified"] {
+            assert_eq!(header(&head, name), header(&got, name), "{} {}", path, name);
+        }
+        // The encoder writes Content-Length from the body's length.
+        assert_eq!(head.len(), got.len(), "{}", path);
+        assert_eq!(head.body_string().await.unwrap(), "", "{}", path);
+    }
+    let head = send(&app, request(Method::Head, "/proj/page.html")).await;
+    assert_eq!(head.len(), Some(11));
+    assert!(header(&head, "etag").is_some());
+}
+
+#[async_std::test]
+async fn hides_dotfiles_unless_asked() {
+    let docs = TempDir::new();
+    docs.write(".git/config", "[core]");
+    docs.write(".hidden.html", "hidden");
+    docs.write(".well-known/security.txt", "Contact: x");
+    let app = common::app(&[]).await;
+    register(&app, "hidden", docs.path(), json!({})).await;
+    register(&app, "shown", docs.path(), json!({ "dotfiles": true })).await;
+
+    for path in &["/hidden/.git/config", "/hidden/.hidden.html"] {
+        let status = get(&app, path).await.status();
+        assert_eq!(status, 404, "{}", path);
+    }
+    assert_eq!(
+        get(&app, "/hidden/.well-known/security.txt").await.status(),
+        200
+    );
+    assert_eq!(get(&app, "/shown/.git/config").await.status(), 200);
+}
@@ -0,0 +1,55 @@
+mod common;
+
+use std::os::unix::fs::symlink;
+
+use common::{get, register, TempDir};
+use serde_json::{json, Value};
+
+#[async_std::test]
+async fn describes_pages_without_serving_them() {
+    let docs = TempDir::new();
+    docs.write("guide/index.html", "guide");
+    docs.write("with space.css", "body {}");
+    let app = common::app(&[]).await;
+    register(&app, "proj", docs.path(), json!({})).await;
+
+    let mut response = get(&app, "/api/projects/proj/stat?path=guide/").await;
+    assert_eq!(response.status(), 200);
+    let stat: Value = response.body_json().await.unwrap();
+    assert_eq!(stat["exists"], true);
+    assert_eq!(stat["size"], 5);
+    assert_eq!(stat["content-type"], "text/html;charset=utf-8");
+    assert!(stat["modified"].as_str().unwrap().ends_with('Z'));
+
+    let mut response = get(&app, "/api/projects/proj/stat?path=/with%2520space.css").await;
+    assert_eq!(response.status(), 200);
+    let stat: Value = response.body_json().await.unwrap();
+    assert_eq!(stat["size"], 7);
+    assert_eq!(stat["content-type"], "text/css;charset=utf-8");
+}
+
+#[async_std::test]
+async fn keeps_the_protections_of_serving() {
+    let root = TempDir::new();
+    root.write("secret.txt", "secret");
+    let docs = root.write("docs/.git/config", "[core]");
+    let docs = docs.parent().unwrap().parent().unwrap();
+    symlink("/etc/passwd", docs.join("passwd")).unwrap();
+    let app = common::app(&[]).await;
+    register(&app, "proj", docs, json!({})).await;
+
+    for (path, status) in &[
+        ("missing.html", 404),
+        (".git/config", 404),
+        ("..%252fsecret.txt", 400),
+        ("passwd", 403),
+    ] {
+        let url = format!("/api/projects/proj/stat?path={}", path);
+        assert_eq!(get(&app, &url).await.status(), *status, "{}", path);
+    }
+    assert_eq!(get(&app, "/api/projects/proj/stat").await.status(), 400);
+    assert_eq!(
+        get(&app, "/api/projects/other/stat?path=a").await.status(),
+        404
+    );
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use common::{header, register, request, send, TempDir};
+use serde_json::json;
+use tide::http::{Method, Response};
+
+/// `geoff:secret`, which the config below accepts for `rust`.
+const CREDENTIALS: &str = "Basic Z2VvZmY6c2VjcmV0";
+
+fn vary(response: &Response) -> Vec<String> {
+    let mut names: Vec<String> = header(response, "vary")
+        .unwrap_or_default()
+        .split(',')
+        .map(|name| name.trim().to_ascii_lowercase())
+        .filter(|name| !name.is_empty())
+        .collect();
+    names.sort();
+    names
+}
+
+async fn gated_app(config: &TempDir) -> tide::Server<doc_and_pony_show::ServerState> {
+    let config_path = config.write(
+        "config.json",
+        &json!({
+            "auth": {
+                "rust": [{
+                    "username": "geoff",
+                    "password-sha256":
+                        "2bb80d537b1da3e38bd30361aa855686bde0eacd7162fef6a25fe97bf527a25b",
+                }],
+            },
+        })
+        .to_string(),
+    );
+    common::app(&["--config", config_path.to_str().unwrap()]).await
+}
+
+#[async_std::test]
+async fn lists_encoding_and_credentials_for_gated_pages() {
+    let config = TempDir::new();
+    let docs = TempDir::new();
+    docs.write("page.html", &"<p>gated</p>\n".repeat(200));
+    let app = gated_app(&config).await;
+    register(&app, "proj", docs.path(), json!({})).await;
+
+    let mut req = request(Method::Get, "/proj/page.html");
+    req.insert_header("accept-encoding", "gzip");
+    req.insert_header("authorization", CREDENTIALS);
+    let response = send(&app, req).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        header(&response, "content-encoding").as_deref(),
+        Some("gzip")
+    );
+    assert_eq!(vary(&response), ["accept-encoding", "authorization"]);
+
+    let challenge = send(&app, request(Method::Get, "/proj/page.html")).await;
+    assert_eq!(challenge.status(), 401);
+    assert_eq!(vary(&challenge), ["authorization"]);
+}
+
+#[async_std::test]
+async fn lists_only_what_applied() {
+    let docs = TempDir::new();
+    docs.write("small.html", "small");
+    let app = common::app(&[]).await;
+    register(&app, "proj", docs.path(), json!({})).await;
+
+    let found = send(&app, request(Method::Get, "/proj/small.html")).await;
+    assert_eq!(found.status(), 200);
+    assert!(vary(&found).is_empty(), "{:?}", vary(&found));
+
+    let missing = send(&app, request(Method::Get, "/proj/missing.html")).await;
+    assert_eq!(missing.status(), 404);
+    assert_eq!(vary(&missing), ["accept-language"]);
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use common::{get, register, TempDir};
+use serde_json::json;
+
+/// The version listing and the status of `latest/` after registering each
+/// of `labels` as a version of one project.
+async fn versions_of(labels: &[&str]) -> (serde_json::Value, u16) {
+    let docs = TempDir::new();
+    docs.write("index.html", "docs");
+    let app = common::app(&[]).await;
+    for label in labels {
+        register(&app, "proj", docs.path(), json!({ "version": label })).await;
+    }
+    let mut listing = get(&app, "/api/projects/proj/versions").await;
+    assert_eq!(listing.status(), 200);
+    let listing = listing.body_json().await.unwrap();
+    (listing, get(&app, "/proj/latest/").await.status() as u16)
+}
+
+#[async_std::test]
+async fn latest_prefers_releases() {
+    let (listing, status) = versions_of(&["nightly", "2.0.0-rc1", "1.0.0"]).await;
+    assert_eq!(listing["latest"], "1.0.0");
+    assert_eq!(status, 200);
+    let order: Vec<_> = listing["versions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|version| version["version"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(order, ["1.0.0", "2.0.0-rc1", "nightly"]);
+}
+
+#[async_std::test]
+async fn latest_falls_back_to_pre_releases() {
+    let (listing, status) = versions_of(&["nightly", "2.0.0-rc1"]).await;
+    assert_eq!(listing["latest"], "2.0.0-rc1");
+    assert_eq!(status, 200);
+}
+
+#[async_std::test]
+async fn latest_is_never_a_label() {
+    let (listing, status) = versions_of(&["nightly"]).await;
+    assert_eq!(listing["latest"], serde_json::Value::Null);
+    assert_eq!(status, 404);
+}